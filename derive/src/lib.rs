@@ -0,0 +1,364 @@
+use proc_macro::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Expr, Field, Fields, Index};
+
+const FNV_OFFSET: u32 = 0x811c9dc5;
+const FNV_PRIME: u32 = 0x01000193;
+
+fn fnv1a(bytes: &[u8]) -> u32 {
+  bytes.iter().fold(FNV_OFFSET, |hash, b| (hash ^ *b as u32).wrapping_mul(FNV_PRIME))
+}
+
+// Whether the field is marked `#[config(skip)]`
+fn is_skipped(field: &Field) -> syn::Result<bool> {
+  Ok(attrs(field)?.skip)
+}
+
+// A field's `#[config(...)]` attributes, shared by every derive so that each accepts the others'
+#[derive(Default)]
+struct Attrs {
+  skip: bool,
+  secret: bool,
+  default: Option<Expr>,
+  key: Option<u16>,
+}
+
+fn attrs(field: &Field) -> syn::Result<Attrs> {
+  let mut attrs = Attrs::default();
+  for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("config")) {
+    attr.parse_nested_meta(|meta| {
+      if meta.path.is_ident("skip") {
+        attrs.skip = true;
+        Ok(())
+      } else if meta.path.is_ident("secret") {
+        attrs.secret = true;
+        Ok(())
+      } else if meta.path.is_ident("default") {
+        attrs.default = Some(meta.value()?.parse()?);
+        Ok(())
+      } else if meta.path.is_ident("key") {
+        attrs.key = Some(meta.value()?.parse::<syn::LitInt>()?.base10_parse()?);
+        Ok(())
+      } else {
+        Err(meta.error("unknown config attribute"))
+      }
+    })?;
+  }
+  Ok(attrs)
+}
+
+fn describe_fields(fields: &Fields, out: &mut String) -> syn::Result<()> {
+  out.push('(');
+  for field in fields.iter() {
+    if !is_skipped(field)? {
+      out.push_str(&field.ty.to_token_stream().to_string());
+      out.push(',');
+    }
+  }
+  out.push(')');
+  Ok(())
+}
+
+/// Derive `grapple_config::Schema`, hashing the type of every persisted field, in order. Field and type names
+/// themselves don't contribute, so either can be renamed without changing the hash, but marking a field
+/// `#[config(skip)]` does.
+#[proc_macro_derive(Schema, attributes(config))]
+pub fn derive_schema(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+
+  let mut layout = String::new();
+  let described = match &input.data {
+    Data::Struct(s) => describe_fields(&s.fields, &mut layout),
+    Data::Enum(e) => e.variants.iter().try_for_each(|variant| {
+      describe_fields(&variant.fields, &mut layout)?;
+      layout.push('|');
+      Ok(())
+    }),
+    Data::Union(_) => Err(syn::Error::new_spanned(&input.ident, "Schema can't be derived for unions")),
+  };
+  if let Err(e) = described {
+    return e.to_compile_error().into();
+  }
+
+  // 0 means "no schema", so it can't be the result of a real one
+  let hash = match fnv1a(layout.as_bytes()) {
+    0 => 1,
+    hash => hash,
+  };
+
+  let name = &input.ident;
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+  quote! {
+    impl #impl_generics ::grapple_config::Schema for #name #ty_generics #where_clause {
+      const SCHEMA_HASH: u32 = #hash;
+    }
+  }.into()
+}
+
+/// Derive binmarshal's `Marshal` and `Demarshal` for a struct, in place of binmarshal's own derives,
+/// serialising every field in order except those marked `#[config(skip)]`. Skipped fields aren't stored, and
+/// are filled with `Default::default()` on read, e.g. for runtime-only caches.
+#[proc_macro_derive(Persist, attributes(config))]
+pub fn derive_persist(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  match persist(&input) {
+    Ok(tokens) => tokens.into(),
+    Err(e) => e.to_compile_error().into(),
+  }
+}
+
+fn persist(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+  let fields = match &input.data {
+    Data::Struct(s) => &s.fields,
+    _ => return Err(syn::Error::new_spanned(&input.ident, "Persist can only be derived for structs")),
+  };
+
+  let mut writes = Vec::new();
+  let mut reads = Vec::new();
+  for (i, field) in fields.iter().enumerate() {
+    let ty = &field.ty;
+    let member = match &field.ident {
+      Some(ident) => quote!(#ident),
+      None => {
+        let index = Index::from(i);
+        quote!(#index)
+      },
+    };
+
+    if is_skipped(field)? {
+      reads.push(quote!(#member: ::core::default::Default::default()));
+    } else {
+      writes.push(quote!(<#ty as ::grapple_config::__binmarshal::Marshal<()>>::write(&self.#member, writer, ())?;));
+      reads.push(quote!(#member: <#ty as ::grapple_config::__binmarshal::Demarshal<'dm, ()>>::read(view, ())?));
+    }
+  }
+
+  let name = &input.ident;
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+  let mut dm_generics = input.generics.clone();
+  dm_generics.params.insert(0, parse_quote!('dm));
+  let (dm_impl_generics, _, _) = dm_generics.split_for_impl();
+
+  Ok(quote! {
+    impl #impl_generics ::grapple_config::__binmarshal::Marshal<()> for #name #ty_generics #where_clause {
+      fn write<W: ::grapple_config::__binmarshal::rw::BitWriter>(&self, writer: &mut W, _ctx: ()) -> ::core::result::Result<(), ::grapple_config::__binmarshal::MarshalError> {
+        #(#writes)*
+        Ok(())
+      }
+    }
+
+    impl #dm_impl_generics ::grapple_config::__binmarshal::Demarshal<'dm, ()> for #name #ty_generics #where_clause {
+      fn read(view: &mut ::grapple_config::__binmarshal::rw::BitView<'dm>, _ctx: ()) -> ::core::result::Result<Self, ::grapple_config::__binmarshal::MarshalError> {
+        Ok(Self { #(#reads),* })
+      }
+    }
+  })
+}
+
+/// Derive `grapple_config::kv::Entries` for a struct with named fields, storing each field, except those
+/// marked `#[config(skip)]`, under a key hashed from its name, or the one given by `#[config(key = ...)]`, e.g.
+/// to keep a renamed field's key.
+#[proc_macro_derive(Entries, attributes(config))]
+pub fn derive_entries(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  match entries(&input) {
+    Ok(tokens) => tokens.into(),
+    Err(e) => e.to_compile_error().into(),
+  }
+}
+
+fn entries(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+  let fields = match &input.data {
+    Data::Struct(s) if matches!(s.fields, Fields::Named(_)) => &s.fields,
+    _ => return Err(syn::Error::new_spanned(&input.ident, "Entries can only be derived for structs with named fields")),
+  };
+
+  let mut keys: Vec<(u16, &syn::Ident)> = Vec::new();
+  for field in fields.iter() {
+    let attrs = attrs(field)?;
+    if attrs.skip {
+      continue;
+    }
+    let ident = field.ident.as_ref().unwrap();
+    let key = attrs.key.unwrap_or_else(|| {
+      let hash = fnv1a(ident.to_string().as_bytes());
+      ((hash >> 16) ^ (hash & 0xFFFF)) as u16
+    });
+    if let Some((_, other)) = keys.iter().find(|(k, _)| *k == key) {
+      return Err(syn::Error::new_spanned(ident, format!("the key for this field collides with `{}`, rename one of them or give it a `#[config(key = ...)]`", other)));
+    }
+    keys.push((key, ident));
+  }
+
+  let key_list = keys.iter().map(|(key, _)| key);
+  let writes = keys.iter().map(|(key, ident)| quote!(#key => ::grapple_config::kv::__write_field(&self.#ident)));
+  let reads = keys.iter().map(|(key, ident)| quote!(#key => self.#ident = ::grapple_config::kv::__read_field(bytes)?));
+
+  let name = &input.ident;
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+  Ok(quote! {
+    impl #impl_generics ::grapple_config::kv::Entries for #name #ty_generics #where_clause {
+      const KEYS: &'static [u16] = &[#(#key_list),*];
+
+      fn write_entry(&self, key: u16) -> ::core::result::Result<::grapple_config::kv::__Vec<u8>, ::grapple_config::__binmarshal::MarshalError> {
+        match key {
+          #(#writes,)*
+          _ => Ok(::grapple_config::kv::__Vec::new()),
+        }
+      }
+
+      fn read_entry(&mut self, key: u16, bytes: &[u8]) -> ::core::result::Result<(), ::grapple_config::__binmarshal::MarshalError> {
+        match key {
+          #(#reads,)*
+          _ => (),
+        }
+        Ok(())
+      }
+    }
+  })
+}
+
+/// Derive `grapple_config::size::MaxSize` for a struct, adding up the bounds of every field not marked
+/// `#[config(skip)]`. Bit-packed fields are counted at their full size, so the bound is conservative.
+#[proc_macro_derive(MaxSize, attributes(config))]
+pub fn derive_max_size(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  match max_size(&input) {
+    Ok(tokens) => tokens.into(),
+    Err(e) => e.to_compile_error().into(),
+  }
+}
+
+fn max_size(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+  let fields = match &input.data {
+    Data::Struct(s) => &s.fields,
+    _ => return Err(syn::Error::new_spanned(&input.ident, "MaxSize can only be derived for structs")),
+  };
+
+  let mut lens = Vec::new();
+  for field in fields.iter() {
+    if !is_skipped(field)? {
+      let ty = &field.ty;
+      lens.push(quote!(<#ty as ::grapple_config::size::MaxSize>::MAX_SERIALISED_LEN));
+    }
+  }
+
+  let name = &input.ident;
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+  Ok(quote! {
+    impl #impl_generics ::grapple_config::size::MaxSize for #name #ty_generics #where_clause {
+      const MAX_SERIALISED_LEN: usize = 0 #(+ #lens)*;
+    }
+  })
+}
+
+/// Derive `grapple_config::secret::Secrets` for a struct, splitting the fields marked `#[config(secret)]` from
+/// the rest so that `SecretFieldsMarshal` only encrypts those. Fields marked `#[config(skip)]` aren't stored,
+/// as with `Persist`. `Persist` itself stores secret fields like any other.
+#[proc_macro_derive(Secrets, attributes(config))]
+pub fn derive_secrets(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  match secrets(&input) {
+    Ok(tokens) => tokens.into(),
+    Err(e) => e.to_compile_error().into(),
+  }
+}
+
+fn secrets(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+  let fields = match &input.data {
+    Data::Struct(s) => &s.fields,
+    _ => return Err(syn::Error::new_spanned(&input.ident, "Secrets can only be derived for structs")),
+  };
+
+  let mut public_writes = Vec::new();
+  let mut secret_writes = Vec::new();
+  let mut reads = Vec::new();
+  for (i, field) in fields.iter().enumerate() {
+    let ty = &field.ty;
+    let member = match &field.ident {
+      Some(ident) => quote!(#ident),
+      None => {
+        let index = Index::from(i);
+        quote!(#index)
+      },
+    };
+
+    let Attrs { skip, secret, .. } = attrs(field)?;
+    if skip {
+      reads.push(quote!(#member: ::core::default::Default::default()));
+      continue;
+    }
+
+    let (writes, view) = match secret {
+      true => (&mut secret_writes, quote!(secrets)),
+      false => (&mut public_writes, quote!(public)),
+    };
+    writes.push(quote!(<#ty as ::grapple_config::__binmarshal::Marshal<()>>::write(&self.#member, writer, ())?;));
+    reads.push(quote!(#member: <#ty as ::grapple_config::__binmarshal::Demarshal<'dm, ()>>::read(#view, ())?));
+  }
+
+  let name = &input.ident;
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+  Ok(quote! {
+    impl #impl_generics ::grapple_config::secret::Secrets for #name #ty_generics #where_clause {
+      fn write_public<W: ::grapple_config::__binmarshal::rw::BitWriter>(&self, writer: &mut W) -> ::core::result::Result<(), ::grapple_config::__binmarshal::MarshalError> {
+        #(#public_writes)*
+        Ok(())
+      }
+
+      fn write_secrets<W: ::grapple_config::__binmarshal::rw::BitWriter>(&self, writer: &mut W) -> ::core::result::Result<(), ::grapple_config::__binmarshal::MarshalError> {
+        #(#secret_writes)*
+        Ok(())
+      }
+
+      fn read_split<'dm>(public: &mut ::grapple_config::__binmarshal::rw::BitView<'dm>, secrets: &mut ::grapple_config::__binmarshal::rw::BitView<'dm>) -> ::core::result::Result<Self, ::grapple_config::__binmarshal::MarshalError> {
+        Ok(Self { #(#reads),* })
+      }
+    }
+  })
+}
+
+/// Derive `Default` for a struct, with each field marked `#[config(default = <expr>)]` set to `<expr>`, and
+/// the rest to their own `Default::default()`, e.g. for the configuration written to blank storage.
+#[proc_macro_derive(ConfigDefault, attributes(config))]
+pub fn derive_config_default(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  match config_default(&input) {
+    Ok(tokens) => tokens.into(),
+    Err(e) => e.to_compile_error().into(),
+  }
+}
+
+fn config_default(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+  let fields = match &input.data {
+    Data::Struct(s) => &s.fields,
+    _ => return Err(syn::Error::new_spanned(&input.ident, "ConfigDefault can only be derived for structs")),
+  };
+
+  let mut inits = Vec::new();
+  for (i, field) in fields.iter().enumerate() {
+    let member = match &field.ident {
+      Some(ident) => quote!(#ident),
+      None => {
+        let index = Index::from(i);
+        quote!(#index)
+      },
+    };
+
+    let value = match attrs(field)?.default {
+      Some(default) => quote!(#default),
+      None => quote!(::core::default::Default::default()),
+    };
+    inits.push(quote!(#member: #value));
+  }
+
+  let name = &input.ident;
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+  Ok(quote! {
+    impl #impl_generics ::core::default::Default for #name #ty_generics #where_clause {
+      fn default() -> Self {
+        Self { #(#inits),* }
+      }
+    }
+  })
+}
@@ -0,0 +1,154 @@
+use core::fmt;
+
+use crate::{ConfigurationMarshal, RecoveryReason};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AsymmetricError<R, W> {
+  /// The target couldn't be written to, or holds a configuration that couldn't be read.
+  Target(W),
+  /// The target is blank, and the source couldn't be read either.
+  Source { target: W, source: R },
+}
+
+impl<R: fmt::Debug, W: fmt::Debug> fmt::Display for AsymmetricError<R, W> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Target(e) => write!(f, "target error: {:?}", e),
+      Self::Source { target, source } => write!(f, "target error: {:?}, and source error: {:?}", target, source),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<R: fmt::Debug, W: fmt::Debug> std::error::Error for AsymmetricError<R, W> { }
+
+/// Moves the configuration from one storage to another, e.g. from an external EEPROM to internal flash on a
+/// board revision that still has both. Reads come from the target, or from the source while the target is
+/// still blank, and writes only ever go to the target, so the first commit moves the configuration over.
+///
+/// Unlike [`crate::FallbackMarshal`], nothing is written by a read, and the source is only read while the
+/// target has never been written, so a corrupt target is reported rather than hidden behind a stale source.
+pub struct AsymmetricMarshal<R, W> {
+  source: R,
+  target: W,
+  from_source: bool,
+}
+
+impl<R, W> AsymmetricMarshal<R, W> {
+  pub fn new(source: R, target: W) -> Self {
+    Self { source, target, from_source: false }
+  }
+
+  /// Whether the configuration last read came from the source, and hasn't since been written to the target,
+  /// e.g. to commit straight away rather than waiting for the first change.
+  pub fn from_source(&self) -> bool {
+    self.from_source
+  }
+
+  pub fn source_mut(&mut self) -> &mut R {
+    &mut self.source
+  }
+
+  pub fn target_mut(&mut self) -> &mut W {
+    &mut self.target
+  }
+
+  pub fn into_inner(self) -> (R, W) {
+    (self.source, self.target)
+  }
+}
+
+impl<Config, R, W> ConfigurationMarshal<Config> for AsymmetricMarshal<R, W>
+where
+  R: ConfigurationMarshal<Config>,
+  W: ConfigurationMarshal<Config>
+{
+  type Error = AsymmetricError<R::Error, W::Error>;
+  const RETRY_HINT: usize = W::RETRY_HINT;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.target.write(config).map_err(AsymmetricError::Target)?;
+    self.from_source = false;
+    Ok(())
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.target.write_now(config).map_err(AsymmetricError::Target)?;
+    self.from_source = false;
+    Ok(())
+  }
+
+  fn write_changed(&mut self, config: &Config) -> Result<bool, Self::Error> {
+    // Until the configuration's been moved over, it hasn't been kept back by being the same as what's stored
+    if self.from_source {
+      return self.write(config).map(|_| true);
+    }
+    self.target.write_changed(config).map_err(AsymmetricError::Target)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.from_source = false;
+
+    let target = match self.target.read() {
+      Ok(c) => return Ok(c),
+      Err(e) if !W::is_blank(&e) => return Err(AsymmetricError::Target(e)),
+      Err(e) => e,
+    };
+
+    match self.source.read() {
+      Ok(c) => {
+        self.from_source = true;
+        Ok(c)
+      },
+      Err(source) => Err(AsymmetricError::Source { target, source }),
+    }
+  }
+
+  fn capacity(&self) -> usize {
+    self.target.capacity()
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    match error {
+      AsymmetricError::Target(e) => W::is_blank(e),
+      AsymmetricError::Source { source, .. } => R::is_blank(source),
+    }
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      AsymmetricError::Target(e) => W::recovery_reason(e),
+      AsymmetricError::Source { source, .. } => R::recovery_reason(source),
+    }
+  }
+
+  fn migrated(&self) -> bool {
+    match self.from_source {
+      true => self.source.migrated(),
+      false => self.target.migrated(),
+    }
+  }
+
+  fn revision(&self) -> Option<u32> {
+    match self.from_source {
+      true => self.source.revision(),
+      false => self.target.revision(),
+    }
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.target.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    match self.from_source {
+      true => self.source.last_modified(),
+      false => self.target.last_modified(),
+    }
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    self.target.written_externally(config)
+  }
+}
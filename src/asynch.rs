@@ -0,0 +1,255 @@
+use core::future::Future;
+
+use crate::{CommitObserver, ConfigurationMarshal, ProviderInitError};
+
+/// The async counterpart to [`crate::ConfigurationMarshal`], for marshals that shouldn't block the executor.
+#[allow(async_fn_in_trait)]
+pub trait AsyncConfigurationMarshal<Config>
+{
+  type Error;
+  async fn write(&mut self, config: &Config) -> Result<(), Self::Error>;
+  async fn read(&mut self) -> Result<Config, Self::Error>;
+
+  /// See [`crate::ConfigurationMarshal::is_blank`].
+  fn is_blank(error: &Self::Error) -> bool {
+    let _ = error;
+    false
+  }
+
+  /// See [`crate::ConfigurationMarshal::migrated`].
+  fn migrated(&self) -> bool {
+    false
+  }
+
+  /// See [`crate::ConfigurationMarshal::migrate_dry_run`].
+  async fn migrate_dry_run(&mut self) -> Result<(Config, bool), Self::Error> {
+    let config = self.read().await?;
+    Ok((config, self.migrated()))
+  }
+
+  /// See [`crate::ConfigurationMarshal::revision`].
+  fn revision(&self) -> Option<u32> {
+    None
+  }
+}
+
+/// The async counterpart to [`crate::ConfigurationProvider`].
+pub struct AsyncConfigurationProvider<Config, Marshal, Observer = ()> {
+  volatile: Config,
+  dirty: bool,
+  marshal: Marshal,
+  observer: Observer
+}
+
+impl<Config, Marshal> AsyncConfigurationProvider<Config, Marshal>
+where
+  Marshal: AsyncConfigurationMarshal<Config>
+{
+  /// See [`crate::ConfigurationProvider::new_with_default`].
+  pub async fn new_with_default<F: FnOnce() -> Config>(mut marshal: Marshal, default: F) -> Result<Self, ProviderInitError<Marshal::Error>> {
+    let current = marshal.migrate_dry_run().await;
+    match current {
+      Ok((c, migrated)) => {
+        if migrated {
+          marshal.write(&c).await.map_err(ProviderInitError::MigrationWriteFailed)?;
+        }
+        Ok(Self { marshal, volatile: c, dirty: false, observer: () })
+      },
+      Err(e) if !Marshal::is_blank(&e) => Err(ProviderInitError::ReadFailed(e)),
+      Err(_) => {
+        let c = default();
+        marshal.write(&c).await.map_err(ProviderInitError::DefaultWriteFailed)?;
+        Ok(Self { marshal, volatile: c, dirty: false, observer: () })
+      },
+    }
+  }
+}
+
+impl<Config, Marshal> AsyncConfigurationProvider<Config, Marshal>
+where
+  Config: Default,
+  Marshal: AsyncConfigurationMarshal<Config>
+{
+  pub async fn new(marshal: Marshal) -> Result<Self, ProviderInitError<Marshal::Error>> {
+    Self::new_with_default(marshal, Config::default).await
+  }
+
+  /// See [`crate::ConfigurationProvider::new_or_default`].
+  pub async fn new_or_default(mut marshal: Marshal) -> Result<Self, ProviderInitError<Marshal::Error>> {
+    match marshal.migrate_dry_run().await {
+      Ok((c, migrated)) => {
+        if migrated {
+          marshal.write(&c).await.map_err(ProviderInitError::MigrationWriteFailed)?;
+        }
+        Ok(Self { marshal, volatile: c, dirty: false, observer: () })
+      },
+      Err(_) => {
+        let c = Config::default();
+        marshal.write(&c).await.map_err(ProviderInitError::DefaultWriteFailed)?;
+        Ok(Self { marshal, volatile: c, dirty: false, observer: () })
+      },
+    }
+  }
+}
+
+impl<Config, Marshal, Observer> AsyncConfigurationProvider<Config, Marshal, Observer>
+where
+  Config: Default,
+  Marshal: AsyncConfigurationMarshal<Config>,
+  Observer: CommitObserver<Config>
+{
+  /// See [`crate::ConfigurationProvider::factory_reset`].
+  pub async fn factory_reset(&mut self) -> Result<(), Marshal::Error> {
+    self.volatile = Config::default();
+    self.commit().await
+  }
+}
+
+impl<Config, Marshal, Observer> AsyncConfigurationProvider<Config, Marshal, Observer>
+where
+  Marshal: AsyncConfigurationMarshal<Config>,
+  Observer: CommitObserver<Config>
+{
+  /// See [`crate::ConfigurationProvider::on_commit`]. To wake tasks awaiting a change, signal them from the
+  /// observer, e.g. `on_commit(|c: &Config| SIGNAL.signal(c.clone()))` with an `embassy_sync` `Signal`.
+  pub fn on_commit<F: CommitObserver<Config>>(self, observer: F) -> AsyncConfigurationProvider<Config, Marshal, F> {
+    AsyncConfigurationProvider { volatile: self.volatile, dirty: self.dirty, marshal: self.marshal, observer }
+  }
+
+  pub async fn commit(&mut self) -> Result<(), Marshal::Error> {
+    self.marshal.write(&self.volatile).await?;
+    self.dirty = false;
+    self.observer.committed(&self.volatile);
+    Ok(())
+  }
+
+  pub async fn commit_if_dirty(&mut self) -> Result<(), Marshal::Error> {
+    match self.dirty {
+      true => self.commit().await,
+      false => Ok(()),
+    }
+  }
+
+  /// See [`crate::ConfigurationProvider::commit_value`].
+  pub async fn commit_value(&mut self, config: Config) -> Result<(), Marshal::Error> {
+    self.marshal.write(&config).await?;
+    self.volatile = config;
+    self.dirty = false;
+    self.observer.committed(&self.volatile);
+    Ok(())
+  }
+
+  pub async fn commit_ok(&mut self) -> bool {
+    self.commit().await.is_ok()
+  }
+
+  /// See [`crate::GenericConfigurationProvider::update`].
+  pub async fn update<F: FnOnce(&mut Config)>(&mut self, f: F) -> Result<(), Marshal::Error> {
+    f(self.current_mut());
+    self.commit().await
+  }
+
+  /// See [`crate::ConfigurationProvider::discard`].
+  pub async fn discard(&mut self) -> Result<(), Marshal::Error> {
+    self.reload().await
+  }
+
+  pub fn marshal(&self) -> &Marshal {
+    &self.marshal
+  }
+
+  pub fn marshal_mut(&mut self) -> &mut Marshal {
+    &mut self.marshal
+  }
+
+  pub fn current(&self) -> &Config {
+    &self.volatile
+  }
+
+  pub fn current_mut(&mut self) -> &mut Config {
+    self.dirty = true;
+    &mut self.volatile
+  }
+
+  pub fn is_dirty(&self) -> bool {
+    self.dirty
+  }
+
+  /// See [`crate::ConfigurationProvider::reload`].
+  pub async fn reload(&mut self) -> Result<(), Marshal::Error> {
+    self.volatile = self.marshal.read().await?;
+    self.dirty = false;
+    Ok(())
+  }
+}
+
+impl<Config, Marshal, Observer> AsyncConfigurationProvider<Config, Marshal, Observer>
+where
+  Config: Clone
+{
+  /// See [`crate::ConfigurationProvider::snapshot`].
+  pub fn snapshot(&self) -> Config {
+    self.volatile.clone()
+  }
+
+  /// See [`crate::ConfigurationProvider::restore`].
+  pub fn restore(&mut self, snapshot: Config) {
+    self.volatile = snapshot;
+    self.dirty = true;
+  }
+}
+
+/// Runs a future to completion from sync code, e.g. with an executor's `block_on`.
+pub trait BlockOn {
+  fn block_on<F: Future>(&mut self, future: F) -> F::Output;
+}
+
+/// The bridge the other way from [`AsyncConfigurationProvider`], implementing the sync
+/// [`ConfigurationMarshal`] over an [`AsyncConfigurationMarshal`] by blocking on each operation. This lets
+/// async storage sit behind a sync [`crate::ConfigurationProvider`].
+pub struct BlockingMarshal<Inner, Blocker> {
+  inner: Inner,
+  blocker: Blocker,
+}
+
+impl<Inner, Blocker> BlockingMarshal<Inner, Blocker> {
+  pub fn new(inner: Inner, blocker: Blocker) -> Self {
+    Self { inner, blocker }
+  }
+
+  pub fn inner_mut(&mut self) -> &mut Inner {
+    &mut self.inner
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+}
+
+impl<Config, Inner, Blocker> ConfigurationMarshal<Config> for BlockingMarshal<Inner, Blocker>
+where
+  Inner: AsyncConfigurationMarshal<Config>,
+  Blocker: BlockOn
+{
+  type Error = Inner::Error;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.blocker.block_on(self.inner.write(config))
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.blocker.block_on(self.inner.read())
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    Inner::is_blank(error)
+  }
+
+  fn migrated(&self) -> bool {
+    self.inner.migrated()
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.inner.revision()
+  }
+}
@@ -0,0 +1,411 @@
+use core::{fmt, marker::PhantomData};
+
+use embedded_hal::blocking::{i2c, delay::DelayMs};
+
+use crate::{store::ByteStore, FramedMarshal};
+
+const BASE_ADDRESS: u8 = 0x50;
+const MAX_PAGE_SIZE: usize = 128;
+const MAX_POLLS: usize = 10;
+// With one address byte, 8 blocks of 256 bytes picked by the device address, as on the AT24C16, and with two,
+// as much as the AT24C512 holds
+const MAX_CAPACITY: [usize; 2] = [8 * 256, 65536];
+
+/// The geometry of an AT24C-series part.
+pub trait At24cPart {
+  const CAPACITY: usize;
+  /// Writes are split into chunks of at most 128 bytes, so larger pages are still written correctly, just in
+  /// more write cycles.
+  const PAGE_SIZE: usize;
+  /// How many bytes of the memory address are sent after the device address. Any higher address bits
+  /// are carried in the low bits of the device address, as on the AT24C16.
+  const ADDRESS_BYTES: usize;
+}
+
+pub struct At24c01;
+impl At24cPart for At24c01 {
+  const CAPACITY: usize = 128;
+  const PAGE_SIZE: usize = 8;
+  const ADDRESS_BYTES: usize = 1;
+}
+
+pub struct At24c02;
+impl At24cPart for At24c02 {
+  const CAPACITY: usize = 256;
+  const PAGE_SIZE: usize = 8;
+  const ADDRESS_BYTES: usize = 1;
+}
+
+pub struct At24c04;
+impl At24cPart for At24c04 {
+  const CAPACITY: usize = 512;
+  const PAGE_SIZE: usize = 16;
+  const ADDRESS_BYTES: usize = 1;
+}
+
+pub struct At24c08;
+impl At24cPart for At24c08 {
+  const CAPACITY: usize = 1024;
+  const PAGE_SIZE: usize = 16;
+  const ADDRESS_BYTES: usize = 1;
+}
+
+pub struct At24c16;
+impl At24cPart for At24c16 {
+  const CAPACITY: usize = 2048;
+  const PAGE_SIZE: usize = 16;
+  const ADDRESS_BYTES: usize = 1;
+}
+
+pub struct At24c32;
+impl At24cPart for At24c32 {
+  const CAPACITY: usize = 4096;
+  const PAGE_SIZE: usize = 32;
+  const ADDRESS_BYTES: usize = 2;
+}
+
+pub struct At24c64;
+impl At24cPart for At24c64 {
+  const CAPACITY: usize = 8192;
+  const PAGE_SIZE: usize = 32;
+  const ADDRESS_BYTES: usize = 2;
+}
+
+pub struct At24c128;
+impl At24cPart for At24c128 {
+  const CAPACITY: usize = 16384;
+  const PAGE_SIZE: usize = 64;
+  const ADDRESS_BYTES: usize = 2;
+}
+
+pub struct At24c256;
+impl At24cPart for At24c256 {
+  const CAPACITY: usize = 32768;
+  const PAGE_SIZE: usize = 64;
+  const ADDRESS_BYTES: usize = 2;
+}
+
+pub struct At24c512;
+impl At24cPart for At24c512 {
+  const CAPACITY: usize = 65536;
+  const PAGE_SIZE: usize = 128;
+  const ADDRESS_BYTES: usize = 2;
+}
+
+/// The ST M24C64, which is laid out the same as the [`At24c64`]. This only gives raw access for the generic
+/// marshals, e.g. through [`At24cMarshal`]. [`crate::m24c64::M24C64ConfigurationMarshal`] keeps its own format.
+pub type M24c64 = At24c64;
+
+/// The geometry of a part chosen at runtime, e.g. from the board revision, rather than by type. See
+/// [`At24cStore::with_geometry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct At24cGeometry {
+  pub capacity: usize,
+  pub page_size: usize,
+  pub address_bytes: usize,
+}
+
+impl At24cGeometry {
+  /// A part of `capacity` bytes, written in pages of `page_size`. Parts of up to 2KiB take one address byte,
+  /// with any higher bits in the device address, and larger parts take two, as across the AT24C range.
+  /// Capacities past the 64KiB of the AT24C512 can't be addressed, and are clamped to it, as is a `page_size`
+  /// of 0 to 1, with a debug assertion for either.
+  pub const fn new(capacity: usize, page_size: usize) -> Self {
+    let address_bytes = match capacity {
+      0..=2048 => 1,
+      _ => 2,
+    };
+    Self { capacity, page_size, address_bytes }.clamped()
+  }
+
+  // Bound the geometry to what can be addressed and written, so a bad one can't alias addresses or divide by 0
+  const fn clamped(self) -> Self {
+    debug_assert!(self.page_size > 0, "AT24C page size must be at least 1 byte");
+    debug_assert!(self.address_bytes == 1 || self.address_bytes == 2, "AT24C parts take 1 or 2 address bytes");
+    let address_bytes = match self.address_bytes {
+      0 | 1 => 1,
+      _ => 2,
+    };
+    let max_capacity = MAX_CAPACITY[address_bytes - 1];
+    debug_assert!(self.capacity <= max_capacity, "AT24C capacity is more than its address bytes can reach");
+    Self {
+      capacity: if self.capacity > max_capacity { max_capacity } else { self.capacity },
+      page_size: if self.page_size == 0 { 1 } else { self.page_size },
+      address_bytes,
+    }
+  }
+
+  pub const fn of<Part: At24cPart>() -> Self {
+    Self { capacity: Part::CAPACITY, page_size: Part::PAGE_SIZE, address_bytes: Part::ADDRESS_BYTES }
+  }
+}
+
+/// A [`FramedMarshal`] over an AT24C-series part, with a single byte of length on parts of 256 bytes or less,
+/// e.g. `At24cMarshal::new(At24cStore::<_, _, At24c02>::new(i2c, 0, delay))`.
+pub type At24cMarshal<Config, I2C, Delay, Part = At24cGeometry> = FramedMarshal<At24cStore<I2C, Delay, Part>, Config>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum At24cError<E> {
+  I2C(E),
+  OutOfBounds,
+}
+
+impl<E: fmt::Debug> fmt::Display for At24cError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::I2C(e) => write!(f, "I2C error: {:?}", e),
+      Self::OutOfBounds => f.write_str("the access runs past the end of the EEPROM"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for At24cError<E> { }
+
+/// Raw byte access to an AT24C-series EEPROM, for use with the generic marshals such as
+/// [`crate::FramedMarshal`]. Writes are split into pages, polling for the write cycle to complete after each.
+pub struct At24cStore<I2C, Delay, Part = At24cGeometry> {
+  i2c: I2C,
+  e_addr: u8,
+  delay: Delay,
+  geometry: At24cGeometry,
+  marker: PhantomData<Part>
+}
+
+impl<I2C, Delay, Part: At24cPart> At24cStore<I2C, Delay, Part> {
+  /// `e_addr` is the state of the part's address pins. Parts that use those bits for addressing
+  /// memory (like the AT24C16) should be given 0.
+  pub fn new(i2c: I2C, e_addr: u8, delay: Delay) -> Self {
+    Self { i2c, e_addr, delay, geometry: At24cGeometry::of::<Part>(), marker: PhantomData }
+  }
+}
+
+impl<I2C, Delay> At24cStore<I2C, Delay> {
+  /// Like [`Self::new`], but for a part whose geometry is only known at runtime. The geometry is bounded as
+  /// [`At24cGeometry::new`] bounds it.
+  pub fn with_geometry(i2c: I2C, e_addr: u8, delay: Delay, geometry: At24cGeometry) -> Self {
+    Self { i2c, e_addr, delay, geometry: geometry.clamped(), marker: PhantomData }
+  }
+}
+
+impl<I2C, Delay, Part> At24cStore<I2C, Delay, Part> {
+  pub fn geometry(&self) -> At24cGeometry {
+    self.geometry
+  }
+
+  pub fn into_inner(self) -> I2C {
+    self.i2c
+  }
+
+  // Device address and memory address bytes for `address`
+  fn address(&self, address: usize) -> (u8, [u8; 2]) {
+    let high = (address >> (8 * self.geometry.address_bytes)) as u8;
+    let device = BASE_ADDRESS | ((self.e_addr | high) & 0x7);
+    let word = (address as u16).to_be_bytes();
+    (device, word)
+  }
+}
+
+impl<I2C, Delay, Part, E> At24cStore<I2C, Delay, Part>
+where
+  I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
+  Delay: DelayMs<u16>
+{
+  fn wait_for_write_cycle(&mut self, device: u8, word: &[u8]) -> Result<(), E> {
+    // The EEPROM won't acknowledge its address until the internal write cycle is complete
+    let mut i = 0;
+    loop {
+      match self.i2c.write(device, word) {
+        Ok(()) => return Ok(()),
+        Err(_) if i < MAX_POLLS => (),
+        Err(e) => return Err(e),
+      }
+      i += 1;
+      self.delay.delay_ms(1u16);
+    }
+  }
+}
+
+impl<I2C, Delay, Part, E> ByteStore for At24cStore<I2C, Delay, Part>
+where
+  I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
+  Delay: DelayMs<u16>
+{
+  type Error = At24cError<E>;
+
+  fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Self::Error> {
+    if offset + buf.len() > self.geometry.capacity {
+      return Err(At24cError::OutOfBounds);
+    }
+
+    // Reads roll over between blocks, but the block is selected by the device address
+    let address_bytes = self.geometry.address_bytes;
+    let block_size = 1 << (8 * address_bytes);
+    let mut read = 0;
+    while read < buf.len() {
+      let address = offset + read;
+      let n = (block_size - address % block_size).min(buf.len() - read);
+      let (device, word) = self.address(address);
+      self.i2c.write_read(device, &word[2 - address_bytes..], &mut buf[read..read + n]).map_err(At24cError::I2C)?;
+      read += n;
+    }
+    Ok(())
+  }
+
+  fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Self::Error> {
+    if offset + bytes.len() > self.geometry.capacity {
+      return Err(At24cError::OutOfBounds);
+    }
+
+    // Writing a page in several chunks is fine, so long as none of them cross into the next
+    let page_size = self.geometry.page_size.min(MAX_PAGE_SIZE);
+    let mut buf = [0u8; 2 + MAX_PAGE_SIZE];
+    let mut written = 0;
+    while written < bytes.len() {
+      let address = offset + written;
+      let n = (page_size - address % page_size).min(bytes.len() - written);
+      let (device, word) = self.address(address);
+      let word = &word[2 - self.geometry.address_bytes..];
+
+      buf[..word.len()].copy_from_slice(word);
+      buf[word.len()..word.len() + n].copy_from_slice(&bytes[written..written + n]);
+      self.i2c.write(device, &buf[..word.len() + n]).map_err(At24cError::I2C)?;
+      self.wait_for_write_cycle(device, word).map_err(At24cError::I2C)?;
+
+      written += n;
+    }
+    Ok(())
+  }
+
+  fn capacity(&self) -> usize {
+    self.geometry.capacity
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use binmarshal::{Demarshal, Marshal};
+
+  use crate::{ConfigurationMarshal, FramedError};
+  use super::*;
+
+  #[derive(Debug, Clone, Default, PartialEq, Marshal, Demarshal)]
+  struct Settings {
+    gain: u32,
+  }
+
+  struct NoDelay;
+
+  impl DelayMs<u16> for NoDelay {
+    fn delay_ms(&mut self, _ms: u16) { }
+  }
+
+  // An EEPROM that wraps writes within a page as the real parts do, and records the device address and
+  // length of each page write
+  struct Eeprom {
+    mem: Vec<u8>,
+    geometry: At24cGeometry,
+    writes: Vec<(u8, usize)>,
+  }
+
+  impl Eeprom {
+    fn new(geometry: At24cGeometry) -> Self {
+      Self { mem: vec![0xFF; geometry.capacity], geometry, writes: Vec::new() }
+    }
+
+    fn address(&self, device: u8, word: &[u8]) -> usize {
+      match word {
+        [low] => ((device as usize & 0x7) << 8) | *low as usize,
+        _ => u16::from_be_bytes([word[0], word[1]]) as usize,
+      }
+    }
+  }
+
+  impl i2c::Write for Eeprom {
+    type Error = ();
+
+    fn write(&mut self, device: u8, bytes: &[u8]) -> Result<(), ()> {
+      assert_eq!(device & !0x7, BASE_ADDRESS);
+      let (word, data) = bytes.split_at(self.geometry.address_bytes);
+      if data.is_empty() {
+        // Polling for the write cycle
+        return Ok(());
+      }
+      let address = self.address(device, word);
+      let page = address - address % self.geometry.page_size;
+      for (i, byte) in data.iter().enumerate() {
+        self.mem[page + (address + i) % self.geometry.page_size] = *byte;
+      }
+      self.writes.push((device, data.len()));
+      Ok(())
+    }
+  }
+
+  impl i2c::WriteRead for Eeprom {
+    type Error = ();
+
+    fn write_read(&mut self, device: u8, word: &[u8], buf: &mut [u8]) -> Result<(), ()> {
+      let address = self.address(device, word);
+      for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = self.mem[(address + i) % self.geometry.capacity];
+      }
+      Ok(())
+    }
+  }
+
+  fn store<Part: At24cPart>() -> At24cStore<Eeprom, NoDelay, Part> {
+    At24cStore::new(Eeprom::new(At24cGeometry::of::<Part>()), 0, NoDelay)
+  }
+
+  #[test]
+  fn writes_are_split_at_page_boundaries() {
+    let mut store = store::<At24c02>();
+    let bytes: Vec<u8> = (0..20).collect();
+    store.write_at(5, &bytes).unwrap();
+
+    let mut read = [0u8; 20];
+    store.read_at(5, &mut read).unwrap();
+    assert_eq!(&read[..], &bytes[..]);
+    assert_eq!(store.into_inner().writes, vec![(0x50, 3), (0x50, 8), (0x50, 8), (0x50, 1)]);
+  }
+
+  #[test]
+  fn blocks_are_picked_by_the_device_address() {
+    let mut store = store::<At24c16>();
+    let bytes: Vec<u8> = (0..40).collect();
+    store.write_at(0x1F0, &bytes).unwrap();
+
+    let mut read = [0u8; 40];
+    store.read_at(0x1F0, &mut read).unwrap();
+    assert_eq!(&read[..], &bytes[..]);
+
+    let eeprom = store.into_inner();
+    assert_eq!(&eeprom.mem[0x1F0..0x218], &bytes[..]);
+    assert_eq!(eeprom.writes, vec![(0x51, 16), (0x52, 16), (0x52, 8)]);
+  }
+
+  #[test]
+  fn two_byte_addresses_reach_the_whole_part() {
+    let mut store = store::<At24c64>();
+    store.write_at(0x1FFC, &[1, 2, 3, 4]).unwrap();
+    let mut read = [0u8; 4];
+    store.read_at(0x1FFC, &mut read).unwrap();
+    assert_eq!(read, [1, 2, 3, 4]);
+    assert_eq!(store.write_at(0x1FFD, &[1, 2, 3, 4]), Err(At24cError::OutOfBounds));
+  }
+
+  #[test]
+  fn corrupt_frame_is_reported() {
+    let mut marshal = At24cMarshal::<Settings, _, _, At24c02>::new(store::<At24c02>());
+    marshal.write(&Settings { gain: 42 }).unwrap();
+    assert_eq!(marshal.read().unwrap(), Settings { gain: 42 });
+
+    // Past the magic and length byte, into the payload
+    let mut byte = [0u8];
+    marshal.store_mut().read_at(3, &mut byte).unwrap();
+    marshal.store_mut().write_at(3, &[byte[0] ^ 0x01]).unwrap();
+    assert_eq!(marshal.read(), Err(FramedError::Corrupt));
+  }
+}
@@ -0,0 +1,157 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::vec;
+use binmarshal::{rw::{BitView, BitWriter, BufferBitWriter}, DemarshalOwned, Marshal, MarshalError};
+
+use crate::{store::ByteStore, ConfigurationMarshal};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BitPackedError<E> {
+  Store(E),
+  Serialisation,
+  /// The configuration serialises to more bits than its region has.
+  TooLarge { needed_bits: usize, available_bits: usize },
+}
+
+impl<E: fmt::Debug> fmt::Display for BitPackedError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Store(e) => write!(f, "store error: {:?}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::TooLarge { needed_bits, available_bits } => write!(f, "the configuration needs {} bits, but only {} are available", needed_bits, available_bits),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for BitPackedError<E> { }
+
+/// Stores the configuration, unframed, in a region of a [`ByteStore`] that starts and ends at any bit, so that
+/// several tiny bit-packed configurations can share bytes on a severely space-constrained part, e.g. an
+/// EEPROM through [`crate::m24c64::M24C64Store`]. Bits around the region are read back and preserved on write.
+///
+/// Bits are numbered from the most significant bit of byte 0, as binmarshal does. Fields that binmarshal
+/// aligns to a byte, such as arrays, are aligned to a byte of the store rather than of the region. There's no
+/// framing or checksum, so there's no telling blank or corrupt storage from a valid configuration.
+pub struct BitPackedMarshal<Store, Config> {
+  store: Store,
+  bit_offset: usize,
+  bit_len: usize,
+  marker: PhantomData<Config>
+}
+
+impl<Store, Config> BitPackedMarshal<Store, Config> {
+  /// Store the configuration in the `bit_len` bits starting `bit_offset` bits into `store`.
+  pub fn new(store: Store, bit_offset: usize, bit_len: usize) -> Self {
+    Self { store, bit_offset, bit_len, marker: PhantomData }
+  }
+
+  // The bytes the region touches, and where in the first one it starts
+  fn span(&self) -> (usize, usize, usize) {
+    let start = self.bit_offset / 8;
+    let end = (self.bit_offset + self.bit_len).div_ceil(8);
+    (start, end - start, self.bit_offset % 8)
+  }
+
+  pub fn store_mut(&mut self) -> &mut Store {
+    &mut self.store
+  }
+
+  pub fn into_inner(self) -> Store {
+    self.store
+  }
+}
+
+impl<Store, Config> ConfigurationMarshal<Config> for BitPackedMarshal<Store, Config>
+where
+  Store: ByteStore,
+  Config: Marshal<()> + DemarshalOwned
+{
+  type Error = BitPackedError<Store::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let (start, len, shift) = self.span();
+    let mut bytes = vec![0u8; len];
+    self.store.read_at(start, &mut bytes).map_err(Self::Error::Store)?;
+
+    // binmarshal masks each field into place, so the bits either side of the region come through untouched
+    let mut writer = BufferBitWriter::new(&mut bytes);
+    writer.advance(0, shift);
+    config.write(&mut writer, ()).map_err(|e| match e {
+      // Past the region's last byte, let alone its last bit
+      MarshalError::BufferTooSmall => Self::Error::TooLarge { needed_bits: self.bit_len + 1, available_bits: self.bit_len },
+      _ => Self::Error::Serialisation,
+    })?;
+
+    let end = match writer.bit_offset() {
+      0 => writer.slice().len() * 8,
+      bits => (writer.slice().len() - 1) * 8 + bits,
+    };
+    if end - shift > self.bit_len {
+      return Err(Self::Error::TooLarge { needed_bits: end - shift, available_bits: self.bit_len });
+    }
+
+    self.store.write_at(start, &bytes).map_err(Self::Error::Store)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let (start, len, shift) = self.span();
+    let mut bytes = vec![0u8; len];
+    self.store.read_at(start, &mut bytes).map_err(Self::Error::Store)?;
+
+    let mut view = BitView::new(&bytes);
+    view.advance(0, shift);
+    let config = Config::read(&mut view, ()).map_err(|_| Self::Error::Serialisation)?;
+
+    // Anything past the region's last bit belongs to a neighbour
+    let (bytes_read, bits_read) = view.offset();
+    if bytes_read * 8 + bits_read - shift > self.bit_len {
+      return Err(Self::Error::TooLarge { needed_bits: bytes_read * 8 + bits_read - shift, available_bits: self.bit_len });
+    }
+    Ok(config)
+  }
+
+  fn capacity(&self) -> usize {
+    self.bit_len / 8
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use binmarshal::{Demarshal, Marshal};
+
+  use super::*;
+
+  #[derive(Debug, Clone, Default, PartialEq, Marshal, Demarshal)]
+  struct Mode {
+    #[marshal(bits = 5)]
+    level: u8,
+  }
+
+  #[test]
+  fn neighbours_are_preserved() {
+    let mut storage = [0xFFu8; 2];
+    BitPackedMarshal::new(&mut storage[..], 3, 5).write(&Mode { level: 0b10101 }).unwrap();
+    assert_eq!(storage, [0b1111_0101, 0xFF]);
+
+    BitPackedMarshal::new(&mut storage[..], 8, 5).write(&Mode { level: 0b00110 }).unwrap();
+    assert_eq!(storage, [0b1111_0101, 0b0011_0111]);
+
+    assert_eq!(BitPackedMarshal::new(&mut storage[..], 3, 5).read(), Ok(Mode { level: 0b10101 }));
+    assert_eq!(BitPackedMarshal::new(&mut storage[..], 8, 5).read(), Ok(Mode { level: 0b00110 }));
+  }
+
+  #[test]
+  fn configuration_past_the_region_is_refused() {
+    let mut storage = [0xFFu8; 2];
+    let result = BitPackedMarshal::new(&mut storage[..], 2, 4).write(&Mode { level: 1 });
+    assert_eq!(result, Err(BitPackedError::TooLarge { needed_bits: 5, available_bits: 4 }));
+    assert_eq!(storage, [0xFF, 0xFF]);
+
+    let result: Result<Mode, _> = BitPackedMarshal::new(&mut storage[..], 2, 4).read();
+    assert_eq!(result, Err(BitPackedError::TooLarge { needed_bits: 5, available_bits: 4 }));
+  }
+}
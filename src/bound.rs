@@ -0,0 +1,135 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::vec;
+use binmarshal::{rw::{BitView, BitWriter, VecBitWriter}, DemarshalOwned, Marshal};
+
+use crate::{crc::crc32, store::ByteStore, ConfigurationMarshal, RecoveryReason};
+
+// [device:4][len:2]
+const HEADER_LEN: usize = 6;
+const CRC_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BoundError<E> {
+  Store(E),
+  Serialisation,
+  Blank,
+  Corrupt,
+  /// The configuration is intact, but was written on another device, e.g. the EEPROM was moved between
+  /// boards or a backup was restored to the wrong one.
+  WrongDevice,
+}
+
+impl<E: fmt::Debug> fmt::Display for BoundError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Store(e) => write!(f, "store error: {:?}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Blank => f.write_str("nothing is stored"),
+      Self::Corrupt => f.write_str("the stored configuration is corrupt"),
+      Self::WrongDevice => f.write_str("the configuration was written on another device"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for BoundError<E> { }
+
+/// Binds the configuration to the device it was written on, by storing a hash of a hardware-unique ID, such
+/// as the MCU's serial number, alongside it. Reads of a configuration written with any other ID fail with
+/// [`BoundError::WrongDevice`].
+///
+/// The stored image is `[ID hash:4][len:2][payload][CRC32]`, little-endian, with the CRC covering everything
+/// before it. The hash is a CRC32 of the ID, which catches mix-ups, but won't stop a determined attacker.
+pub struct BoundMarshal<Inner, Config> {
+  inner: Inner,
+  device: u32,
+  marker: PhantomData<Config>
+}
+
+impl<Inner, Config> BoundMarshal<Inner, Config> {
+  pub fn new(inner: Inner, device_id: &[u8]) -> Self {
+    Self { inner, device: crc32(device_id), marker: PhantomData }
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+}
+
+impl<Inner, Config> ConfigurationMarshal<Config> for BoundMarshal<Inner, Config>
+where
+  Inner: ByteStore,
+  Config: Marshal<()> + DemarshalOwned
+{
+  type Error = BoundError<Inner::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let mut writer = VecBitWriter::new();
+    config.write(&mut writer, ()).map_err(|_| Self::Error::Serialisation)?;
+    let payload = writer.slice();
+    if payload.len() > u16::MAX as usize {
+      return Err(Self::Error::Serialisation);
+    }
+
+    let mut image = vec![0u8; HEADER_LEN + payload.len() + CRC_LEN];
+    image[0..4].copy_from_slice(&self.device.to_le_bytes());
+    image[4..HEADER_LEN].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+    image[HEADER_LEN..HEADER_LEN + payload.len()].copy_from_slice(payload);
+    let crc = crc32(&image[..HEADER_LEN + payload.len()]);
+    image[HEADER_LEN + payload.len()..].copy_from_slice(&crc.to_le_bytes());
+
+    self.inner.write_at(0, &image).map_err(Self::Error::Store)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let mut header = [0u8; HEADER_LEN];
+    self.inner.read_at(0, &mut header).map_err(Self::Error::Store)?;
+
+    // Erased EEPROM and flash read back as 0xFF
+    if header == [0xFF; HEADER_LEN] {
+      return Err(Self::Error::Blank);
+    }
+
+    let len = u16::from_le_bytes([header[4], header[5]]) as usize;
+    if HEADER_LEN + len + CRC_LEN > self.inner.capacity() {
+      return Err(Self::Error::Corrupt);
+    }
+
+    let mut image = vec![0u8; HEADER_LEN + len + CRC_LEN];
+    self.inner.read_at(0, &mut image).map_err(Self::Error::Store)?;
+
+    // Check the CRC first, so that a corrupt ID isn't mistaken for another device's
+    let (framed, crc) = image.split_at(HEADER_LEN + len);
+    if crc32(framed).to_le_bytes() != crc {
+      return Err(Self::Error::Corrupt);
+    }
+    if framed[0..4] != self.device.to_le_bytes() {
+      return Err(Self::Error::WrongDevice);
+    }
+
+    match Config::read(&mut BitView::new(&framed[HEADER_LEN..]), ()) {
+      Ok(c) => Ok(c),
+      Err(_) => Err(Self::Error::Serialisation),
+    }
+  }
+
+  fn capacity(&self) -> usize {
+    self.inner.capacity().saturating_sub(HEADER_LEN + CRC_LEN)
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      Self::Error::Blank => RecoveryReason::Blank,
+      Self::Error::Corrupt | Self::Error::Serialisation => RecoveryReason::Corrupt,
+      Self::Error::Store(_) | Self::Error::WrongDevice => RecoveryReason::Unreadable,
+    }
+  }
+}
@@ -0,0 +1,170 @@
+use core::fmt;
+
+use crate::{ConfigurationMarshal, RecoveryReason};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WriteBudgetError<E> {
+  Inner(E),
+  /// The configuration has been written as many times as the budget allows. Nothing was written.
+  WriteBudgetExhausted,
+}
+
+impl<E: fmt::Debug> fmt::Display for WriteBudgetError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Inner(e) => write!(f, "marshal error: {:?}", e),
+      Self::WriteBudgetExhausted => f.write_str("the write budget is exhausted"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for WriteBudgetError<E> { }
+
+/// Refuses writes once the configuration has been written `budget` times over the life of the device, e.g. to
+/// keep an EEPROM's last few cycles for a critical final write rather than quietly wearing it out.
+///
+/// Writes are counted with the inner marshal's [`ConfigurationMarshal::revision`], which is persisted
+/// alongside the configuration, so the count carries over reboots once it's been read. Marshals without a
+/// revision only have the writes made through this one counted. The budget itself isn't stored, so set it the
+/// same way on every boot, e.g. from a constant.
+pub struct WriteBudgetMarshal<Inner> {
+  inner: Inner,
+  budget: u32,
+  // Writes made through this marshal, for inner marshals without a revision
+  writes: u32,
+}
+
+impl<Inner> WriteBudgetMarshal<Inner> {
+  pub fn new(inner: Inner, budget: u32) -> Self {
+    Self { inner, budget, writes: 0 }
+  }
+
+  pub fn set_write_budget(&mut self, budget: u32) {
+    self.budget = budget;
+  }
+
+  pub fn write_budget(&self) -> u32 {
+    self.budget
+  }
+
+  pub fn inner_mut(&mut self) -> &mut Inner {
+    &mut self.inner
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+
+  /// How many times the configuration has been written over the life of the device, as far as is known.
+  pub fn lifetime_writes<Config>(&self) -> u32
+  where
+    Inner: ConfigurationMarshal<Config>
+  {
+    // Revisions count from 0
+    self.inner.revision().map(|revision| revision.saturating_add(1)).unwrap_or(self.writes)
+  }
+
+  /// How many more writes the budget allows.
+  pub fn remaining_writes<Config>(&self) -> u32
+  where
+    Inner: ConfigurationMarshal<Config>
+  {
+    self.budget.saturating_sub(self.lifetime_writes::<Config>())
+  }
+
+  fn check<Config>(&self) -> Result<(), WriteBudgetError<Inner::Error>>
+  where
+    Inner: ConfigurationMarshal<Config>
+  {
+    match self.remaining_writes::<Config>() {
+      0 => Err(WriteBudgetError::WriteBudgetExhausted),
+      _ => Ok(()),
+    }
+  }
+}
+
+impl<Config, Inner> ConfigurationMarshal<Config> for WriteBudgetMarshal<Inner>
+where
+  Inner: ConfigurationMarshal<Config>
+{
+  type Error = WriteBudgetError<Inner::Error>;
+  const RETRY_HINT: usize = Inner::RETRY_HINT;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.check::<Config>()?;
+    self.inner.write(config).map_err(Self::Error::Inner)?;
+    self.writes = self.writes.saturating_add(1);
+    Ok(())
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.check::<Config>()?;
+    self.inner.write_now(config).map_err(Self::Error::Inner)?;
+    self.writes = self.writes.saturating_add(1);
+    Ok(())
+  }
+
+  fn write_serialised(&mut self, config: &Config, bytes: &[u8]) -> Result<(), Self::Error> {
+    self.check::<Config>()?;
+    self.inner.write_serialised(config, bytes).map_err(Self::Error::Inner)?;
+    self.writes = self.writes.saturating_add(1);
+    Ok(())
+  }
+
+  fn write_changed(&mut self, config: &Config) -> Result<bool, Self::Error> {
+    self.check::<Config>()?;
+    let changed = self.inner.write_changed(config).map_err(Self::Error::Inner)?;
+    if changed {
+      self.writes = self.writes.saturating_add(1);
+    }
+    Ok(changed)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.inner.read().map_err(Self::Error::Inner)
+  }
+
+  fn capacity(&self) -> usize {
+    self.inner.capacity()
+  }
+
+  fn free(&mut self) -> Result<usize, Self::Error> {
+    self.inner.free().map_err(Self::Error::Inner)
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    match error {
+      WriteBudgetError::Inner(e) => Inner::is_blank(e),
+      WriteBudgetError::WriteBudgetExhausted => false,
+    }
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      WriteBudgetError::Inner(e) => Inner::recovery_reason(e),
+      WriteBudgetError::WriteBudgetExhausted => RecoveryReason::Unreadable,
+    }
+  }
+
+  fn migrated(&self) -> bool {
+    self.inner.migrated()
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.inner.revision()
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.inner.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.inner.last_modified()
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    self.inner.written_externally(config)
+  }
+}
@@ -0,0 +1,95 @@
+use crate::{ConfigurationMarshal, RecoveryReason};
+
+/// Stages writes in memory until [`Self::flush`], so a burst of writes costs a single write to the inner
+/// marshal. Reads see staged writes.
+pub struct BufferedMarshal<Inner, Config> {
+  inner: Inner,
+  staged: Option<Config>,
+}
+
+impl<Inner, Config> BufferedMarshal<Inner, Config> {
+  pub fn new(inner: Inner) -> Self {
+    Self { inner, staged: None }
+  }
+
+  /// Whether there's a staged write waiting to be flushed.
+  pub fn is_staged(&self) -> bool {
+    self.staged.is_some()
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+}
+
+impl<Inner, Config> BufferedMarshal<Inner, Config>
+where
+  Inner: ConfigurationMarshal<Config>
+{
+  /// Write the staged configuration, if any, to the inner marshal. It stays staged if the write fails.
+  pub fn flush(&mut self) -> Result<(), Inner::Error> {
+    if let Some(config) = &self.staged {
+      self.inner.write(config)?;
+      self.staged = None;
+    }
+    Ok(())
+  }
+}
+
+impl<Inner, Config> ConfigurationMarshal<Config> for BufferedMarshal<Inner, Config>
+where
+  Inner: ConfigurationMarshal<Config>,
+  Config: Clone
+{
+  type Error = Inner::Error;
+  const RETRY_HINT: usize = Inner::RETRY_HINT;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.staged = Some(config.clone());
+    Ok(())
+  }
+
+  /// Write straight through to the inner marshal, replacing any staged write.
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.inner.write_now(config)?;
+    self.staged = None;
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    match &self.staged {
+      Some(config) => Ok(config.clone()),
+      None => self.inner.read(),
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    Inner::is_blank(error)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    Inner::recovery_reason(error)
+  }
+
+  fn migrated(&self) -> bool {
+    self.staged.is_none() && self.inner.migrated()
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    // Storage is already up to date, so there's nothing left to flush
+    self.staged = None;
+    self.inner.written_externally(config)
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.inner.revision()
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.inner.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.inner.last_modified()
+  }
+}
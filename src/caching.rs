@@ -0,0 +1,187 @@
+#[cfg(feature = "async")]
+use crate::asynch::AsyncConfigurationMarshal;
+use crate::{ConfigurationMarshal, RecoveryReason};
+
+/// Remembers the last configuration read from or written to the inner marshal, serving reads from
+/// memory until [`Self::invalidate`] is called.
+pub struct CachingMarshal<Inner, Config> {
+  inner: Inner,
+  cache: Option<Config>,
+  migrated: bool,
+}
+
+impl<Inner, Config> CachingMarshal<Inner, Config> {
+  pub fn new(inner: Inner) -> Self {
+    Self { inner, cache: None, migrated: false }
+  }
+
+  /// Drop the cached configuration, so the next read goes to the inner marshal.
+  pub fn invalidate(&mut self) {
+    self.cache = None;
+  }
+
+  /// Access the inner marshal. The cache is invalidated, since it may be written through directly.
+  pub fn inner_mut(&mut self) -> &mut Inner {
+    self.invalidate();
+    &mut self.inner
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+
+  // The cached configuration, if there is one. Shared with the async impl, as are the helpers below.
+  fn cached(&mut self) -> Option<Config>
+  where
+    Config: Clone
+  {
+    let c = self.cache.clone()?;
+    self.migrated = false;
+    Some(c)
+  }
+
+  // Forget what's stored ahead of a write, so that one that fails, or is cancelled part way, can't leave a
+  // stale cache behind
+  fn writing(&mut self) {
+    self.cache = None;
+  }
+
+  fn written(&mut self, config: &Config)
+  where
+    Config: Clone
+  {
+    self.cache = Some(config.clone());
+    self.migrated = false;
+  }
+
+  fn loaded(&mut self, config: &Config, migrated: bool)
+  where
+    Config: Clone
+  {
+    self.cache = Some(config.clone());
+    self.migrated = migrated;
+  }
+}
+
+impl<Inner, Config> ConfigurationMarshal<Config> for CachingMarshal<Inner, Config>
+where
+  Inner: ConfigurationMarshal<Config>,
+  Config: Clone
+{
+  type Error = Inner::Error;
+  const RETRY_HINT: usize = Inner::RETRY_HINT;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    // If the write fails we no longer know what's stored
+    self.writing();
+    self.inner.write(config)?;
+    self.written(config);
+    Ok(())
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.writing();
+    self.inner.write_now(config)?;
+    self.written(config);
+    Ok(())
+  }
+
+  fn write_serialised(&mut self, config: &Config, bytes: &[u8]) -> Result<(), Self::Error> {
+    self.writing();
+    self.inner.write_serialised(config, bytes)?;
+    self.written(config);
+    Ok(())
+  }
+
+  fn write_changed(&mut self, config: &Config) -> Result<bool, Self::Error> {
+    self.writing();
+    let changed = self.inner.write_changed(config)?;
+    self.written(config);
+    Ok(changed)
+  }
+
+  fn capacity(&self) -> usize {
+    self.inner.capacity()
+  }
+
+  fn free(&mut self) -> Result<usize, Self::Error> {
+    self.inner.free()
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    if let Some(c) = self.cached() {
+      return Ok(c);
+    }
+
+    let c = self.inner.read()?;
+    self.loaded(&c, self.inner.migrated());
+    Ok(c)
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    Inner::is_blank(error)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    Inner::recovery_reason(error)
+  }
+
+  fn migrated(&self) -> bool {
+    self.migrated
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.inner.revision()
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.inner.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.inner.last_modified()
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    self.inner.written_externally(config);
+    self.written(config);
+  }
+}
+
+#[cfg(feature = "async")]
+impl<Inner, Config> AsyncConfigurationMarshal<Config> for CachingMarshal<Inner, Config>
+where
+  Inner: AsyncConfigurationMarshal<Config>,
+  Config: Clone
+{
+  type Error = Inner::Error;
+
+  async fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.writing();
+    self.inner.write(config).await?;
+    self.written(config);
+    Ok(())
+  }
+
+  async fn read(&mut self) -> Result<Config, Self::Error> {
+    if let Some(c) = self.cached() {
+      return Ok(c);
+    }
+
+    let c = self.inner.read().await?;
+    self.loaded(&c, self.inner.migrated());
+    Ok(c)
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    Inner::is_blank(error)
+  }
+
+  fn migrated(&self) -> bool {
+    self.migrated
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.inner.revision()
+  }
+}
@@ -0,0 +1,128 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::vec;
+use binmarshal::{rw::{BitView, BitWriter, VecBitWriter}, DemarshalOwned, Marshal};
+
+use crate::{crc::{checksum_bytes, Checksum, Crc32}, store::ByteStore, ConfigurationMarshal, RecoveryReason};
+
+// [len:2]
+const HEADER_LEN: usize = 2;
+// A length of 0xFFFF is what erased storage reads back as, so it's reserved to mean blank
+const MAX_LEN: usize = u16::MAX as usize - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChecksumError<E> {
+  Store(E),
+  Serialisation,
+  Blank,
+  Corrupt,
+  /// The configuration serialises to more than the 65534 bytes a length can describe.
+  TooLarge { needed: usize, available: usize },
+}
+
+impl<E: fmt::Debug> fmt::Display for ChecksumError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Store(e) => write!(f, "store error: {:?}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Blank => f.write_str("nothing is stored"),
+      Self::Corrupt => f.write_str("the stored configuration is corrupt"),
+      Self::TooLarge { needed, available } => write!(f, "the configuration serialises to {} bytes, but at most {} can be stored", needed, available),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for ChecksumError<E> { }
+
+/// Frames the serialised configuration with its length and a checksum before handing it to a
+/// [`ByteStore`], so any backend gets corruption detection. Configurations can be at most 65534 bytes, as a
+/// length of 0xFFFF is taken to be erased storage.
+pub struct ChecksumMarshal<Inner, Config, C = Crc32> {
+  inner: Inner,
+  marker: PhantomData<(Config, C)>
+}
+
+impl<Inner, Config, C> ChecksumMarshal<Inner, Config, C> {
+  pub fn new(inner: Inner) -> Self {
+    Self { inner, marker: PhantomData }
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+}
+
+impl<Inner, Config, C> ConfigurationMarshal<Config> for ChecksumMarshal<Inner, Config, C>
+where
+  Inner: ByteStore,
+  Config: Marshal<()> + DemarshalOwned,
+  C: Checksum
+{
+  type Error = ChecksumError<Inner::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let mut writer = VecBitWriter::new();
+    if config.write(&mut writer, ()).is_err() {
+      return Err(Self::Error::Serialisation);
+    }
+    let payload = writer.slice();
+    if payload.len() > MAX_LEN {
+      return Err(Self::Error::TooLarge { needed: payload.len(), available: MAX_LEN });
+    }
+
+    let mut image = vec![0u8; HEADER_LEN + payload.len() + C::LEN];
+    image[0..HEADER_LEN].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+    image[HEADER_LEN..HEADER_LEN + payload.len()].copy_from_slice(payload);
+    image[HEADER_LEN + payload.len()..].copy_from_slice(&checksum_bytes::<C>(payload)[..C::LEN]);
+
+    self.inner.write_at(0, &image).map_err(Self::Error::Store)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let mut header = [0u8; HEADER_LEN];
+    self.inner.read_at(0, &mut header).map_err(Self::Error::Store)?;
+
+    // Erased EEPROM and flash read back as 0xFF
+    if header == [0xFF; HEADER_LEN] {
+      return Err(Self::Error::Blank);
+    }
+
+    let len = u16::from_le_bytes(header) as usize;
+    if HEADER_LEN + len + C::LEN > self.inner.capacity() {
+      return Err(Self::Error::Corrupt);
+    }
+
+    let mut buf = vec![0u8; len + C::LEN];
+    self.inner.read_at(HEADER_LEN, &mut buf).map_err(Self::Error::Store)?;
+
+    let (payload, checksum) = buf.split_at(len);
+    if checksum_bytes::<C>(payload)[..C::LEN] != *checksum {
+      return Err(Self::Error::Corrupt);
+    }
+
+    match Config::read(&mut BitView::new(payload), ()) {
+      Ok(c) => Ok(c),
+      Err(_) => Err(Self::Error::Serialisation),
+    }
+  }
+
+  fn capacity(&self) -> usize {
+    self.inner.capacity().saturating_sub(HEADER_LEN + C::LEN).min(MAX_LEN)
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      Self::Error::Blank => RecoveryReason::Blank,
+      Self::Error::Corrupt | Self::Error::Serialisation => RecoveryReason::Corrupt,
+      Self::Error::Store(_) | Self::Error::TooLarge { .. } => RecoveryReason::Unreadable,
+    }
+  }
+}
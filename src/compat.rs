@@ -0,0 +1,42 @@
+use embedded_hal::blocking::{delay::DelayMs, i2c};
+use embedded_hal_1::{delay::DelayNs, i2c::I2c};
+
+/// Adapts an embedded-hal 1.0 [`I2c`] to the embedded-hal 0.2 traits the EEPROM marshals and stores take, e.g.
+/// to put the M24C64 on a bus shared through one of embedded-hal-bus' `RefCellDevice`, `CriticalSectionDevice`,
+/// or `MutexDevice`, alongside the bus' other drivers:
+///
+/// `M24C64::new(I2cCompat(RefCellDevice::new(&bus)), address)`
+pub struct I2cCompat<I2C>(pub I2C);
+
+impl<I2C: I2c> i2c::Write<u8> for I2cCompat<I2C> {
+  type Error = I2C::Error;
+
+  fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+    I2c::write(&mut self.0, address, bytes)
+  }
+}
+
+impl<I2C: I2c> i2c::WriteRead<u8> for I2cCompat<I2C> {
+  type Error = I2C::Error;
+
+  fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
+    I2c::write_read(&mut self.0, address, bytes, buffer)
+  }
+}
+
+impl<I2C: I2c> i2c::Read<u8> for I2cCompat<I2C> {
+  type Error = I2C::Error;
+
+  fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+    I2c::read(&mut self.0, address, buffer)
+  }
+}
+
+/// Adapts an embedded-hal 1.0 [`DelayNs`] to the embedded-hal 0.2 delay the marshals take. See [`I2cCompat`].
+pub struct DelayCompat<Delay>(pub Delay);
+
+impl<Delay: DelayNs> DelayMs<u16> for DelayCompat<Delay> {
+  fn delay_ms(&mut self, ms: u16) {
+    self.0.delay_ms(ms as u32)
+  }
+}
@@ -0,0 +1,128 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::vec;
+use binmarshal::{rw::{BitView, BitWriter, VecBitWriter}, DemarshalOwned, Marshal};
+
+use crate::{store::ByteStore, ConfigurationMarshal, RecoveryReason};
+
+const MAGIC: [u8; 2] = *b"GR";
+// [magic:2][flags:1][len:2][uncompressed len:2]
+const HEADER_LEN: usize = 7;
+const FLAG_COMPRESSED: u8 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CompressedError<E> {
+  Store(E),
+  Serialisation,
+  Blank,
+  Corrupt
+}
+
+impl<E: fmt::Debug> fmt::Display for CompressedError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Store(e) => write!(f, "store error: {:?}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Blank => f.write_str("nothing is stored"),
+      Self::Corrupt => f.write_str("the stored configuration is corrupt"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for CompressedError<E> { }
+
+/// LZ4-compresses the serialised configuration before handing it to a [`ByteStore`]. Configurations that
+/// don't compress are stored as-is, so the stored image is never larger than the uncompressed one plus
+/// its header.
+pub struct CompressedMarshal<Inner, Config> {
+  inner: Inner,
+  marker: PhantomData<Config>
+}
+
+impl<Inner, Config> CompressedMarshal<Inner, Config> {
+  pub fn new(inner: Inner) -> Self {
+    Self { inner, marker: PhantomData }
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+}
+
+impl<Inner, Config> ConfigurationMarshal<Config> for CompressedMarshal<Inner, Config>
+where
+  Inner: ByteStore,
+  Config: Marshal<()> + DemarshalOwned
+{
+  type Error = CompressedError<Inner::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let mut writer = VecBitWriter::new();
+    if config.write(&mut writer, ()).is_err() {
+      return Err(Self::Error::Serialisation);
+    }
+    let payload = writer.slice();
+
+    let compressed = lz4_flex::compress(payload);
+    let (flags, data) = match compressed.len() < payload.len() {
+      true => (FLAG_COMPRESSED, &compressed[..]),
+      false => (0, payload),
+    };
+
+    let mut image = vec![0u8; HEADER_LEN + data.len()];
+    image[0..2].copy_from_slice(&MAGIC);
+    image[2] = flags;
+    image[3..5].copy_from_slice(&(data.len() as u16).to_le_bytes());
+    image[5..7].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+    image[HEADER_LEN..].copy_from_slice(data);
+
+    self.inner.write_at(0, &image).map_err(Self::Error::Store)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let mut header = [0u8; HEADER_LEN];
+    self.inner.read_at(0, &mut header).map_err(Self::Error::Store)?;
+
+    if header[0..2] != MAGIC {
+      return Err(Self::Error::Blank);
+    }
+
+    let flags = header[2];
+    let len = u16::from_le_bytes([header[3], header[4]]) as usize;
+    let uncompressed_len = u16::from_le_bytes([header[5], header[6]]) as usize;
+    if HEADER_LEN + len > self.inner.capacity() {
+      return Err(Self::Error::Corrupt);
+    }
+
+    let mut data = vec![0u8; len];
+    self.inner.read_at(HEADER_LEN, &mut data).map_err(Self::Error::Store)?;
+
+    if flags & FLAG_COMPRESSED != 0 {
+      data = lz4_flex::decompress(&data, uncompressed_len).map_err(|_| Self::Error::Corrupt)?;
+    }
+    if data.len() != uncompressed_len {
+      return Err(Self::Error::Corrupt);
+    }
+
+    match Config::read(&mut BitView::new(&data), ()) {
+      Ok(c) => Ok(c),
+      Err(_) => Err(Self::Error::Serialisation),
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      Self::Error::Blank => RecoveryReason::Blank,
+      Self::Error::Corrupt | Self::Error::Serialisation => RecoveryReason::Corrupt,
+      Self::Error::Store(_) => RecoveryReason::Unreadable,
+    }
+  }
+}
@@ -0,0 +1,106 @@
+/// A checksum over a serialised configuration. Marshals that take one, like [`crate::FramedMarshal`], default
+/// to [`Crc32`], but can use [`Crc16Ccitt`], [`Crc32c`], or your own to match the checksum another tool, such
+/// as a bootloader, expects over the same payload. Checksums are stored little-endian.
+pub trait Checksum {
+  /// How many bytes of [`Self::checksum`] are stored, at most 8.
+  const LEN: usize;
+
+  fn checksum(data: &[u8]) -> u64;
+}
+
+// `C`'s checksum of `data`, of which the first `C::LEN` bytes are stored. Refuses to build for a `C::LEN`
+// that doesn't fit in the checksum, rather than panicking when it's sliced.
+pub(crate) fn checksum_bytes<C: Checksum>(data: &[u8]) -> [u8; 8] {
+  const { assert!(C::LEN <= 8, "a checksum is at most 8 bytes") };
+  C::checksum(data).to_le_bytes()
+}
+
+// Reflected polynomials
+pub(crate) const CRC32_POLY: u32 = 0xEDB8_8320;
+const CRC32C_POLY: u32 = 0x82F6_3B78;
+
+/// CRC-32 (IEEE 802.3, reflected, polynomial 0xEDB88320), as used by zlib and friends.
+pub fn crc32(data: &[u8]) -> u32 {
+  !crc32_update(CRC32_POLY, 0xFFFF_FFFF, data)
+}
+
+// Continue a reflected CRC-32 with polynomial `poly` over `data`, for data that arrives in pieces. The state
+// starts at 0xFFFF_FFFF, and the CRC is the inverted final state.
+pub(crate) fn crc32_update(poly: u32, mut crc: u32, data: &[u8]) -> u32 {
+  for byte in data {
+    crc ^= *byte as u32;
+    for _ in 0..8 {
+      let mask = (!(crc & 1)).wrapping_add(1);
+      crc = (crc >> 1) ^ (poly & mask);
+    }
+  }
+  crc
+}
+
+/// [`crc32`] as a [`Checksum`].
+pub struct Crc32;
+
+impl Checksum for Crc32 {
+  const LEN: usize = 4;
+
+  fn checksum(data: &[u8]) -> u64 {
+    crc32(data) as u64
+  }
+}
+
+/// CRC-16/CCITT-FALSE (polynomial 0x1021, initial 0xFFFF, not reflected), as used by many bootloaders.
+pub fn crc16_ccitt(data: &[u8]) -> u16 {
+  let mut crc = 0xFFFFu16;
+  for byte in data {
+    crc ^= (*byte as u16) << 8;
+    for _ in 0..8 {
+      crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+    }
+  }
+  crc
+}
+
+/// [`crc16_ccitt`] as a [`Checksum`].
+pub struct Crc16Ccitt;
+
+impl Checksum for Crc16Ccitt {
+  const LEN: usize = 2;
+
+  fn checksum(data: &[u8]) -> u64 {
+    crc16_ccitt(data) as u64
+  }
+}
+
+/// CRC-32C (Castagnoli, reflected, polynomial 0x82F63B78), as used by iSCSI and ext4.
+pub fn crc32c(data: &[u8]) -> u32 {
+  !crc32_update(CRC32C_POLY, 0xFFFF_FFFF, data)
+}
+
+/// [`crc32c`] as a [`Checksum`].
+pub struct Crc32c;
+
+impl Checksum for Crc32c {
+  const LEN: usize = 4;
+
+  fn checksum(data: &[u8]) -> u64 {
+    crc32c(data) as u64
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn check_values() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+    assert_eq!(crc16_ccitt(b"123456789"), 0x29B1);
+  }
+
+  #[test]
+  fn update_in_pieces_matches_all_at_once() {
+    let crc = crc32_update(CRC32C_POLY, 0xFFFF_FFFF, b"1234");
+    assert_eq!(!crc32_update(CRC32C_POLY, crc, b"56789"), crc32c(b"123456789"));
+  }
+}
@@ -0,0 +1,95 @@
+use core::marker::PhantomData;
+
+use crate::{GenericConfigurationProvider, MonotonicClock};
+
+/// Wraps a provider, holding back commits until the configuration hasn't been changed for `quiet_ms`, so that a
+/// burst of edits, e.g. from an encoder being scrolled through values, is coalesced into a single write.
+///
+/// Edits through [`GenericConfigurationProvider::current_mut`] and commits both restart the wait. Call
+/// [`Self::tick`] periodically to commit once it's over, and [`Self::flush`] to commit straight away, e.g.
+/// before shutting down. Unlike [`crate::RateLimitedMarshal`], a steady stream of edits is never written
+/// until it stops.
+pub struct DebouncedProvider<Config, Provider, Clock> {
+  provider: Provider,
+  clock: Clock,
+  quiet_ms: u64,
+  // When the configuration was last changed, while there's a commit waiting
+  last_edit: Option<u64>,
+  marker: PhantomData<Config>
+}
+
+impl<Config, Provider, Clock> DebouncedProvider<Config, Provider, Clock> {
+  pub fn new(provider: Provider, clock: Clock, quiet_ms: u64) -> Self {
+    Self { provider, clock, quiet_ms, last_edit: None, marker: PhantomData }
+  }
+
+  /// Whether there's a commit waiting for the edits to settle.
+  pub fn is_pending(&self) -> bool {
+    self.last_edit.is_some()
+  }
+
+  pub fn provider(&self) -> &Provider {
+    &self.provider
+  }
+
+  /// The wrapped provider, with any waiting commit left uncommitted.
+  pub fn into_inner(self) -> Provider {
+    self.provider
+  }
+}
+
+impl<Config, Provider, Clock> DebouncedProvider<Config, Provider, Clock>
+where
+  Provider: GenericConfigurationProvider<Config>,
+  Clock: MonotonicClock
+{
+  /// Commit if the configuration has been left alone for long enough since it was last changed.
+  pub fn tick(&mut self) -> Result<(), Provider::Error> {
+    match self.last_edit {
+      Some(last) if self.clock.now_ms().saturating_sub(last) >= self.quiet_ms => self.flush(),
+      _ => Ok(()),
+    }
+  }
+
+  /// Commit any waiting changes now, without waiting for the edits to settle. If the commit fails, it's tried
+  /// again on the next [`Self::tick`].
+  pub fn flush(&mut self) -> Result<(), Provider::Error> {
+    if self.last_edit.is_none() {
+      return Ok(());
+    }
+    self.provider.commit()?;
+    self.last_edit = None;
+    Ok(())
+  }
+}
+
+impl<Config, Provider, Clock> GenericConfigurationProvider<Config> for DebouncedProvider<Config, Provider, Clock>
+where
+  Provider: GenericConfigurationProvider<Config>,
+  Clock: MonotonicClock
+{
+  type Error = Provider::Error;
+
+  /// Hold the commit back until the edits settle. Nothing is written until then.
+  fn commit(&mut self) -> Result<(), Self::Error> {
+    self.last_edit = Some(self.clock.now_ms());
+    Ok(())
+  }
+
+  fn current(&self) -> &Config {
+    self.provider.current()
+  }
+
+  fn current_mut(&mut self) -> &mut Config {
+    self.last_edit = Some(self.clock.now_ms());
+    self.provider.current_mut()
+  }
+
+  fn is_dirty(&self) -> bool {
+    self.provider.is_dirty()
+  }
+
+  fn peek_stored(&mut self) -> Result<Config, Self::Error> {
+    self.provider.peek_stored()
+  }
+}
@@ -0,0 +1,55 @@
+extern crate alloc;
+
+use core::ops::Range;
+
+use alloc::vec::Vec;
+use binmarshal::{rw::{BitWriter, VecBitWriter}, Marshal, MarshalError};
+
+/// The byte ranges that differ between the serialised forms of two configurations, e.g. a
+/// [`crate::ConfigurationProvider::snapshot`] and the committed configuration, for compact audit records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDiff {
+  changes: Vec<Range<usize>>,
+}
+
+impl ConfigDiff {
+  pub fn between<Config: Marshal<()>>(before: &Config, after: &Config) -> Result<Self, MarshalError> {
+    let mut a = VecBitWriter::new();
+    before.write(&mut a, ())?;
+    let mut b = VecBitWriter::new();
+    after.write(&mut b, ())?;
+    Ok(Self::between_bytes(a.slice(), b.slice()))
+  }
+
+  /// Diff two already-serialised configurations. If the lengths differ, the extra bytes count as changed.
+  pub fn between_bytes(before: &[u8], after: &[u8]) -> Self {
+    let mut changes = Vec::new();
+    let mut start = None;
+
+    for i in 0..before.len().max(after.len()) {
+      let changed = before.get(i) != after.get(i);
+      match (changed, start) {
+        (true, None) => start = Some(i),
+        (false, Some(s)) => {
+          changes.push(s..i);
+          start = None;
+        },
+        _ => (),
+      }
+    }
+    if let Some(s) = start {
+      changes.push(s..before.len().max(after.len()));
+    }
+
+    Self { changes }
+  }
+
+  /// Changed byte ranges, in order of offset.
+  pub fn changes(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+    self.changes.iter().cloned()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.changes.is_empty()
+  }
+}
@@ -0,0 +1,227 @@
+use core::{fmt, marker::PhantomData};
+
+use binmarshal::{rw::{BitView, BitWriter, BufferBitWriter}, DemarshalOwned, Marshal, MarshalError};
+
+use crate::{crc::crc32, ConfigurationMarshal, RecoveryReason};
+
+const MAGIC: [u8; 2] = *b"GD";
+// [magic:2][seq:4][len:2], followed by the payload and its CRC
+const HEADER_LEN: usize = 8;
+const CRC_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DoubleBufferError {
+  BufferTooSmall,
+  Serialisation,
+  /// Neither copy has ever been written.
+  Blank,
+  /// Neither copy is intact.
+  Corrupt,
+}
+
+impl fmt::Display for DoubleBufferError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::BufferTooSmall => f.write_str("the buffer is too small for the configuration"),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Blank => f.write_str("neither copy has been written"),
+      Self::Corrupt => f.write_str("neither copy is intact"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DoubleBufferError { }
+
+/// Keeps two copies of the configuration in a borrowed byte buffer, e.g. a `.noinit` RAM section shared with
+/// a bootloader. Each half of the buffer holds one copy and a sequence number. Writes go to the older copy,
+/// and reads pick the newest intact one, so a reset partway through a write leaves the previous copy intact.
+pub struct DoubleBufferMarshal<'a, Config> {
+  buffer: &'a mut [u8],
+  // (copy, sequence number) of the most recent copy, if known
+  latest: Option<(usize, u32)>,
+  marker: PhantomData<Config>
+}
+
+impl<'a, Config> DoubleBufferMarshal<'a, Config> {
+  pub fn new(buffer: &'a mut [u8]) -> Self {
+    Self { buffer, latest: None, marker: PhantomData }
+  }
+
+  pub fn into_inner(self) -> &'a mut [u8] {
+    self.buffer
+  }
+
+  fn copy(&self, i: usize) -> &[u8] {
+    let half = self.buffer.len() / 2;
+    &self.buffer[i * half..(i + 1) * half]
+  }
+
+  fn copy_mut(&mut self, i: usize) -> &mut [u8] {
+    let half = self.buffer.len() / 2;
+    &mut self.buffer[i * half..(i + 1) * half]
+  }
+
+  // The newest intact copy
+  fn find_latest(&self) -> Result<(usize, u32), DoubleBufferError> {
+    let mut latest: Option<(usize, u32)> = None;
+    let mut error = DoubleBufferError::Blank;
+    for i in 0..2 {
+      match decode(self.copy(i)) {
+        Ok((seq, _)) if latest.map(|(_, l)| seq > l).unwrap_or(true) => latest = Some((i, seq)),
+        Ok(_) | Err(DoubleBufferError::Blank) => (),
+        Err(e) => error = e,
+      }
+    }
+    latest.ok_or(error)
+  }
+}
+
+// Check a copy, returning its sequence number and payload
+fn decode(copy: &[u8]) -> Result<(u32, &[u8]), DoubleBufferError> {
+  if copy.len() < HEADER_LEN + CRC_LEN || copy[..2] != MAGIC {
+    return Err(DoubleBufferError::Blank);
+  }
+  let seq = u32::from_le_bytes([copy[2], copy[3], copy[4], copy[5]]);
+  let len = u16::from_le_bytes([copy[6], copy[7]]) as usize;
+
+  let body = copy.get(HEADER_LEN..HEADER_LEN + len + CRC_LEN).ok_or(DoubleBufferError::Corrupt)?;
+  let (payload, crc) = body.split_at(len);
+  if crc32(payload).to_le_bytes() != crc {
+    return Err(DoubleBufferError::Corrupt);
+  }
+  Ok((seq, payload))
+}
+
+impl<'a, Config> ConfigurationMarshal<Config> for DoubleBufferMarshal<'a, Config>
+where
+  Config: Marshal<()> + DemarshalOwned
+{
+  type Error = DoubleBufferError;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let latest = match self.latest {
+      Some(latest) => Some(latest),
+      None => self.find_latest().ok(),
+    };
+    let (i, seq) = match latest {
+      Some((i, seq)) => (1 - i, seq.wrapping_add(1)),
+      None => (0, 0),
+    };
+
+    let copy = self.copy_mut(i);
+    if copy.len() < HEADER_LEN + CRC_LEN {
+      return Err(DoubleBufferError::BufferTooSmall);
+    }
+
+    // Clear the magic first, so this copy reads back as blank until it's completely written
+    copy[..2].fill(0);
+    // The length field is only 16 bits
+    let body_end = (copy.len() - CRC_LEN).min(HEADER_LEN + u16::MAX as usize);
+    let payload = &mut copy[HEADER_LEN..body_end];
+    payload.fill(0);
+    let mut writer = BufferBitWriter::new(payload);
+    match config.write(&mut writer, ()) {
+      Ok(()) => (),
+      Err(MarshalError::BufferTooSmall) => return Err(DoubleBufferError::BufferTooSmall),
+      Err(_) => return Err(DoubleBufferError::Serialisation),
+    }
+    let len = writer.slice().len();
+
+    let crc = crc32(&copy[HEADER_LEN..HEADER_LEN + len]);
+    copy[HEADER_LEN + len..HEADER_LEN + len + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+    copy[2..6].copy_from_slice(&seq.to_le_bytes());
+    copy[6..8].copy_from_slice(&(len as u16).to_le_bytes());
+    copy[..2].copy_from_slice(&MAGIC);
+
+    self.latest = Some((i, seq));
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let (i, seq) = self.find_latest()?;
+    self.latest = Some((i, seq));
+
+    let (_, payload) = decode(self.copy(i))?;
+    Config::read(&mut BitView::new(payload), ()).map_err(|_| DoubleBufferError::Serialisation)
+  }
+
+  fn capacity(&self) -> usize {
+    (self.buffer.len() / 2).saturating_sub(HEADER_LEN + CRC_LEN).min(u16::MAX as usize)
+  }
+
+  fn free(&mut self) -> Result<usize, Self::Error> {
+    let len = match self.find_latest() {
+      Ok((i, _)) => decode(self.copy(i))?.1.len(),
+      Err(DoubleBufferError::Blank) => 0,
+      Err(e) => return Err(e),
+    };
+    Ok(ConfigurationMarshal::<Config>::capacity(self).saturating_sub(len))
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, DoubleBufferError::Blank)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      Self::Error::Blank => RecoveryReason::Blank,
+      Self::Error::Corrupt | Self::Error::Serialisation => RecoveryReason::Corrupt,
+      Self::Error::BufferTooSmall => RecoveryReason::Unreadable,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use binmarshal::{Demarshal, Marshal};
+
+  use super::*;
+
+  #[derive(Debug, Clone, Default, PartialEq, Marshal, Demarshal)]
+  struct Settings {
+    gain: u32,
+  }
+
+  // The header, payload and CRC of each copy
+  const COPY_LEN: usize = HEADER_LEN + 4 + CRC_LEN;
+
+  fn write(buffer: &mut [u8], gain: u32) {
+    DoubleBufferMarshal::new(buffer).write(&Settings { gain }).unwrap();
+  }
+
+  fn read(buffer: &mut [u8]) -> Result<Settings, DoubleBufferError> {
+    DoubleBufferMarshal::new(buffer).read()
+  }
+
+  #[test]
+  fn newest_copy_is_read() {
+    let mut buffer = [0u8; 2 * COPY_LEN];
+    write(&mut buffer, 1);
+    write(&mut buffer, 2);
+    assert_eq!(read(&mut buffer).unwrap(), Settings { gain: 2 });
+    write(&mut buffer, 3);
+    assert_eq!(read(&mut buffer).unwrap(), Settings { gain: 3 });
+  }
+
+  #[test]
+  fn torn_write_leaves_the_previous_copy() {
+    let mut buffer = [0u8; 2 * COPY_LEN];
+    write(&mut buffer, 1);
+    write(&mut buffer, 2);
+    // The second write went to the second copy
+    buffer[COPY_LEN + HEADER_LEN] ^= 0x01;
+    assert_eq!(read(&mut buffer).unwrap(), Settings { gain: 1 });
+  }
+
+  #[test]
+  fn blank_and_corrupt_buffers_are_told_apart() {
+    let mut buffer = [0u8; 2 * COPY_LEN];
+    assert_eq!(read(&mut buffer), Err(DoubleBufferError::Blank));
+
+    write(&mut buffer, 1);
+    buffer[HEADER_LEN] ^= 0x01;
+    assert_eq!(read(&mut buffer), Err(DoubleBufferError::Corrupt));
+  }
+}
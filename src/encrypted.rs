@@ -0,0 +1,150 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::{vec, vec::Vec};
+use binmarshal::{rw::BitView, DemarshalOwned, Marshal};
+use chacha20::{cipher::{KeyIvInit, StreamCipher}, ChaCha20};
+
+use crate::{crc::crc32, store::ByteStore, wipe::{serialise, Scratch}, ConfigurationMarshal};
+
+// [len:2], after the nonce
+const LEN_LEN: usize = 2;
+const CRC_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EncryptedError<E> {
+  Store(E),
+  Serialisation,
+  Blank,
+  /// The stored configuration didn't decrypt to a valid image, either because it's corrupt or the key
+  /// is wrong.
+  Decrypt,
+}
+
+impl<E: fmt::Debug> fmt::Display for EncryptedError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Store(e) => write!(f, "store error: {:?}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Blank => f.write_str("nothing is stored"),
+      Self::Decrypt => f.write_str("the stored configuration didn't decrypt to a valid image"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for EncryptedError<E> { }
+
+/// Encrypts the serialised configuration with a stream cipher before handing it to a [`ByteStore`].
+///
+/// The stored image is `[nonce][len:2][encrypted payload + CRC32]`. The nonce is a counter, incremented
+/// on every write, so the same key must not be shared by stores that are written independently.
+pub struct EncryptedMarshal<Inner, Config, Cipher = ChaCha20> {
+  inner: Inner,
+  key: [u8; 32],
+  nonce: Option<Vec<u8>>,
+  marker: PhantomData<(Config, Cipher)>
+}
+
+impl<Inner, Config, Cipher> EncryptedMarshal<Inner, Config, Cipher> {
+  pub fn new(inner: Inner, key: [u8; 32]) -> Self {
+    Self { inner, key, nonce: None, marker: PhantomData }
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+}
+
+// Encrypt or decrypt `data` in place. Shared with `SecretFieldsMarshal`, as is `next_nonce`.
+pub(crate) fn apply<Cipher: KeyIvInit + StreamCipher, E>(key: &[u8; 32], nonce: &[u8], data: &mut [u8]) -> Result<(), EncryptedError<E>> {
+  let mut cipher = Cipher::new_from_slices(key, nonce).map_err(|_| EncryptedError::Decrypt)?;
+  cipher.apply_keystream(data);
+  Ok(())
+}
+
+// The nonce after `last`, or if that's not known, after the one stored at the start of `store`
+pub(crate) fn next_nonce<Cipher: KeyIvInit, S: ByteStore>(store: &mut S, last: Option<Vec<u8>>) -> Result<Vec<u8>, EncryptedError<S::Error>> {
+  let mut nonce = match last {
+    Some(nonce) => nonce,
+    None => {
+      // Carry on from whatever was last stored, so power cycles don't reuse a nonce
+      let mut nonce = vec![0u8; Cipher::iv_size()];
+      store.read_at(0, &mut nonce).map_err(EncryptedError::Store)?;
+      nonce
+    },
+  };
+
+  for byte in nonce.iter_mut() {
+    *byte = byte.wrapping_add(1);
+    if *byte != 0 {
+      break;
+    }
+  }
+  Ok(nonce)
+}
+
+impl<Inner, Config, Cipher> ConfigurationMarshal<Config> for EncryptedMarshal<Inner, Config, Cipher>
+where
+  Inner: ByteStore,
+  Config: Marshal<()> + DemarshalOwned,
+  Cipher: KeyIvInit + StreamCipher
+{
+  type Error = EncryptedError<Inner::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let payload = serialise(config, ()).map_err(|_| Self::Error::Serialisation)?;
+
+    let nonce = next_nonce::<Cipher, _>(&mut self.inner, self.nonce.take())?;
+    let mut body = Scratch(Vec::with_capacity(payload.len() + CRC_LEN));
+    body.extend_from_slice(&payload);
+    body.extend_from_slice(&crc32(&payload).to_le_bytes());
+    apply::<Cipher, _>(&self.key, &nonce, &mut body)?;
+
+    let mut image = Vec::with_capacity(nonce.len() + LEN_LEN + body.len());
+    image.extend_from_slice(&nonce);
+    image.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    image.extend_from_slice(&body);
+
+    self.inner.write_at(0, &image).map_err(Self::Error::Store)?;
+    self.nonce = Some(nonce);
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let nonce_len = Cipher::iv_size();
+    let mut header = vec![0u8; nonce_len + LEN_LEN];
+    self.inner.read_at(0, &mut header).map_err(Self::Error::Store)?;
+
+    // Erased EEPROM and flash read back as 0xFF
+    if header.iter().all(|&b| b == 0xFF) {
+      return Err(Self::Error::Blank);
+    }
+
+    let len = u16::from_le_bytes([header[nonce_len], header[nonce_len + 1]]) as usize;
+    if header.len() + len + CRC_LEN > self.inner.capacity() {
+      return Err(Self::Error::Decrypt);
+    }
+
+    let mut body = Scratch(vec![0u8; len + CRC_LEN]);
+    self.inner.read_at(header.len(), &mut body).map_err(Self::Error::Store)?;
+    apply::<Cipher, _>(&self.key, &header[..nonce_len], &mut body)?;
+
+    let (payload, crc) = body.split_at(len);
+    if crc32(payload).to_le_bytes() != crc {
+      return Err(Self::Error::Decrypt);
+    }
+    self.nonce = Some(header[..nonce_len].to_vec());
+
+    match Config::read(&mut BitView::new(payload), ()) {
+      Ok(c) => Ok(c),
+      Err(_) => Err(Self::Error::Serialisation),
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+}
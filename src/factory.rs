@@ -0,0 +1,236 @@
+use core::fmt;
+
+use crate::{CommitObserver, ConfigurationMarshal, ConfigurationProvider, GenericConfigurationProvider, RecoveryReason};
+
+/// One of the copies kept by a [`FactoryMarshal`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Slot {
+  /// The copy the device runs on, and commits go to.
+  User,
+  /// The factory defaults, only written by [`FactoryMarshal::provision`].
+  Factory,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FactoryError<U, F> {
+  User(U),
+  Factory(F),
+}
+
+impl<U: fmt::Debug, F: fmt::Debug> fmt::Display for FactoryError<U, F> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::User(e) => write!(f, "user error: {:?}", e),
+      Self::Factory(e) => write!(f, "factory error: {:?}", e),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<U: fmt::Debug, F: fmt::Debug> std::error::Error for FactoryError<U, F> { }
+
+/// Keeps a user configuration alongside a golden factory copy in separate marshals, e.g. two regions of the
+/// same EEPROM. Reads and writes go to the user copy, falling back to the factory copy if the user one is
+/// blank. The factory copy is only written when provisioning, and [`Self::reset_to_factory`] restores it.
+pub struct FactoryMarshal<U, F> {
+  user: U,
+  factory: F,
+}
+
+impl<U, F> FactoryMarshal<U, F> {
+  pub fn new(user: U, factory: F) -> Self {
+    Self { user, factory }
+  }
+
+  pub fn user_mut(&mut self) -> &mut U {
+    &mut self.user
+  }
+
+  pub fn factory_mut(&mut self) -> &mut F {
+    &mut self.factory
+  }
+
+  pub fn into_inner(self) -> (U, F) {
+    (self.user, self.factory)
+  }
+
+  /// Read one copy in particular, without falling back to the other.
+  pub fn read_from<Config>(&mut self, slot: Slot) -> Result<Config, FactoryError<U::Error, F::Error>>
+  where
+    U: ConfigurationMarshal<Config>,
+    F: ConfigurationMarshal<Config>
+  {
+    match slot {
+      Slot::User => self.user.read().map_err(FactoryError::User),
+      Slot::Factory => self.factory.read().map_err(FactoryError::Factory),
+    }
+  }
+
+  /// Write the factory copy, e.g. on the provisioning line.
+  pub fn provision<Config>(&mut self, config: &Config) -> Result<(), F::Error>
+  where
+    F: ConfigurationMarshal<Config>
+  {
+    self.factory.write(config)
+  }
+
+  /// Copy the factory configuration over the user one, returning it.
+  pub fn reset_to_factory<Config>(&mut self) -> Result<Config, FactoryError<U::Error, F::Error>>
+  where
+    U: ConfigurationMarshal<Config>,
+    F: ConfigurationMarshal<Config>
+  {
+    let config = self.factory.read().map_err(FactoryError::Factory)?;
+    self.user.write(&config).map_err(FactoryError::User)?;
+    Ok(config)
+  }
+}
+
+impl<Config, U, F> ConfigurationMarshal<Config> for FactoryMarshal<U, F>
+where
+  U: ConfigurationMarshal<Config>,
+  F: ConfigurationMarshal<Config>
+{
+  type Error = FactoryError<U::Error, F::Error>;
+  const RETRY_HINT: usize = U::RETRY_HINT;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.user.write(config).map_err(FactoryError::User)
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.user.write_now(config).map_err(FactoryError::User)
+  }
+
+  fn write_changed(&mut self, config: &Config) -> Result<bool, Self::Error> {
+    self.user.write_changed(config).map_err(FactoryError::User)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    match self.user.read() {
+      Err(e) if U::is_blank(&e) => self.factory.read().map_err(FactoryError::Factory),
+      result => result.map_err(FactoryError::User),
+    }
+  }
+
+  fn capacity(&self) -> usize {
+    self.user.capacity()
+  }
+
+  fn free(&mut self) -> Result<usize, Self::Error> {
+    self.user.free().map_err(FactoryError::User)
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    // The user copy is only reported if it wasn't blank
+    matches!(error, FactoryError::Factory(e) if F::is_blank(e))
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      FactoryError::User(e) => U::recovery_reason(e),
+      FactoryError::Factory(e) => F::recovery_reason(e),
+    }
+  }
+
+  fn migrated(&self) -> bool {
+    self.user.migrated()
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.user.revision()
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.user.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.user.last_modified()
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    self.user.written_externally(config)
+  }
+}
+
+impl<Config, U, F, Observer> ConfigurationProvider<Config, FactoryMarshal<U, F>, Observer>
+where
+  U: ConfigurationMarshal<Config>,
+  F: ConfigurationMarshal<Config>,
+  Observer: CommitObserver<Config>
+{
+  /// Restore the factory configuration into the user copy, and make it current.
+  pub fn reset_to_factory(&mut self) -> Result<(), FactoryError<U::Error, F::Error>> {
+    self.volatile = self.marshal.reset_to_factory()?;
+    self.dirty = false;
+    self.observer.committed(&self.volatile);
+    Ok(())
+  }
+
+  /// Write the current configuration as the factory copy. The user copy isn't touched.
+  pub fn provision_factory(&mut self) -> Result<(), F::Error> {
+    self.marshal.provision(&self.volatile)
+  }
+
+  /// Commit the current configuration to one copy in particular. [`Slot::User`] is an ordinary
+  /// [`GenericConfigurationProvider::commit`], and [`Slot::Factory`] goes through [`Self::provision_factory`],
+  /// leaving the user copy and whether there are uncommitted changes alone.
+  pub fn commit_to(&mut self, slot: Slot) -> Result<(), FactoryError<U::Error, F::Error>> {
+    match slot {
+      Slot::User => self.commit(),
+      Slot::Factory => self.provision_factory().map_err(FactoryError::Factory),
+    }
+  }
+
+  /// Read one copy in particular, without falling back to the other or changing the current configuration.
+  pub fn read_from(&mut self, slot: Slot) -> Result<Config, FactoryError<U::Error, F::Error>> {
+    self.marshal.read_from(slot)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use binmarshal::{Demarshal, Marshal};
+
+  use crate::HeaplessMarshal;
+  use super::*;
+
+  #[derive(Debug, Clone, Default, PartialEq, Marshal, Demarshal)]
+  struct Settings {
+    gain: u32,
+  }
+
+  type Factory = FactoryMarshal<HeaplessMarshal<Settings, 8>, HeaplessMarshal<Settings, 8>>;
+
+  fn provider() -> ConfigurationProvider<Settings, Factory> {
+    let marshal = FactoryMarshal::new(HeaplessMarshal::new(), HeaplessMarshal::new());
+    ConfigurationProvider::new(marshal).ok().unwrap()
+  }
+
+  #[test]
+  fn commit_to_factory_only_provisions() {
+    let mut provider = provider();
+    provider.current_mut().gain = 7;
+    provider.commit_to(Slot::Factory).unwrap();
+
+    assert_eq!(provider.read_from(Slot::Factory).unwrap(), Settings { gain: 7 });
+    assert_eq!(provider.read_from(Slot::User).unwrap(), Settings::default());
+    assert_eq!(provider.current(), &Settings { gain: 7 });
+  }
+
+  #[test]
+  fn commit_to_user_leaves_the_factory_copy() {
+    let mut provider = provider();
+    provider.current_mut().gain = 7;
+    provider.commit_to(Slot::Factory).unwrap();
+    provider.current_mut().gain = 9;
+    provider.commit_to(Slot::User).unwrap();
+
+    assert_eq!(provider.read_from(Slot::User).unwrap(), Settings { gain: 9 });
+    provider.reset_to_factory().unwrap();
+    assert_eq!(provider.current(), &Settings { gain: 7 });
+  }
+}
@@ -0,0 +1,218 @@
+use core::fmt;
+
+use crate::{ConfigurationMarshal, RecoveryReason};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FallbackError<A, B> {
+  /// The primary couldn't be written to, or couldn't be read for some reason other than being blank or
+  /// corrupt, e.g. a bus error.
+  Primary(A),
+  /// The primary is blank or corrupt, and reading the secondary failed too.
+  Secondary { primary: A, secondary: B },
+}
+
+impl<A: fmt::Debug, B: fmt::Debug> fmt::Display for FallbackError<A, B> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Primary(e) => write!(f, "primary error: {:?}", e),
+      Self::Secondary { primary, secondary } => write!(f, "primary error: {:?}, and secondary error: {:?}", primary, secondary),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<A: fmt::Debug, B: fmt::Debug> std::error::Error for FallbackError<A, B> { }
+
+/// Reads from the secondary when the primary is blank or corrupt, moving the recovered configuration over to
+/// the primary. Any other failure to read the primary, e.g. a transient bus error, is reported rather than
+/// replacing what may still be a good configuration with an older one. Writes only ever go to the primary, making this suitable for relocating a
+/// configuration to a new region.
+pub struct FallbackMarshal<A, B> {
+  primary: A,
+  secondary: B,
+  fell_back: bool,
+  // Whether the configuration read from the secondary was moved over to the primary
+  moved: bool,
+}
+
+impl<A, B> FallbackMarshal<A, B> {
+  pub fn new(primary: A, secondary: B) -> Self {
+    Self { primary, secondary, fell_back: false, moved: false }
+  }
+
+  /// Whether the last successful read came from the secondary.
+  pub fn fell_back(&self) -> bool {
+    self.fell_back
+  }
+
+  pub fn primary_mut(&mut self) -> &mut A {
+    &mut self.primary
+  }
+
+  pub fn secondary_mut(&mut self) -> &mut B {
+    &mut self.secondary
+  }
+
+  pub fn into_inner(self) -> (A, B) {
+    (self.primary, self.secondary)
+  }
+}
+
+impl<Config, A, B> ConfigurationMarshal<Config> for FallbackMarshal<A, B>
+where
+  A: ConfigurationMarshal<Config>,
+  B: ConfigurationMarshal<Config>
+{
+  type Error = FallbackError<A::Error, B::Error>;
+  const RETRY_HINT: usize = A::RETRY_HINT;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.primary.write(config).map_err(FallbackError::Primary)
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.primary.write_now(config).map_err(FallbackError::Primary)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.fell_back = false;
+
+    let primary = match self.primary.read() {
+      Ok(c) => return Ok(c),
+      Err(e) if A::is_blank(&e) || A::recovery_reason(&e).is_corruption() => e,
+      Err(e) => return Err(FallbackError::Primary(e)),
+    };
+
+    match self.secondary.read() {
+      Ok(c) => {
+        // Best effort - if the move fails we'll just fall back again next time.
+        self.moved = self.primary.write(&c).is_ok();
+        self.fell_back = true;
+        Ok(c)
+      },
+      Err(secondary) => Err(FallbackError::Secondary { primary, secondary }),
+    }
+  }
+
+  /// Like [`Self::read`], but without moving a configuration recovered from the secondary over to the primary,
+  /// which is reported as needing to be written back instead.
+  fn migrate_dry_run(&mut self) -> Result<(Config, bool), Self::Error> {
+    self.fell_back = false;
+
+    let primary = match self.primary.migrate_dry_run() {
+      Ok(r) => return Ok(r),
+      Err(e) if A::is_blank(&e) || A::recovery_reason(&e).is_corruption() => e,
+      Err(e) => return Err(FallbackError::Primary(e)),
+    };
+
+    match self.secondary.migrate_dry_run() {
+      Ok((c, _)) => {
+        self.fell_back = true;
+        self.moved = false;
+        Ok((c, true))
+      },
+      Err(secondary) => Err(FallbackError::Secondary { primary, secondary }),
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    match error {
+      FallbackError::Primary(e) => A::is_blank(e),
+      FallbackError::Secondary { primary, secondary } => A::is_blank(primary) && B::is_blank(secondary),
+    }
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      FallbackError::Primary(e) => A::recovery_reason(e),
+      // A blank secondary says nothing about why the primary was abandoned
+      FallbackError::Secondary { primary, secondary } if B::is_blank(secondary) => A::recovery_reason(primary),
+      FallbackError::Secondary { secondary, .. } => B::recovery_reason(secondary),
+    }
+  }
+
+  fn migrated(&self) -> bool {
+    // A configuration recovered from the secondary needs writing back, unless it's already been moved over
+    match self.fell_back {
+      true => !self.moved,
+      false => self.primary.migrated(),
+    }
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.primary.revision()
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.primary.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.primary.last_modified()
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    self.primary.written_externally(config)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use binmarshal::{Demarshal, Marshal};
+
+  use crate::{fault::{Fault, FaultError, FaultInjectingMarshal}, HeaplessMarshal};
+  use super::*;
+
+  #[derive(Debug, Clone, Default, PartialEq, Marshal, Demarshal)]
+  struct Settings {
+    gain: u32,
+  }
+
+  type Primary = FaultInjectingMarshal<HeaplessMarshal<Settings, 8>>;
+
+  fn marshal(primary: u32, secondary: u32) -> FallbackMarshal<Primary, HeaplessMarshal<Settings, 8>> {
+    let mut a = FaultInjectingMarshal::new(HeaplessMarshal::new());
+    a.write(&Settings { gain: primary }).unwrap();
+    let mut b = HeaplessMarshal::new();
+    b.write(&Settings { gain: secondary }).unwrap();
+    FallbackMarshal::new(a, b)
+  }
+
+  #[test]
+  fn blank_primary_falls_back_and_is_moved_over() {
+    let mut m = marshal(1, 2);
+    m.primary_mut().fail_reads(1, Fault::Blank);
+    assert_eq!(m.read().unwrap(), Settings { gain: 2 });
+    assert!(m.fell_back());
+    assert!(!ConfigurationMarshal::<Settings>::migrated(&m));
+    assert_eq!(m.primary_mut().inner_mut().read().unwrap(), Settings { gain: 2 });
+  }
+
+  #[test]
+  fn corrupt_primary_falls_back() {
+    let mut m = marshal(1, 2);
+    m.primary_mut().fail_reads(1, Fault::Corrupt);
+    assert_eq!(m.read().unwrap(), Settings { gain: 2 });
+    assert!(m.fell_back());
+  }
+
+  #[test]
+  fn transient_primary_error_is_returned_without_touching_the_primary() {
+    let mut m = marshal(1, 2);
+    m.primary_mut().fail_reads(1, Fault::Transient);
+    assert_eq!(m.read(), Err(FallbackError::Primary(FaultError::Injected(Fault::Transient))));
+    assert!(!m.fell_back());
+    assert_eq!(m.primary_mut().writes(), 1);
+    assert_eq!(m.read().unwrap(), Settings { gain: 1 });
+  }
+
+  #[test]
+  fn dry_run_leaves_the_primary_alone() {
+    let mut m = marshal(1, 2);
+    m.primary_mut().fail_reads(1, Fault::Blank);
+    assert_eq!(m.migrate_dry_run().unwrap(), (Settings { gain: 2 }, true));
+    assert_eq!(m.primary_mut().writes(), 1);
+    assert_eq!(m.primary_mut().inner_mut().read().unwrap(), Settings { gain: 1 });
+  }
+}
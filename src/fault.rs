@@ -0,0 +1,274 @@
+use core::fmt;
+
+use crate::{store::ByteStore, ConfigurationMarshal, RecoveryReason};
+
+/// A failure injected by a [`FaultInjectingMarshal`] or [`FaultInjectingStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Fault {
+  /// A one-off failure, like an I2C NACK.
+  Transient,
+  /// Storage that's never been written.
+  Blank,
+  /// A stored configuration that fails its checksum.
+  Corrupt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FaultError<E> {
+  Inner(E),
+  Injected(Fault),
+}
+
+impl<E: fmt::Debug> fmt::Display for FaultError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Inner(e) => write!(f, "marshal error: {:?}", e),
+      Self::Injected(fault) => write!(f, "injected fault: {:?}", fault),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for FaultError<E> { }
+
+// Counts down to the write that should fail
+fn countdown(pending: &mut Option<usize>) -> bool {
+  match pending {
+    Some(1) => {
+      *pending = None;
+      true
+    },
+    Some(n) => {
+      *n -= 1;
+      false
+    },
+    None => false,
+  }
+}
+
+/// Wraps a marshal to fail reads and writes on cue, for testing recovery paths deterministically. Injected
+/// faults never reach the inner marshal.
+pub struct FaultInjectingMarshal<Inner> {
+  inner: Inner,
+  failing_write: Option<usize>,
+  failing_reads: (usize, Fault),
+  writes: usize,
+  reads: usize,
+}
+
+impl<Inner> FaultInjectingMarshal<Inner> {
+  pub fn new(inner: Inner) -> Self {
+    Self { inner, failing_write: None, failing_reads: (0, Fault::Transient), writes: 0, reads: 0 }
+  }
+
+  /// Fail the `n`th write from now, counting from 1, with [`Fault::Transient`].
+  pub fn fail_write(&mut self, n: usize) {
+    self.failing_write = Some(n).filter(|n| *n > 0);
+  }
+
+  /// Fail the next `count` reads with `fault`.
+  pub fn fail_reads(&mut self, count: usize, fault: Fault) {
+    self.failing_reads = (count, fault);
+  }
+
+  /// How many writes have been attempted, including failed ones.
+  pub fn writes(&self) -> usize {
+    self.writes
+  }
+
+  /// How many reads have been attempted, including failed ones.
+  pub fn reads(&self) -> usize {
+    self.reads
+  }
+
+  pub fn inner_mut(&mut self) -> &mut Inner {
+    &mut self.inner
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+}
+
+impl<Config, Inner> ConfigurationMarshal<Config> for FaultInjectingMarshal<Inner>
+where
+  Inner: ConfigurationMarshal<Config>
+{
+  type Error = FaultError<Inner::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.writes += 1;
+    if countdown(&mut self.failing_write) {
+      return Err(Self::Error::Injected(Fault::Transient));
+    }
+    self.inner.write(config).map_err(Self::Error::Inner)
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.writes += 1;
+    if countdown(&mut self.failing_write) {
+      return Err(Self::Error::Injected(Fault::Transient));
+    }
+    self.inner.write_now(config).map_err(Self::Error::Inner)
+  }
+
+  fn write_serialised(&mut self, config: &Config, bytes: &[u8]) -> Result<(), Self::Error> {
+    self.writes += 1;
+    if countdown(&mut self.failing_write) {
+      return Err(Self::Error::Injected(Fault::Transient));
+    }
+    self.inner.write_serialised(config, bytes).map_err(Self::Error::Inner)
+  }
+
+  fn write_changed(&mut self, config: &Config) -> Result<bool, Self::Error> {
+    self.writes += 1;
+    if countdown(&mut self.failing_write) {
+      return Err(Self::Error::Injected(Fault::Transient));
+    }
+    self.inner.write_changed(config).map_err(Self::Error::Inner)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.reads += 1;
+    if self.failing_reads.0 > 0 {
+      self.failing_reads.0 -= 1;
+      return Err(Self::Error::Injected(self.failing_reads.1));
+    }
+    self.inner.read().map_err(Self::Error::Inner)
+  }
+
+  fn capacity(&self) -> usize {
+    self.inner.capacity()
+  }
+
+  fn free(&mut self) -> Result<usize, Self::Error> {
+    self.inner.free().map_err(Self::Error::Inner)
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    match error {
+      FaultError::Inner(e) => Inner::is_blank(e),
+      FaultError::Injected(fault) => *fault == Fault::Blank,
+    }
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      FaultError::Inner(e) => Inner::recovery_reason(e),
+      FaultError::Injected(Fault::Transient) => RecoveryReason::Unreadable,
+      FaultError::Injected(Fault::Blank) => RecoveryReason::Blank,
+      FaultError::Injected(Fault::Corrupt) => RecoveryReason::Corrupt,
+    }
+  }
+
+  fn migrated(&self) -> bool {
+    self.inner.migrated()
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.inner.revision()
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.inner.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.inner.last_modified()
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    self.inner.written_externally(config)
+  }
+}
+
+/// Wraps a [`ByteStore`] to corrupt, blank, or tear its bytes on cue, exercising the framing and recovery
+/// of whichever marshal sits on top of it. Failures come back as [`Fault::Transient`].
+pub struct FaultInjectingStore<Store> {
+  store: Store,
+  failing_write: Option<usize>,
+  // Bytes of the failing write to let through first, simulating a power loss part way in
+  torn_len: usize,
+  failing_reads: usize,
+  corrupt_reads: usize,
+  blank_reads: usize,
+}
+
+impl<Store> FaultInjectingStore<Store> {
+  pub fn new(store: Store) -> Self {
+    Self { store, failing_write: None, torn_len: 0, failing_reads: 0, corrupt_reads: 0, blank_reads: 0 }
+  }
+
+  /// Fail the `n`th write from now, counting from 1, without writing anything.
+  pub fn fail_write(&mut self, n: usize) {
+    self.tear_write(n, 0);
+  }
+
+  /// Tear the `n`th write from now, counting from 1, writing only its first `len` bytes before failing.
+  pub fn tear_write(&mut self, n: usize, len: usize) {
+    self.failing_write = Some(n).filter(|n| *n > 0);
+    self.torn_len = len;
+  }
+
+  /// Fail the next `count` reads.
+  pub fn fail_reads(&mut self, count: usize) {
+    self.failing_reads = count;
+  }
+
+  /// Flip a bit in the last byte of each of the next `count` reads.
+  pub fn corrupt_reads(&mut self, count: usize) {
+    self.corrupt_reads = count;
+  }
+
+  /// Return erased (0xFF) bytes from the next `count` reads, as if the device were blank.
+  pub fn blank_reads(&mut self, count: usize) {
+    self.blank_reads = count;
+  }
+
+  pub fn store_mut(&mut self) -> &mut Store {
+    &mut self.store
+  }
+
+  pub fn into_inner(self) -> Store {
+    self.store
+  }
+}
+
+impl<Store: ByteStore> ByteStore for FaultInjectingStore<Store> {
+  type Error = FaultError<Store::Error>;
+
+  fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Self::Error> {
+    if self.failing_reads > 0 {
+      self.failing_reads -= 1;
+      return Err(Self::Error::Injected(Fault::Transient));
+    }
+    if self.blank_reads > 0 {
+      self.blank_reads -= 1;
+      buf.fill(0xFF);
+      return Ok(());
+    }
+    self.store.read_at(offset, buf).map_err(Self::Error::Inner)?;
+    if self.corrupt_reads > 0 {
+      self.corrupt_reads -= 1;
+      if let Some(last) = buf.last_mut() {
+        *last ^= 0x01;
+      }
+    }
+    Ok(())
+  }
+
+  fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Self::Error> {
+    if countdown(&mut self.failing_write) {
+      let len = self.torn_len.min(bytes.len());
+      self.store.write_at(offset, &bytes[..len]).map_err(Self::Error::Inner)?;
+      return Err(Self::Error::Injected(Fault::Transient));
+    }
+    self.store.write_at(offset, bytes).map_err(Self::Error::Inner)
+  }
+
+  fn capacity(&self) -> usize {
+    self.store.capacity()
+  }
+}
@@ -0,0 +1,120 @@
+use std::{fmt, fs::{self, File, OpenOptions}, io::{self, Read, Seek, SeekFrom, Write}, marker::PhantomData, path::PathBuf};
+
+use binmarshal::{rw::{BitView, BitWriter, VecBitWriter}, DemarshalOwned, Marshal};
+
+use crate::{store::ByteStore, ConfigurationMarshal};
+
+#[derive(Debug)]
+pub enum FileMarshalError {
+  Io(io::Error),
+  Serialisation,
+  Blank
+}
+
+impl fmt::Display for FileMarshalError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Io(e) => write!(f, "I/O error: {}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Blank => f.write_str("nothing is stored"),
+    }
+  }
+}
+
+impl std::error::Error for FileMarshalError { }
+
+impl From<io::Error> for FileMarshalError {
+  fn from(e: io::Error) -> Self {
+    match e.kind() {
+      io::ErrorKind::NotFound => Self::Blank,
+      _ => Self::Io(e),
+    }
+  }
+}
+
+/// Stores the serialised configuration in a file, for desktop tooling and tests that don't have hardware.
+///
+/// Writes go to a temporary sibling file, with `.tmp` appended to the name, which is then renamed over the
+/// target. The rename is atomic on POSIX, so a crash part way through a write leaves the old file intact.
+/// A temporary file left behind by a crash is ignored, and overwritten by the next write.
+pub struct FileMarshal<Config> {
+  path: PathBuf,
+  marker: PhantomData<Config>
+}
+
+impl<Config> FileMarshal<Config> {
+  pub fn new(path: impl Into<PathBuf>) -> Self {
+    Self { path: path.into(), marker: PhantomData }
+  }
+
+  pub fn path(&self) -> &PathBuf {
+    &self.path
+  }
+
+  fn temp_path(&self) -> PathBuf {
+    let mut path = self.path.clone().into_os_string();
+    path.push(".tmp");
+    path.into()
+  }
+}
+
+impl<Config> ConfigurationMarshal<Config> for FileMarshal<Config>
+where
+  Config: Marshal<()> + DemarshalOwned
+{
+  type Error = FileMarshalError;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let mut writer = VecBitWriter::new();
+    if config.write(&mut writer, ()).is_err() {
+      return Err(Self::Error::Serialisation);
+    }
+
+    let temp = self.temp_path();
+    let mut file = OpenOptions::new().write(true).create(true).truncate(true).open(&temp).map_err(FileMarshalError::Io)?;
+    file.write_all(writer.slice()).map_err(FileMarshalError::Io)?;
+    file.sync_all().map_err(FileMarshalError::Io)?;
+    fs::rename(&temp, &self.path).map_err(FileMarshalError::Io)?;
+
+    // Make the rename itself durable. Directories can't be opened this way everywhere, so it's best effort.
+    if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+      File::open(dir).and_then(|dir| dir.sync_all()).ok();
+    }
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let mut buf = vec![];
+    File::open(&self.path)?.read_to_end(&mut buf)?;
+
+    match Config::read(&mut BitView::new(&buf), ()) {
+      Ok(c) => Ok(c),
+      Err(_) => Err(Self::Error::Serialisation),
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+}
+
+/// Files grow as they're written, so there's no fixed capacity. Reading past the end of the file is an
+/// [`io::ErrorKind::UnexpectedEof`] error.
+impl ByteStore for File {
+  type Error = io::Error;
+
+  fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Self::Error> {
+    self.seek(SeekFrom::Start(offset as u64))?;
+    self.read_exact(buf)
+  }
+
+  fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Self::Error> {
+    self.seek(SeekFrom::Start(offset as u64))?;
+    self.write_all(bytes)?;
+    self.sync_data()
+  }
+
+  fn capacity(&self) -> usize {
+    usize::MAX
+  }
+}
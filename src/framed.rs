@@ -0,0 +1,133 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::vec;
+use binmarshal::{rw::{BitView, BitWriter, VecBitWriter}, DemarshalOwned, Marshal};
+
+use crate::{crc::{checksum_bytes, Checksum, Crc32}, store::ByteStore, ConfigurationMarshal, RecoveryReason};
+
+const MAGIC: [u8; 2] = *b"GR";
+const MAGIC_LEN: usize = 2;
+
+// Stores small enough to be addressed by one byte only need one byte of length
+fn len_width(capacity: usize) -> usize {
+  match capacity {
+    0..=0x100 => 1,
+    _ => 2,
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FramedError<E> {
+  Store(E),
+  Serialisation,
+  Blank,
+  Corrupt
+}
+
+impl<E: fmt::Debug> fmt::Display for FramedError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Store(e) => write!(f, "store error: {:?}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Blank => f.write_str("nothing is stored"),
+      Self::Corrupt => f.write_str("the stored configuration is corrupt"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for FramedError<E> { }
+
+/// Serialises the configuration into a `[magic:2][len][payload][checksum]` frame on any [`ByteStore`],
+/// so backends only need to provide raw byte access. The length is a single byte on stores of 256 bytes
+/// or less, and two bytes otherwise. The checksum covers the payload only, and is picked with `C`, e.g.
+/// `FramedMarshal<_, _, Crc16Ccitt>`, from [`crate::crc`].
+pub struct FramedMarshal<Store, Config, C = Crc32> {
+  store: Store,
+  marker: PhantomData<(Config, C)>
+}
+
+impl<Store, Config, C> FramedMarshal<Store, Config, C> {
+  pub fn new(store: Store) -> Self {
+    Self { store, marker: PhantomData }
+  }
+
+  pub fn store_mut(&mut self) -> &mut Store {
+    &mut self.store
+  }
+
+  pub fn into_inner(self) -> Store {
+    self.store
+  }
+}
+
+impl<Store, Config, C> ConfigurationMarshal<Config> for FramedMarshal<Store, Config, C>
+where
+  Store: ByteStore,
+  Config: Marshal<()> + DemarshalOwned,
+  C: Checksum
+{
+  type Error = FramedError<Store::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let mut writer = VecBitWriter::new();
+    if config.write(&mut writer, ()).is_err() {
+      return Err(Self::Error::Serialisation);
+    }
+    let payload = writer.slice();
+
+    let width = len_width(self.store.capacity());
+    let header_len = MAGIC_LEN + width;
+    let mut image = vec![0u8; header_len + payload.len() + C::LEN];
+    image[0..MAGIC_LEN].copy_from_slice(&MAGIC);
+    image[MAGIC_LEN..header_len].copy_from_slice(&(payload.len() as u16).to_le_bytes()[..width]);
+    image[header_len..header_len + payload.len()].copy_from_slice(payload);
+    image[header_len + payload.len()..].copy_from_slice(&checksum_bytes::<C>(payload)[..C::LEN]);
+
+    self.store.write_at(0, &image).map_err(Self::Error::Store)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let width = len_width(self.store.capacity());
+    let header_len = MAGIC_LEN + width;
+    let mut header = [0u8; MAGIC_LEN + 2];
+    self.store.read_at(0, &mut header[..header_len]).map_err(Self::Error::Store)?;
+
+    if header[0..MAGIC_LEN] != MAGIC {
+      return Err(Self::Error::Blank);
+    }
+
+    let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+    if header_len + len + C::LEN > self.store.capacity() {
+      return Err(Self::Error::Corrupt);
+    }
+
+    let mut buf = vec![0u8; len + C::LEN];
+    self.store.read_at(header_len, &mut buf).map_err(Self::Error::Store)?;
+
+    let (payload, checksum) = buf.split_at(len);
+    if checksum_bytes::<C>(payload)[..C::LEN] != *checksum {
+      return Err(Self::Error::Corrupt);
+    }
+
+    match Config::read(&mut BitView::new(payload), ()) {
+      Ok(c) => Ok(c),
+      Err(_) => Err(Self::Error::Serialisation),
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      Self::Error::Blank => RecoveryReason::Blank,
+      Self::Error::Corrupt | Self::Error::Serialisation => RecoveryReason::Corrupt,
+      Self::Error::Store(_) => RecoveryReason::Unreadable,
+    }
+  }
+}
@@ -0,0 +1,166 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::vec::Vec;
+use binmarshal::{rw::{BitView, BitWriter, VecBitWriter}, DemarshalOwned, Marshal, MarshalError};
+
+use crate::ConfigurationMarshal;
+
+/// A configuration stored as one entry per field, so that a commit only rewrites the fields that changed.
+/// `#[derive(Entries)]`, with the `derive` feature, keys each field by [`key_for`] its name, or `#[config(key = ...)]`,
+/// so fields can be reordered, renamed, or added without disturbing the others.
+pub trait Entries {
+  /// The key of every entry.
+  const KEYS: &'static [u16];
+
+  /// Serialise the field stored under `key`.
+  fn write_entry(&self, key: u16) -> Result<Vec<u8>, MarshalError>;
+  /// Deserialise `bytes` into the field stored under `key`. Unknown keys are ignored.
+  fn read_entry(&mut self, key: u16, bytes: &[u8]) -> Result<(), MarshalError>;
+}
+
+/// The key `#[derive(Entries)]` gives the field called `name`: its 32 bit FNV-1a hash, with the upper and lower
+/// halves XORed together.
+pub const fn key_for(name: &str) -> u16 {
+  // Must match the derive's
+  let bytes = name.as_bytes();
+  let mut hash: u32 = 0x811c_9dc5;
+  let mut i = 0;
+  while i < bytes.len() {
+    hash ^= bytes[i] as u32;
+    hash = hash.wrapping_mul(0x0100_0193);
+    i += 1;
+  }
+  ((hash >> 16) ^ (hash & 0xFFFF)) as u16
+}
+
+/// Storage for a set of keyed entries, such as [`crate::seq_storage::SeqStorageKv`].
+pub trait KvStore {
+  type Error;
+
+  /// The entry stored under `key`, or `None` if there isn't one.
+  fn fetch(&mut self, key: u16) -> Result<Option<Vec<u8>>, Self::Error>;
+  fn store(&mut self, key: u16, value: &[u8]) -> Result<(), Self::Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KvError<E> {
+  Store(E),
+  Serialisation,
+  /// None of the configuration's entries are stored.
+  Blank,
+}
+
+impl<E: fmt::Debug> fmt::Display for KvError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Store(e) => write!(f, "store error: {:?}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Blank => f.write_str("none of the configuration's entries are stored"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for KvError<E> { }
+
+/// Stores an [`Entries`] configuration as key-value entries in a [`KvStore`]. Writes skip entries that are
+/// already stored with the same value, and reads fill any missing entries from `Config::default()`.
+pub struct KvMarshal<Config, Store> {
+  store: Store,
+  // The last value read or written for each key
+  known: Vec<(u16, Vec<u8>)>,
+  marker: PhantomData<Config>
+}
+
+impl<Config, Store> KvMarshal<Config, Store> {
+  pub fn new(store: Store) -> Self {
+    Self { store, known: Vec::new(), marker: PhantomData }
+  }
+
+  /// Forget the known values of the stored entries, e.g. after something else has changed them, so that
+  /// the next write fetches them again rather than skipping against a stale copy.
+  pub fn invalidate(&mut self) {
+    self.known.clear();
+  }
+
+  pub fn into_inner(self) -> Store {
+    self.store
+  }
+
+  fn remember(&mut self, key: u16, value: Vec<u8>) {
+    match self.known.iter_mut().find(|(k, _)| *k == key) {
+      Some((_, v)) => *v = value,
+      None => self.known.push((key, value)),
+    }
+  }
+}
+
+impl<Config, Store> ConfigurationMarshal<Config> for KvMarshal<Config, Store>
+where
+  Config: Entries + Default,
+  Store: KvStore
+{
+  type Error = KvError<Store::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.write_changed(config).map(|_| ())
+  }
+
+  fn write_changed(&mut self, config: &Config) -> Result<bool, Self::Error> {
+    let mut changed = false;
+    for key in Config::KEYS.iter().copied() {
+      let value = config.write_entry(key).map_err(|_| KvError::Serialisation)?;
+
+      let unchanged = match self.known.iter().find(|(k, _)| *k == key) {
+        Some((_, known)) => *known == value,
+        None => self.store.fetch(key).map_err(KvError::Store)?.as_ref() == Some(&value),
+      };
+      if !unchanged {
+        self.store.store(key, &value).map_err(KvError::Store)?;
+        changed = true;
+      }
+      self.remember(key, value);
+    }
+    Ok(changed)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let mut config = Config::default();
+    let mut found = false;
+    for key in Config::KEYS.iter().copied() {
+      if let Some(value) = self.store.fetch(key).map_err(KvError::Store)? {
+        config.read_entry(key, &value).map_err(|_| KvError::Serialisation)?;
+        self.remember(key, value);
+        found = true;
+      }
+    }
+
+    match found {
+      true => Ok(config),
+      false => Err(KvError::Blank),
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, KvError::Blank)
+  }
+}
+
+// For the code generated by `#[derive(Entries)]`
+#[doc(hidden)]
+pub use alloc::vec::Vec as __Vec;
+
+#[doc(hidden)]
+pub fn __write_field<T: Marshal<()>>(field: &T) -> Result<Vec<u8>, MarshalError> {
+  let mut writer = VecBitWriter::new();
+  field.write(&mut writer, ())?;
+  Ok(writer.slice().to_vec())
+}
+
+#[doc(hidden)]
+pub fn __read_field<T: DemarshalOwned>(bytes: &[u8]) -> Result<T, MarshalError> {
+  T::read(&mut BitView::new(bytes), ())
+}
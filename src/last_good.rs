@@ -0,0 +1,196 @@
+use core::fmt;
+
+use crate::{CommitObserver, ConfigurationMarshal, ConfigurationProvider, RecoveryReason};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LastGoodError<P, L> {
+  /// The primary couldn't be written to, or couldn't be read for some reason other than being blank or
+  /// corrupt, e.g. a bus error.
+  Primary(P),
+  /// The primary is blank or corrupt, and the last-good copy couldn't be read either.
+  LastGood { primary: P, last_good: L },
+}
+
+impl<P: fmt::Debug, L: fmt::Debug> fmt::Display for LastGoodError<P, L> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Primary(e) => write!(f, "primary error: {:?}", e),
+      Self::LastGood { primary, last_good } => write!(f, "primary error: {:?}, and last-good error: {:?}", primary, last_good),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<P: fmt::Debug, L: fmt::Debug> std::error::Error for LastGoodError<P, L> { }
+
+/// Mirrors every successful write into a second "last good" marshal, and reads from it when the primary is
+/// blank or corrupt, so that a single corruption doesn't lose every setting. Any other failure to read the
+/// primary, e.g. a transient bus error, is reported rather than running on an older copy. Wrap
+/// this in a [`crate::ValidatedMarshal`] to only ever mirror configurations that pass validation.
+///
+/// The primary is left as it is after falling back, so the next commit is what repairs it.
+pub struct LastGoodMarshal<P, L> {
+  primary: P,
+  last_good: L,
+  fell_back: bool,
+}
+
+impl<P, L> LastGoodMarshal<P, L> {
+  pub fn new(primary: P, last_good: L) -> Self {
+    Self { primary, last_good, fell_back: false }
+  }
+
+  /// Whether the configuration last read came from the last-good copy, and hasn't since been written over.
+  pub fn fell_back(&self) -> bool {
+    self.fell_back
+  }
+
+  pub fn primary_mut(&mut self) -> &mut P {
+    &mut self.primary
+  }
+
+  pub fn last_good_mut(&mut self) -> &mut L {
+    &mut self.last_good
+  }
+
+  pub fn into_inner(self) -> (P, L) {
+    (self.primary, self.last_good)
+  }
+}
+
+impl<Config, P, L> ConfigurationMarshal<Config> for LastGoodMarshal<P, L>
+where
+  P: ConfigurationMarshal<Config>,
+  L: ConfigurationMarshal<Config>
+{
+  type Error = LastGoodError<P::Error, L::Error>;
+  const RETRY_HINT: usize = P::RETRY_HINT;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.primary.write(config).map_err(LastGoodError::Primary)?;
+    self.fell_back = false;
+    // Best effort - the primary holds the configuration either way, and the old copy is still good.
+    self.last_good.write(config).ok();
+    Ok(())
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.primary.write_now(config).map_err(LastGoodError::Primary)?;
+    self.fell_back = false;
+    // The mirror is optional, and the old copy is still good if power goes before it's updated.
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.fell_back = false;
+
+    let primary = match self.primary.read() {
+      Ok(c) => return Ok(c),
+      Err(e) if P::is_blank(&e) || P::recovery_reason(&e).is_corruption() => e,
+      Err(e) => return Err(LastGoodError::Primary(e)),
+    };
+
+    match self.last_good.read() {
+      Ok(c) => {
+        self.fell_back = true;
+        Ok(c)
+      },
+      Err(last_good) => Err(LastGoodError::LastGood { primary, last_good }),
+    }
+  }
+
+  fn capacity(&self) -> usize {
+    self.primary.capacity().min(self.last_good.capacity())
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    match error {
+      LastGoodError::Primary(e) => P::is_blank(e),
+      LastGoodError::LastGood { primary, last_good } => P::is_blank(primary) && L::is_blank(last_good),
+    }
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      LastGoodError::Primary(e) => P::recovery_reason(e),
+      // A blank last-good copy says nothing about why the primary was abandoned
+      LastGoodError::LastGood { primary, last_good } if L::is_blank(last_good) => P::recovery_reason(primary),
+      LastGoodError::LastGood { last_good, .. } => L::recovery_reason(last_good),
+    }
+  }
+
+  fn migrated(&self) -> bool {
+    !self.fell_back && self.primary.migrated()
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.primary.revision()
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.primary.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.primary.last_modified()
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    self.primary.written_externally(config)
+  }
+}
+
+impl<Config, P, L, Observer> ConfigurationProvider<Config, LastGoodMarshal<P, L>, Observer>
+where
+  P: ConfigurationMarshal<Config>,
+  L: ConfigurationMarshal<Config>,
+  Observer: CommitObserver<Config>
+{
+  /// Whether the current configuration was recovered from the last-good copy because the primary was
+  /// corrupt, e.g. to warn the user that their latest changes may have been lost.
+  pub fn current_is_fallback(&self) -> bool {
+    self.marshal.fell_back()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use binmarshal::{Demarshal, Marshal};
+
+  use crate::{fault::{Fault, FaultError, FaultInjectingMarshal}, HeaplessMarshal};
+  use super::*;
+
+  #[derive(Debug, Clone, Default, PartialEq, Marshal, Demarshal)]
+  struct Settings {
+    gain: u32,
+  }
+
+  type Primary = FaultInjectingMarshal<HeaplessMarshal<Settings, 8>>;
+
+  fn marshal(primary: u32, last_good: u32) -> LastGoodMarshal<Primary, HeaplessMarshal<Settings, 8>> {
+    let mut p = FaultInjectingMarshal::new(HeaplessMarshal::new());
+    p.write(&Settings { gain: primary }).unwrap();
+    let mut l = HeaplessMarshal::new();
+    l.write(&Settings { gain: last_good }).unwrap();
+    LastGoodMarshal::new(p, l)
+  }
+
+  #[test]
+  fn corrupt_primary_falls_back() {
+    let mut m = marshal(1, 2);
+    m.primary_mut().fail_reads(1, Fault::Corrupt);
+    assert_eq!(m.read().unwrap(), Settings { gain: 2 });
+    assert!(m.fell_back());
+  }
+
+  #[test]
+  fn transient_primary_error_is_returned_without_touching_the_primary() {
+    let mut m = marshal(1, 2);
+    m.primary_mut().fail_reads(1, Fault::Transient);
+    assert_eq!(m.read(), Err(LastGoodError::Primary(FaultError::Injected(Fault::Transient))));
+    assert!(!m.fell_back());
+    assert_eq!(m.primary_mut().writes(), 1);
+    assert_eq!(m.read().unwrap(), Settings { gain: 1 });
+  }
+}
@@ -0,0 +1,61 @@
+use crate::{load, CommitPolicy, ConfigurationMarshal, ConfigurationProvider, GenericConfigurationProvider, ProviderInitError};
+
+/// A [`ConfigurationProvider`] that isn't loaded until it's first used, returned by
+/// [`ConfigurationProvider::deferred`]. The first access reads storage just as [`ConfigurationProvider::new`]
+/// would. If that fails, nothing is kept and the next access tries again.
+pub struct LazyProvider<Config, Marshal> {
+  // Until the configuration is loaded
+  pending: Option<Marshal>,
+  provider: Option<ConfigurationProvider<Config, Marshal>>,
+}
+
+impl<Config, Marshal> LazyProvider<Config, Marshal> {
+  pub const fn new(marshal: Marshal) -> Self {
+    Self { pending: Some(marshal), provider: None }
+  }
+
+  /// Whether the configuration has been loaded yet.
+  pub fn is_loaded(&self) -> bool {
+    self.provider.is_some()
+  }
+}
+
+impl<Config, Marshal> LazyProvider<Config, Marshal>
+where
+  Config: Default,
+  Marshal: ConfigurationMarshal<Config>
+{
+  /// The underlying provider, loading the configuration if this is the first access. Load failures are
+  /// reported as by [`ConfigurationProvider::new`].
+  pub fn provider(&mut self) -> Result<&mut ConfigurationProvider<Config, Marshal>, ProviderInitError<Marshal::Error>> {
+    if let Some(mut marshal) = self.pending.take() {
+      match load(&mut marshal, Config::default) {
+        Ok((volatile, outcome)) => self.provider = Some(ConfigurationProvider { volatile, dirty: false, outcome, deferred: false, policy: CommitPolicy::AlwaysWrite, clock: None, marshal, observer: () }),
+        Err(e) => {
+          self.pending = Some(marshal);
+          return Err(e);
+        },
+      }
+    }
+    // Once there's nothing pending, the provider is always there
+    Ok(self.provider.as_mut().unwrap())
+  }
+
+  pub fn current(&mut self) -> Result<&Config, Marshal::Error> {
+    Ok(self.provider().map_err(ProviderInitError::into_inner)?.current())
+  }
+
+  pub fn current_mut(&mut self) -> Result<&mut Config, Marshal::Error> {
+    Ok(self.provider().map_err(ProviderInitError::into_inner)?.current_mut())
+  }
+
+  pub fn commit(&mut self) -> Result<(), Marshal::Error> {
+    self.provider().map_err(ProviderInitError::into_inner)?.commit()
+  }
+
+  /// The loaded provider, loading it first if need be.
+  pub fn into_inner(mut self) -> Result<ConfigurationProvider<Config, Marshal>, ProviderInitError<Marshal::Error>> {
+    self.provider()?;
+    Ok(self.provider.unwrap())
+  }
+}
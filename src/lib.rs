@@ -1,57 +1,968 @@
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 
 #![doc = include_str!("../README.md")]
 
-use core::{convert::Infallible, marker::PhantomData};
+use core::{fmt, convert::Infallible, marker::PhantomData, mem::ManuallyDrop, ops::{Deref, DerefMut}};
+
+use embedded_hal::blocking::delay::DelayMs;
+
+pub mod asymmetric;
+pub mod bit_packed;
+pub mod bound;
+pub mod budget;
+pub mod buffered;
+pub mod caching;
+pub mod checksum;
+pub mod crc;
+pub mod debounce;
+pub mod diff;
+pub mod double_buffer;
+pub mod factory;
+pub mod fallback;
+pub mod framed;
+pub mod kv;
+pub mod last_good;
+pub mod lazy;
+pub mod locked;
+#[macro_use]
+pub mod logging;
+pub mod migrate;
+pub mod migrating;
+pub mod normalize;
+pub mod observers;
+pub mod overlay;
+pub mod owned;
+pub mod patch;
+pub mod rate_limit;
+pub mod receipt;
+pub mod redundant;
+pub mod remote;
+pub mod retry;
+pub mod shadow;
+pub mod shared;
+pub mod size;
+pub mod slice;
+pub mod staging;
+pub mod static_image;
+pub mod stats;
+pub mod store;
+pub mod tlv;
+pub mod touched;
+pub mod validate;
+
+#[cfg(any(feature = "m24c64", feature = "crypto"))]
+mod wipe;
+
+#[cfg(feature = "async")]
+pub mod asynch;
+
+#[cfg(feature = "at24c")]
+pub mod at24c;
+
+#[cfg(feature = "m24c64")]
+pub mod m24c64;
+
+#[cfg(feature = "nor-flash")]
+pub mod nor_flash;
+
+#[cfg(feature = "compression")]
+pub mod compressed;
+
+#[cfg(feature = "crypto")]
+pub mod encrypted;
+
+#[cfg(feature = "crypto")]
+pub mod secret;
+
+#[cfg(feature = "spi-flash")]
+pub mod spi_flash;
+
+#[cfg(all(feature = "rp2040", target_arch = "arm"))]
+pub mod rp2040;
+
+#[cfg(feature = "sequential-storage")]
+pub mod seq_storage;
+
+#[cfg(feature = "std")]
+pub mod file;
+
+#[cfg(feature = "serde")]
+pub mod postcard;
+
+#[cfg(feature = "text")]
+pub mod text;
+
+#[cfg(feature = "zeroize")]
+pub mod zeroizing;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod fault;
+
+#[cfg(feature = "embedded-hal-1")]
+pub mod compat;
+
+pub use asymmetric::{AsymmetricError, AsymmetricMarshal};
+pub use bit_packed::{BitPackedError, BitPackedMarshal};
+pub use bound::{BoundError, BoundMarshal};
+pub use budget::{WriteBudgetError, WriteBudgetMarshal};
+pub use buffered::BufferedMarshal;
+pub use caching::CachingMarshal;
+pub use checksum::{ChecksumError, ChecksumMarshal};
+pub use debounce::DebouncedProvider;
+pub use diff::ConfigDiff;
+pub use double_buffer::{DoubleBufferError, DoubleBufferMarshal};
+pub use factory::{FactoryError, FactoryMarshal, Slot};
+pub use fallback::{FallbackError, FallbackMarshal};
+pub use framed::{FramedError, FramedMarshal};
+pub use kv::{Entries, KvError, KvMarshal, KvStore};
+pub use last_good::{LastGoodError, LastGoodMarshal};
+pub use lazy::LazyProvider;
+pub use locked::{BusLock, LockedMarshal};
+pub use logging::{LoggingMarshal, LoggingStore};
+pub use migrate::{Migrate, MigrationError, Schema};
+#[cfg(feature = "derive")]
+pub use grapple_config_derive::{ConfigDefault, Entries, MaxSize, Persist, Schema};
+#[cfg(all(feature = "derive", feature = "crypto"))]
+pub use grapple_config_derive::Secrets;
+// For the code generated by the derives
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub use binmarshal as __binmarshal;
+pub use migrating::{MigratingError, MigratingMarshal, MigrationGap, MigrationStep};
+pub use normalize::{Normalize, NormalizingMarshal};
+pub use observers::{CommitObservers, ObserverHandle, ObserversFull};
+pub use overlay::OverlayProvider;
+pub use owned::{HeaplessMarshal, HeaplessMarshalError};
+pub use patch::{encode_patch, PatchError};
+pub use rate_limit::{MonotonicClock, RateLimitedMarshal};
+pub use receipt::{CommitReceipt, ReceiptError};
+pub use redundant::{RedundantError, RedundantMarshal, RedundantSource};
+pub use remote::{Op, RemoteError, RemoteMarshal, Transport};
+pub use retry::RetryMarshal;
+pub use shadow::{ShadowError, ShadowMarshal};
+pub use shared::{SharedCell, SharedConfigurationProvider};
+pub use size::MaxSize;
+pub use slice::{SliceMarshal, SliceMarshalError};
+pub use staging::StagingProvider;
+pub use static_image::{StaticError, StaticMarshal};
+pub use stats::{MarshalStats, StatsStore};
+pub use store::ByteStore;
+pub use tlv::{TlvError, TlvField, TlvMarshal};
+pub use touched::{ResetError, Touched};
+pub use validate::{Validate, ValidatedError, ValidatedMarshal, ValidationError};
 
 pub trait ConfigurationMarshal<Config>
 {
   type Error;
   fn write(&mut self, config: &Config) -> Result<(), Self::Error>;
   fn read(&mut self) -> Result<Config, Self::Error>;
+
+  /// How many times [`ConfigurationProvider`] retries a failed commit, for backends prone to transient
+  /// failures. For an explicit policy, use [`ConfigurationProvider::with_retry`] instead, which replaces this.
+  const RETRY_HINT: usize = 0;
+
+  /// Like [`Self::write`], but as quickly as possible, skipping anything optional such as verifying the write,
+  /// for when power is about to be lost. See [`ConfigurationProvider::flush_now`].
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.write(config)
+  }
+
+  /// Like [`Self::write`], but with `config` already serialised with binmarshal as `bytes`, for marshals that
+  /// can store them as they are rather than serialising `config` again.
+  fn write_serialised(&mut self, config: &Config, bytes: &[u8]) -> Result<(), Self::Error> {
+    let _ = bytes;
+    self.write(config)
+  }
+
+  /// Like [`Self::write`], but returns whether the stored bytes actually changed. Marshals that can't tell
+  /// assume they did.
+  fn write_changed(&mut self, config: &Config) -> Result<bool, Self::Error> {
+    self.write(config)?;
+    Ok(true)
+  }
+
+  /// How many bytes there's room for in the serialised configuration, after any framing. Unbounded backends,
+  /// like files, return `usize::MAX`.
+  fn capacity(&self) -> usize {
+    usize::MAX
+  }
+
+  /// How many more bytes the serialised configuration could grow by before it no longer fits, e.g. to check
+  /// whether there's room to add another entry before committing it.
+  fn free(&mut self) -> Result<usize, Self::Error> {
+    Ok(usize::MAX)
+  }
+
+  /// Whether `error`, as returned from [`Self::read`], means storage has never been written and it's safe
+  /// to overwrite it with a default. Anything else, like a bus error or corruption, is assumed to be worth
+  /// preserving.
+  fn is_blank(error: &Self::Error) -> bool {
+    let _ = error;
+    false
+  }
+
+  /// Why `error`, as returned from [`Self::read`], left the stored configuration unusable, for when it's
+  /// abandoned in favour of a default. See [`InitOutcome::Recovered`].
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    if Self::is_blank(error) { RecoveryReason::Blank } else { RecoveryReason::Unreadable }
+  }
+
+  /// Whether the last [`Self::read`] upgraded an older stored configuration, which should be written back.
+  fn migrated(&self) -> bool {
+    false
+  }
+
+  /// Read the stored configuration, upgrading it in memory if it's of an older version, but without writing
+  /// it back, e.g. to check a migration on a sample device before rolling it out to the fleet. Returns the
+  /// configuration and whether it needed migrating, which is what creating a provider would write back.
+  fn migrate_dry_run(&mut self) -> Result<(Config, bool), Self::Error> {
+    let config = self.read()?;
+    Ok((config, self.migrated()))
+  }
+
+  /// The revision of the stored configuration, persisted alongside it and bumped on every write that changes
+  /// it, as of the last read or write. `None` if it's not known yet, or the marshal doesn't keep one.
+  fn revision(&self) -> Option<u32> {
+    None
+  }
+
+  /// Told the time, from the provider's [`Clock`], just before each commit, to record alongside the next
+  /// configuration written. Marshals with nowhere to keep it ignore it.
+  fn stamp(&mut self, now: u64) {
+    let _ = now;
+  }
+
+  /// When the stored configuration was last modified, as given to [`Self::stamp`] before it was written, as of
+  /// the last read or write. `None` if it's not known yet, it wasn't stamped, or the marshal doesn't keep one.
+  fn last_modified(&self) -> Option<u64> {
+    None
+  }
+
+  /// Told that `config` has been written to storage by something else, e.g. so that cached copies of what's
+  /// stored can be brought up to date. See [`ConfigurationProvider::mark_committed`].
+  fn written_externally(&mut self, config: &Config) {
+    let _ = config;
+  }
 }
 
-pub trait GenericConfigurationProvider<Config>
+// Run a write, retrying up to the marshal's `RETRY_HINT` times
+fn with_retry_hint<Config, Marshal, T>(mut write: impl FnMut() -> Result<T, Marshal::Error>) -> Result<T, Marshal::Error>
 where
-  Config: Clone
+  Marshal: ConfigurationMarshal<Config>
 {
-  fn commit(&mut self) -> bool;
+  let mut attempt = 0;
+  loop {
+    match write() {
+      Err(_) if attempt < Marshal::RETRY_HINT => attempt += 1,
+      result => return result,
+    }
+  }
+}
+
+pub trait GenericConfigurationProvider<Config> {
+  type Error;
+
+  fn commit(&mut self) -> Result<(), Self::Error>;
   fn current(&self) -> &Config;
   fn current_mut(&mut self) -> &mut Config;
+  fn is_dirty(&self) -> bool;
+
+  /// Read what's stored afresh, leaving the current configuration alone, e.g. to show which settings have
+  /// unsaved changes by comparing it against [`Self::current`].
+  fn peek_stored(&mut self) -> Result<Config, Self::Error>;
+
+  fn commit_ok(&mut self) -> bool {
+    self.commit().is_ok()
+  }
+
+  fn commit_if_dirty(&mut self) -> Result<(), Self::Error> {
+    match self.is_dirty() {
+      true => self.commit(),
+      false => Ok(()),
+    }
+  }
+
+  /// Apply `f` to the configuration and commit it.
+  fn update<F: FnOnce(&mut Config)>(&mut self, f: F) -> Result<(), Self::Error> {
+    f(self.current_mut());
+    self.commit()
+  }
+
+  /// Edit the configuration through a guard that commits when it's dropped.
+  fn edit(&mut self) -> EditGuard<'_, Config, Self>
+  where
+    Self: Sized
+  {
+    EditGuard { provider: self, pending: true, marker: PhantomData }
+  }
+
+  /// A handle that can read the configuration, but not change or commit it.
+  fn view(&self) -> ReadOnlyProvider<'_, Config, Self>
+  where
+    Self: Sized
+  {
+    ReadOnlyProvider { provider: self, marker: PhantomData }
+  }
+}
+
+/// Read-only access to a provider, for code that should never change the configuration. See
+/// [`GenericConfigurationProvider::view`].
+pub struct ReadOnlyProvider<'a, Config, Provider> {
+  provider: &'a Provider,
+  marker: PhantomData<Config>
+}
+
+impl<'a, Config, Provider> ReadOnlyProvider<'a, Config, Provider>
+where
+  Provider: GenericConfigurationProvider<Config>
+{
+  pub fn current(&self) -> &'a Config {
+    self.provider.current()
+  }
+
+  pub fn is_dirty(&self) -> bool {
+    self.provider.is_dirty()
+  }
+}
+
+impl<'a, Config, Provider> Clone for ReadOnlyProvider<'a, Config, Provider> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<'a, Config, Provider> Copy for ReadOnlyProvider<'a, Config, Provider> { }
+
+/// Mutable access to a provider's configuration, committed when dropped. See
+/// [`GenericConfigurationProvider::edit`].
+///
+/// A commit that fails on drop can't be returned, but leaves the provider dirty so it's picked up by
+/// [`GenericConfigurationProvider::is_dirty`] and retried by the next commit. Use [`Self::commit`] to handle
+/// the error directly, or [`Self::checkpoint`] to handle it and keep editing.
+pub struct EditGuard<'a, Config, Provider>
+where
+  Provider: GenericConfigurationProvider<Config>
+{
+  provider: &'a mut Provider,
+  // Whether there's anything to commit on drop, i.e. no checkpoint since the last edit
+  pending: bool,
+  marker: PhantomData<Config>
+}
+
+impl<'a, Config, Provider> EditGuard<'a, Config, Provider>
+where
+  Provider: GenericConfigurationProvider<Config>
+{
+  /// Commit now, returning the result instead of committing on drop.
+  pub fn commit(self) -> Result<(), Provider::Error> {
+    ManuallyDrop::new(self).provider.commit()
+  }
+
+  /// Commit now and keep editing, e.g. to save part way through a longer session. If nothing is changed
+  /// afterwards, the guard doesn't commit again when dropped.
+  pub fn checkpoint(&mut self) -> Result<(), Provider::Error> {
+    self.provider.commit()?;
+    self.pending = false;
+    Ok(())
+  }
+
+  /// Finish editing without committing. The changes stay in the provider, uncommitted.
+  pub fn cancel(self) {
+    let _ = ManuallyDrop::new(self);
+  }
+}
+
+impl<'a, Config, Provider> Deref for EditGuard<'a, Config, Provider>
+where
+  Provider: GenericConfigurationProvider<Config>
+{
+  type Target = Config;
+
+  fn deref(&self) -> &Config {
+    self.provider.current()
+  }
+}
+
+impl<'a, Config, Provider> DerefMut for EditGuard<'a, Config, Provider>
+where
+  Provider: GenericConfigurationProvider<Config>
+{
+  fn deref_mut(&mut self) -> &mut Config {
+    self.pending = true;
+    self.provider.current_mut()
+  }
+}
+
+impl<'a, Config, Provider> Drop for EditGuard<'a, Config, Provider>
+where
+  Provider: GenericConfigurationProvider<Config>
+{
+  fn drop(&mut self) {
+    if self.pending {
+      let _ = self.provider.commit();
+    }
+  }
+}
+
+/// Notified with the freshly committed configuration after every successful commit.
+pub trait CommitObserver<Config> {
+  fn committed(&mut self, config: &Config);
+}
+
+impl<Config> CommitObserver<Config> for () {
+  fn committed(&mut self, _config: &Config) { }
+}
+
+impl<Config, F> CommitObserver<Config> for F
+where
+  F: FnMut(&Config)
+{
+  fn committed(&mut self, config: &Config) {
+    self(config)
+  }
+}
+
+/// A source of timestamps for [`ConfigurationProvider::with_clock`], in whatever units suit, e.g. seconds from
+/// an RTC, or an uptime counter on devices without one.
+pub trait Clock {
+  fn now(&self) -> u64;
+}
+
+/// How a [`ConfigurationProvider`] came by its initial configuration. See [`ConfigurationProvider::init_outcome`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InitOutcome {
+  /// A stored configuration was read.
+  Loaded,
+  /// A stored configuration of an older version was read, upgraded, and written back. See [`Migrate`].
+  Migrated,
+  /// Nothing usable was stored, so a default was written, e.g. on first boot.
+  Defaulted,
+  /// A stored configuration couldn't be read, so it was abandoned and a default written in its place. Only
+  /// [`ConfigurationProvider::new_or_default`] and [`load_config`] do this, and log a warning when they do.
+  Recovered { reason: RecoveryReason },
+}
+
+/// Why a stored configuration was abandoned. See [`ConfigurationMarshal::recovery_reason`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RecoveryReason {
+  /// Nothing was stored.
+  Blank,
+  /// The stored configuration failed its checksum, or is otherwise malformed.
+  Corrupt,
+  /// The last write was interrupted before it completed.
+  TornWrite,
+  /// The stored configuration is of a version, or schema, that this firmware can't read.
+  UnsupportedVersion,
+  /// Storage couldn't be read at all, or the marshal can't tell why.
+  Unreadable,
+  /// The stored configuration was read, but failed [`Validate::validate`]. Only [`load_config`] checks.
+  Invalid,
+}
+
+impl RecoveryReason {
+  /// Whether what's stored is damaged, rather than missing, unreadable for now, or just not understood, i.e.
+  /// whether it's safe to replace it without losing anything that could still be read.
+  pub fn is_corruption(self) -> bool {
+    matches!(self, Self::Corrupt | Self::TornWrite)
+  }
+}
+
+/// How [`GenericConfigurationProvider::commit`] decides whether to write. See
+/// [`ConfigurationProvider::with_commit_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CommitPolicy {
+  /// Hand every commit to the marshal, which may still skip identical writes itself, as the M24C64 marshal
+  /// does unless told otherwise with [`crate::m24c64::M24C64ConfigurationMarshal::with_write_elision`].
+  #[default]
+  AlwaysWrite,
+  /// Commit with [`ConfigurationMarshal::write_changed`], as [`ConfigurationProvider::commit_changed`] does,
+  /// so the observer is only called if the stored configuration changed.
+  ElideIdentical,
+  /// Skip commits entirely unless the configuration is dirty. [`GenericConfigurationProvider::current_mut`]
+  /// always marks it dirty, whether or not anything changed.
+  ElideUnlessDirty,
+}
+
+/// What went wrong constructing a [`ConfigurationProvider`], so that boot-time diagnostics can tell a device
+/// that couldn't be read from one that couldn't be written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProviderInitError<E> {
+  /// The stored configuration couldn't be read, and storage isn't blank, so it was left alone. The marshal's
+  /// error tells whether it's corrupt, written by newer firmware, or just unreachable.
+  ReadFailed(E),
+  /// Storage was blank, or unreadable for [`ConfigurationProvider::new_or_default`], but the default
+  /// configuration couldn't be written to it.
+  DefaultWriteFailed(E),
+  /// The stored configuration was upgraded from an older version, but couldn't be written back.
+  MigrationWriteFailed(E),
 }
 
-pub struct ConfigurationProvider<Config, Marshal> {
+impl<E: fmt::Debug> fmt::Display for ProviderInitError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::ReadFailed(e) => write!(f, "couldn't read the stored configuration: {:?}", e),
+      Self::DefaultWriteFailed(e) => write!(f, "couldn't write the default configuration: {:?}", e),
+      Self::MigrationWriteFailed(e) => write!(f, "couldn't write back the migrated configuration: {:?}", e),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for ProviderInitError<E> { }
+
+impl<E> ProviderInitError<E> {
+  /// The marshal's error, whichever step it came from.
+  pub fn into_inner(self) -> E {
+    match self {
+      Self::ReadFailed(e) | Self::DefaultWriteFailed(e) | Self::MigrationWriteFailed(e) => e,
+    }
+  }
+}
+
+pub struct ConfigurationProvider<Config, Marshal, Observer = ()> {
   volatile: Config,
-  marshal: Marshal
+  dirty: bool,
+  outcome: InitOutcome,
+  // Between begin() and end(), commits are held back
+  deferred: bool,
+  policy: CommitPolicy,
+  clock: Option<&'static (dyn Clock + Sync)>,
+  marshal: Marshal,
+  observer: Observer
 }
 
 impl<Config, Marshal> ConfigurationProvider<Config, Marshal>
 where
-  Config: Default + Clone,
   Marshal: ConfigurationMarshal<Config>
 {
-  pub fn new(mut marshal: Marshal) -> Result<Self, Marshal::Error> {
-    let current = marshal.read();
-    match current {
-      Ok(c) => {
-        Ok(Self { marshal, volatile: c })
+  /// Like [`Self::new`], but with the configuration written to blank storage coming from `default`, e.g. for
+  /// defaults that depend on the board revision. `default` is only called if storage is blank.
+  pub fn new_with_default<F: FnOnce() -> Config>(mut marshal: Marshal, default: F) -> Result<Self, ProviderInitError<Marshal::Error>> {
+    let (volatile, outcome) = load(&mut marshal, default)?;
+    Ok(Self { marshal, volatile, dirty: false, outcome, deferred: false, policy: CommitPolicy::AlwaysWrite, clock: None, observer: () })
+  }
+}
+
+// Read the initial configuration for a new provider, writing back migrations and writing `default` to blank storage
+fn load<Config, Marshal, F>(marshal: &mut Marshal, default: F) -> Result<(Config, InitOutcome), ProviderInitError<Marshal::Error>>
+where
+  Marshal: ConfigurationMarshal<Config>,
+  F: FnOnce() -> Config
+{
+  load_with(marshal, |marshal| {
+    let c = default();
+    marshal.write(&c).map(|_| c)
+  })
+}
+
+// As with `load`, but with `write_default` both coming up with the default and writing it
+fn load_with<Config, Marshal, F>(marshal: &mut Marshal, write_default: F) -> Result<(Config, InitOutcome), ProviderInitError<Marshal::Error>>
+where
+  Marshal: ConfigurationMarshal<Config>,
+  F: FnOnce(&mut Marshal) -> Result<Config, Marshal::Error>
+{
+  match marshal.migrate_dry_run() {
+    Ok((c, migrated)) => {
+      if !migrated {
+        return Ok((c, InitOutcome::Loaded));
+      }
+      marshal.write(&c).map_err(ProviderInitError::MigrationWriteFailed)?;
+      Ok((c, InitOutcome::Migrated))
+    },
+    Err(e) if !Marshal::is_blank(&e) => Err(ProviderInitError::ReadFailed(e)),
+    Err(_) => {
+      let c = write_default(marshal).map_err(ProviderInitError::DefaultWriteFailed)?;
+      Ok((c, InitOutcome::Defaulted))
+    },
+  }
+}
+
+impl<Config, Marshal> ConfigurationProvider<Config, Marshal>
+where
+  Config: Default,
+  Marshal: ConfigurationMarshal<Config>
+{
+  pub fn new(marshal: Marshal) -> Result<Self, ProviderInitError<Marshal::Error>> {
+    Self::new_with_default(marshal, Config::default)
+  }
+
+  /// Like [`Self::new`], but with blank storage given `bytes`, baked into firmware, as the default rather than
+  /// the serialised `Config::default()`, e.g. to skip serialising a large default on first boot. `bytes` are
+  /// [`Migrate::VERSION`] followed by the configuration as [`Self::serialise_current`] writes it, and are
+  /// written as they are with [`ConfigurationMarshal::write_serialised`]. If they're of another version, or
+  /// don't deserialise, `Config::default()` is written instead, and a warning logged.
+  pub fn new_with_default_bytes(mut marshal: Marshal, bytes: &'static [u8]) -> Result<Self, ProviderInitError<Marshal::Error>>
+  where
+    Config: binmarshal::DemarshalOwned + Migrate
+  {
+    let (volatile, outcome) = load_with(&mut marshal, |marshal| {
+      let adopted = match bytes.split_first() {
+        Some((&version, payload)) if version == Config::VERSION => {
+          Config::read(&mut binmarshal::rw::BitView::new(payload), ()).ok().map(|c| (c, payload))
+        },
+        _ => None,
+      };
+      match adopted {
+        Some((c, payload)) => marshal.write_serialised(&c, payload).map(|_| c),
+        None => {
+          trace_error!("default bytes aren't of version {}, writing the default instead", Config::VERSION);
+          let c = Config::default();
+          marshal.write(&c).map(|_| c)
+        },
+      }
+    })?;
+    Ok(Self { marshal, volatile, dirty: false, outcome, deferred: false, policy: CommitPolicy::AlwaysWrite, clock: None, observer: () })
+  }
+
+  /// Like [`Self::new`], but without touching storage until the configuration is first needed, e.g. for a
+  /// provider constructed before the bus it sits on is up.
+  pub const fn deferred(marshal: Marshal) -> LazyProvider<Config, Marshal> {
+    LazyProvider::new(marshal)
+  }
+
+  /// Like [`Self::new`], but falls back to writing the default configuration on any read error, not just
+  /// when storage is blank. Whatever was stored is lost.
+  pub fn new_or_default(mut marshal: Marshal) -> Result<Self, ProviderInitError<Marshal::Error>> {
+    match marshal.migrate_dry_run() {
+      Ok((c, migrated)) => {
+        let outcome = match migrated {
+          true => {
+            marshal.write(&c).map_err(ProviderInitError::MigrationWriteFailed)?;
+            InitOutcome::Migrated
+          },
+          false => InitOutcome::Loaded,
+        };
+        Ok(Self { marshal, volatile: c, dirty: false, outcome, deferred: false, policy: CommitPolicy::AlwaysWrite, clock: None, observer: () })
       },
-      Err(_) => {
+      Err(e) => {
+        let outcome = if Marshal::is_blank(&e) {
+          InitOutcome::Defaulted
+        } else {
+          let reason = Marshal::recovery_reason(&e);
+          trace_error!("stored configuration abandoned for the default: {:?}", reason);
+          InitOutcome::Recovered { reason }
+        };
         let c = Config::default();
-        marshal.write(&c)?;
-        Ok(Self { marshal, volatile: c })
+        marshal.write(&c).map_err(ProviderInitError::DefaultWriteFailed)?;
+        Ok(Self { marshal, volatile: c, dirty: false, outcome, deferred: false, policy: CommitPolicy::AlwaysWrite, clock: None, observer: () })
       },
     }
   }
 }
 
-impl<Config, Marshal> GenericConfigurationProvider<Config> for ConfigurationProvider<Config, Marshal>
+/// A provider from [`load_config`], and how it came by its configuration.
+pub type LoadedConfig<Config, Marshal> = (ConfigurationProvider<Config, Marshal>, InitOutcome);
+
+/// Load the configuration at boot in one go: read it, upgrading and writing back an older version, and fall
+/// back to writing the default if nothing is stored, what's stored is corrupt (see
+/// [`RecoveryReason::is_corruption`]), or it fails [`Validate::validate`]. Any other read error, e.g. a bus
+/// error, is returned as [`ProviderInitError::ReadFailed`], leaving storage alone. Returns the provider along
+/// with how it came by its configuration, which it also keeps as [`ConfigurationProvider::init_outcome`].
+pub fn load_config<Config, Marshal>(mut marshal: Marshal) -> Result<LoadedConfig<Config, Marshal>, ProviderInitError<Marshal::Error>>
 where
-  Config: Default + Clone,
+  Config: Default + Validate,
   Marshal: ConfigurationMarshal<Config>
 {
-  fn commit(&mut self) -> bool {
-    self.marshal.write(&self.volatile).is_ok()
+  let (mut volatile, mut outcome) = match load(&mut marshal, Config::default) {
+    Err(ProviderInitError::ReadFailed(e)) if Marshal::recovery_reason(&e).is_corruption() => {
+      let reason = Marshal::recovery_reason(&e);
+      trace_error!("stored configuration abandoned for the default: {:?}", reason);
+      let c = Config::default();
+      marshal.write(&c).map_err(ProviderInitError::DefaultWriteFailed)?;
+      (c, InitOutcome::Recovered { reason })
+    },
+    loaded => loaded?,
+  };
+  if matches!(outcome, InitOutcome::Loaded | InitOutcome::Migrated) {
+    if let Err(e) = volatile.validate() {
+      trace_error!("stored configuration abandoned for the default: invalid {}", e.0);
+      volatile = Config::default();
+      marshal.write(&volatile).map_err(ProviderInitError::DefaultWriteFailed)?;
+      outcome = InitOutcome::Recovered { reason: RecoveryReason::Invalid };
+    }
+  }
+  let provider = ConfigurationProvider { marshal, volatile, dirty: false, outcome, deferred: false, policy: CommitPolicy::AlwaysWrite, clock: None, observer: () };
+  Ok((provider, outcome))
+}
+
+impl<Config, Marshal, Observer> ConfigurationProvider<Config, Marshal, Observer>
+where
+  Config: Default,
+  Marshal: ConfigurationMarshal<Config>,
+  Observer: CommitObserver<Config>
+{
+  /// Restore the default configuration and commit it straight away.
+  pub fn factory_reset(&mut self) -> Result<(), Marshal::Error> {
+    self.volatile = Config::default();
+    // So that it's written under CommitPolicy::ElideUnlessDirty too
+    self.dirty = true;
+    self.commit()
+  }
+}
+
+impl<Config, Marshal, Observer> ConfigurationProvider<Config, Marshal, Observer>
+where
+  Config: binmarshal::Marshal<()>
+{
+  /// Serialise the current, possibly uncommitted, configuration into `buf` without touching storage, e.g. to
+  /// send it over telemetry. Returns the length written. This is the bare binmarshal payload that marshals
+  /// store, without any of their framing such as headers or CRCs.
+  pub fn serialise_current(&self, buf: &mut [u8]) -> Result<usize, binmarshal::MarshalError> {
+    use binmarshal::rw::{BitWriter, BufferBitWriter};
+
+    let mut writer = BufferBitWriter::new(buf);
+    self.write_into(&mut writer)?;
+    Ok(writer.slice().len())
+  }
+
+  /// Like [`Self::serialise_current`], but into any binmarshal writer, from wherever it's got to, e.g. to
+  /// embed the configuration in a larger telemetry frame without an intermediate buffer.
+  pub fn write_into<W: binmarshal::rw::BitWriter>(&self, writer: &mut W) -> Result<(), binmarshal::MarshalError> {
+    self.volatile.write(writer, ())
+  }
+}
+
+impl<Config, Marshal, Observer> ConfigurationProvider<Config, Marshal, Observer>
+where
+  Marshal: ConfigurationMarshal<Config>,
+  Observer: CommitObserver<Config>
+{
+  /// Decide how [`GenericConfigurationProvider::commit`] treats commits that may not change anything.
+  /// Defaults to [`CommitPolicy::AlwaysWrite`].
+  pub fn with_commit_policy(mut self, policy: CommitPolicy) -> Self {
+    self.policy = policy;
+    self
+  }
+
+  /// Stamp each commit with the time from `clock`, for the marshal to store alongside the configuration. See
+  /// [`Self::last_modified`].
+  pub fn with_clock(mut self, clock: &'static (dyn Clock + Sync)) -> Self {
+    self.clock = Some(clock);
+    self
+  }
+
+  /// Register `observer` to be called with the new configuration after each successful commit,
+  /// replacing any previously registered observer. For more than one, use [`CommitObservers`].
+  pub fn on_commit<F: CommitObserver<Config>>(self, observer: F) -> ConfigurationProvider<Config, Marshal, F> {
+    ConfigurationProvider { volatile: self.volatile, dirty: self.dirty, outcome: self.outcome, deferred: self.deferred, policy: self.policy, clock: self.clock, marshal: self.marshal, observer }
+  }
+
+  /// Retry failed commits and reloads up to `retries` times, e.g. to ride out bus contention at boot. The
+  /// wait starts at `backoff_ms` and doubles after each attempt. Only errors accepted by `retryable` are
+  /// retried, so fatal ones such as serialisation failures can be returned straight away.
+  pub fn with_retry<Delay, Retryable>(self, retries: usize, delay: Delay, backoff_ms: u16, retryable: Retryable) -> ConfigurationProvider<Config, RetryMarshal<Marshal, Delay, Retryable>, Observer>
+  where
+    Delay: DelayMs<u16>,
+    Retryable: FnMut(&Marshal::Error) -> bool
+  {
+    let marshal = RetryMarshal::new(self.marshal, retries, delay, backoff_ms, retryable).with_exponential_backoff(true);
+    ConfigurationProvider { volatile: self.volatile, dirty: self.dirty, outcome: self.outcome, deferred: self.deferred, policy: self.policy, clock: self.clock, marshal, observer: self.observer }
+  }
+
+  /// Refuse commits once the configuration has been written `budget` times over the life of the device. See
+  /// [`WriteBudgetMarshal`].
+  pub fn with_write_budget(self, budget: u32) -> ConfigurationProvider<Config, WriteBudgetMarshal<Marshal>, Observer> {
+    let marshal = WriteBudgetMarshal::new(self.marshal, budget);
+    ConfigurationProvider { volatile: self.volatile, dirty: self.dirty, outcome: self.outcome, deferred: self.deferred, policy: self.policy, clock: self.clock, marshal, observer: self.observer }
+  }
+
+  /// Whether the initial configuration was read from storage, or a default was written in its place, e.g. to
+  /// run a setup wizard on first boot.
+  pub fn init_outcome(&self) -> InitOutcome {
+    self.outcome
+  }
+
+  /// Whether a default was written in place of a stored configuration, including one abandoned by
+  /// [`Self::new_or_default`]. See [`Self::init_outcome`].
+  pub fn was_defaulted(&self) -> bool {
+    matches!(self.outcome, InitOutcome::Defaulted | InitOutcome::Recovered { .. })
+  }
+
+  /// Throw away any uncommitted changes, restoring the last committed configuration from the marshal.
+  pub fn discard(&mut self) -> Result<(), Marshal::Error> {
+    self.reload()
+  }
+
+  /// Re-read the configuration from the marshal, e.g. if it may have been changed externally.
+  /// Unlike [`Self::new`], a failed read is returned as-is and storage is left untouched.
+  pub fn reload(&mut self) -> Result<(), Marshal::Error> {
+    self.volatile = self.marshal.read()?;
+    self.dirty = false;
+    Ok(())
+  }
+
+  /// Like [`GenericConfigurationProvider::commit`], but returns whether the stored configuration actually
+  /// changed, as reported by [`ConfigurationMarshal::write_changed`]. The observer is only called if it did.
+  /// While deferred by [`Self::begin`], nothing is written and this returns `false`.
+  pub fn commit_changed(&mut self) -> Result<bool, Marshal::Error> {
+    if self.deferred {
+      self.dirty = true;
+      return Ok(false);
+    }
+    self.stamp();
+    let changed = with_retry_hint::<Config, Marshal, _>(|| self.marshal.write_changed(&self.volatile))?;
+    self.dirty = false;
+    if changed {
+      self.observer.committed(&self.volatile);
+    }
+    Ok(changed)
+  }
+
+  /// Write `config` and, if that succeeds, make it the current configuration, replacing it in a single call,
+  /// e.g. with one received over the network. On failure, the current configuration is left as it was.
+  /// While deferred by [`Self::begin`], it's only written by [`Self::end`].
+  pub fn commit_value(&mut self, config: Config) -> Result<(), Marshal::Error> {
+    self.replace(config).map(|_| ())
+  }
+
+  /// As with [`Self::commit_value`], but returning the configuration it replaced, e.g. to diff against or roll
+  /// back to. That's the current configuration, uncommitted changes and all.
+  pub fn replace(&mut self, config: Config) -> Result<Config, Marshal::Error> {
+    if self.deferred {
+      self.dirty = true;
+      return Ok(core::mem::replace(&mut self.volatile, config));
+    }
+    self.stamp();
+    with_retry_hint::<Config, Marshal, _>(|| self.marshal.write(&config))?;
+    let previous = core::mem::replace(&mut self.volatile, config);
+    self.dirty = false;
+    self.observer.committed(&self.volatile);
+    Ok(previous)
+  }
+
+  /// Write any uncommitted changes straight away with [`ConfigurationMarshal::write_now`], e.g. from a
+  /// brownout interrupt. Together with [`Self::begin`] holding every commit in RAM, this writes the
+  /// configuration once per power cycle. The write isn't retried, and the observer isn't called.
+  ///
+  /// The hold-up time must cover the whole write. On an M24C64 that's around 6ms per 32 byte page the
+  /// image touches, at 400kHz, plus another page for the header's flag byte, so up to 30ms for a 64 byte
+  /// configuration. Whatever the marshal is shared with, such as a bus lock, must be usable from the interrupt.
+  pub fn flush_now(&mut self) -> Result<(), Marshal::Error> {
+    if !self.dirty {
+      return Ok(());
+    }
+    self.stamp();
+    self.marshal.write_now(&self.volatile)?;
+    self.dirty = false;
+    Ok(())
+  }
+
+  /// Start a batch of changes. Until [`Self::end`], commits succeed without writing anything, leaving the
+  /// provider dirty.
+  pub fn begin(&mut self) {
+    self.deferred = true;
+  }
+
+  /// Finish a batch started with [`Self::begin`], committing once if anything in it was changed.
+  pub fn end(&mut self) -> Result<(), Marshal::Error> {
+    self.deferred = false;
+    self.commit_if_dirty()
+  }
+
+  pub fn marshal(&self) -> &Marshal {
+    &self.marshal
+  }
+
+  pub fn marshal_mut(&mut self) -> &mut Marshal {
+    &mut self.marshal
+  }
+
+  /// The revision of the stored configuration, loaded on boot and bumped by every commit that changes it,
+  /// e.g. to tell whether a copy elsewhere is older or newer. 0 if the marshal doesn't keep one. See
+  /// [`ConfigurationMarshal::revision`].
+  pub fn revision(&self) -> u32 {
+    self.marshal.revision().unwrap_or(0)
+  }
+
+  /// When the stored configuration was last modified, by the clock given to [`Self::with_clock`], e.g. for an
+  /// audit log. `None` if there's no clock, or the marshal doesn't keep it. See
+  /// [`ConfigurationMarshal::last_modified`].
+  pub fn last_modified(&self) -> Option<u64> {
+    self.marshal.last_modified()
+  }
+
+  // Tell the marshal the time, if there's a clock, ahead of a write
+  fn stamp(&mut self) {
+    if let Some(clock) = self.clock {
+      self.marshal.stamp(clock.now());
+    }
+  }
+
+  /// Treat the current configuration as committed without writing it, e.g. once something else has written
+  /// the same configuration to storage, so that it isn't written again. The marshal is told with
+  /// [`ConfigurationMarshal::written_externally`], and the observer isn't called.
+  pub fn mark_committed(&mut self) {
+    self.marshal.written_externally(&self.volatile);
+    self.dirty = false;
+  }
+
+  /// Give back the marshal, e.g. to reclaim the peripherals it owns. Uncommitted changes are lost.
+  pub fn into_inner(self) -> Marshal {
+    self.marshal
+  }
+}
+
+// Only these need a copy of the configuration, so only these need it to be cloneable
+impl<Config, Marshal, Observer> ConfigurationProvider<Config, Marshal, Observer>
+where
+  Config: Clone
+{
+  /// A copy of the current, possibly uncommitted, configuration for a later [`Self::restore`].
+  pub fn snapshot(&self) -> Config {
+    self.volatile.clone()
+  }
+
+  /// Put back a configuration taken with [`Self::snapshot`]. Like [`GenericConfigurationProvider::current_mut`],
+  /// this marks the provider dirty.
+  pub fn restore(&mut self, snapshot: Config) {
+    self.volatile = snapshot;
+    self.dirty = true;
+  }
+
+  /// Apply `f` to the configuration, rolling back every change it made if it returns an error. Nothing is
+  /// committed either way.
+  pub fn transaction<T, E, F: FnOnce(&mut Config) -> Result<T, E>>(&mut self, f: F) -> Result<T, E> {
+    let snapshot = self.volatile.clone();
+    match f(&mut self.volatile) {
+      Ok(t) => {
+        self.dirty = true;
+        Ok(t)
+      },
+      Err(e) => {
+        self.volatile = snapshot;
+        Err(e)
+      },
+    }
+  }
+}
+
+impl<Config, Marshal, Observer> GenericConfigurationProvider<Config> for ConfigurationProvider<Config, Marshal, Observer>
+where
+  Marshal: ConfigurationMarshal<Config>,
+  Observer: CommitObserver<Config>
+{
+  type Error = Marshal::Error;
+
+  fn commit(&mut self) -> Result<(), Self::Error> {
+    if self.deferred {
+      self.dirty = true;
+      return Ok(());
+    }
+    match self.policy {
+      CommitPolicy::AlwaysWrite => (),
+      CommitPolicy::ElideIdentical => return self.commit_changed().map(|_| ()),
+      CommitPolicy::ElideUnlessDirty if !self.dirty => return Ok(()),
+      CommitPolicy::ElideUnlessDirty => (),
+    }
+    self.stamp();
+    with_retry_hint::<Config, Marshal, _>(|| self.marshal.write(&self.volatile))?;
+    self.dirty = false;
+    self.observer.committed(&self.volatile);
+    Ok(())
   }
 
   fn current(&self) -> &Config {
@@ -59,101 +970,175 @@ where
   }
 
   fn current_mut(&mut self) -> &mut Config {
+    // We can't see what the caller does with the reference, so assume the worst.
+    self.dirty = true;
     &mut self.volatile
   }
+
+  fn is_dirty(&self) -> bool {
+    self.dirty
+  }
+
+  fn peek_stored(&mut self) -> Result<Config, Self::Error> {
+    self.marshal.read()
+  }
 }
 
-pub struct VolatileMarshal<Config>(PhantomData<Config>);
+/// Keeps the configuration in memory only, e.g. for tests or devices without storage. Reads return the last
+/// written configuration, or the default before anything has been written.
+pub struct VolatileMarshal<Config>(Option<Config>);
 
 impl<Config> VolatileMarshal<Config> {
   pub fn new() -> Self {
-    Self(PhantomData)
+    Self(None)
+  }
+}
+
+impl<Config> Default for VolatileMarshal<Config> {
+  fn default() -> Self {
+    Self::new()
   }
 }
 
 impl<Config> ConfigurationMarshal<Config> for VolatileMarshal<Config>
 where
-  Config: Default
+  Config: Default + Clone
 {
   type Error = Infallible;
 
-  fn write(&mut self, _config: &Config) -> Result<(), Self::Error> {
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.0 = Some(config.clone());
     Ok(())
   }
 
   fn read(&mut self) -> Result<Config, Self::Error> {
-    Ok(Config::default())
+    Ok(self.0.clone().unwrap_or_default())
   }
 }
 
-#[cfg(feature = "m24c64")]
-pub mod m24c64 {
-  extern crate alloc;
+#[cfg(test)]
+mod tests {
+  use binmarshal::{Demarshal, Marshal};
 
-  use core::marker::PhantomData;
+  use crate::fault::{Fault, FaultError, FaultInjectingMarshal};
+  use super::*;
 
-  use binmarshal::{rw::{VecBitWriter, BitWriter, BitView}, Demarshal, DemarshalOwned, Marshal};
-  use embedded_hal::blocking::{i2c, delay::DelayMs};
-  use grapple_m24c64::M24C64;
-  use alloc::vec;
+  #[derive(Debug, Clone, Default, PartialEq, Marshal, Demarshal)]
+  struct Settings {
+    gain: u32,
+  }
 
-  use crate::ConfigurationMarshal;
+  impl Validate for Settings {
+    fn validate(&self) -> Result<(), ValidationError> {
+      Ok(())
+    }
+  }
+
+  type Faulty = FaultInjectingMarshal<HeaplessMarshal<Settings, 8>>;
 
-  pub struct M24C64ConfigurationMarshal<Config, I2C, Delay> {
-    delay: Delay,
-    address_offset: usize,
-    eeprom: M24C64<I2C>,
-    marker: PhantomData<Config>
+  fn stored(gain: u32) -> Faulty {
+    let mut marshal = FaultInjectingMarshal::new(HeaplessMarshal::new());
+    marshal.write(&Settings { gain }).unwrap();
+    marshal
   }
 
-  pub enum M24C64ConfigurationError<E> {
-    Serialisation,
-    I2C(E),
-    BlankEeprom
+  #[test]
+  fn transient_read_error_is_returned_rather_than_defaulted() {
+    let mut marshal = stored(42);
+    marshal.fail_reads(1, Fault::Transient);
+    let result = ConfigurationProvider::<Settings, _>::new(marshal);
+    assert!(matches!(result, Err(ProviderInitError::ReadFailed(FaultError::Injected(Fault::Transient)))));
+
+    let mut marshal = stored(42);
+    marshal.fail_reads(1, Fault::Transient);
+    let result = load_config::<Settings, _>(marshal);
+    assert!(matches!(result, Err(ProviderInitError::ReadFailed(FaultError::Injected(Fault::Transient)))));
   }
 
-  impl<Config, I2C, Delay> M24C64ConfigurationMarshal<Config, I2C, Delay> {
-    #[allow(unused)]
-    pub fn new(eeprom: M24C64<I2C>, address: usize, delay: Delay, marker: PhantomData<Config>) -> Self {
-      Self { delay, address_offset: address, eeprom, marker }
-    }
+  #[test]
+  fn blank_storage_is_defaulted() {
+    let mut marshal = stored(42);
+    marshal.fail_reads(1, Fault::Blank);
+    let (provider, outcome) = load_config::<Settings, _>(marshal).ok().unwrap();
+    assert_eq!(outcome, InitOutcome::Defaulted);
+    assert_eq!(provider.current(), &Settings::default());
   }
 
-  impl<'a, I2C, Delay, Config, E> ConfigurationMarshal<Config> for M24C64ConfigurationMarshal<Config, I2C, Delay>
-  where
-    Config: Marshal<()> + DemarshalOwned + Default + Clone,
-    I2C: i2c::Write<u8, Error = E> + i2c::WriteRead<u8, Error = E>,
-    Delay: DelayMs<u16>
-  {
-    type Error = M24C64ConfigurationError<E>;
+  #[test]
+  fn corrupt_storage_is_recovered_by_load_config_only() {
+    let mut marshal = stored(42);
+    marshal.fail_reads(1, Fault::Corrupt);
+    let result = ConfigurationProvider::<Settings, _>::new(marshal);
+    assert!(matches!(result, Err(ProviderInitError::ReadFailed(FaultError::Injected(Fault::Corrupt)))));
 
-    fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
-      // let bytes = config.to_bytes().map_err(|e| Self::Error::Deku(e))?;
-      let mut writer = VecBitWriter::new();
-      if config.clone().write(&mut writer, ()).is_err() {
-        return Err(Self::Error::Serialisation);
-      }
-      let bytes = writer.slice();
-      self.eeprom.write(self.address_offset, &(bytes.len() as u16).to_le_bytes(), &mut self.delay).map_err(|e| Self::Error::I2C(e))?;
-      self.delay.delay_ms(10u16);
-      self.eeprom.write(self.address_offset + 0x02, &bytes[..], &mut self.delay).map_err(|e| Self::Error::I2C(e))?;
-      Ok(())
-    }
+    let mut marshal = stored(42);
+    marshal.fail_reads(1, Fault::Corrupt);
+    let (mut provider, outcome) = load_config::<Settings, _>(marshal).ok().unwrap();
+    assert_eq!(outcome, InitOutcome::Recovered { reason: RecoveryReason::Corrupt });
+    assert_eq!(provider.marshal.read().unwrap(), Settings::default());
+  }
 
-    fn read(&mut self) -> Result<Config, Self::Error> {
-      let mut len_buf = [0u8; 2];
-      self.eeprom.read(self.address_offset, &mut len_buf[..]).map_err(|e| Self::Error::I2C(e))?;
+  #[test]
+  fn checksum_mismatch_is_recovered_by_load_config() {
+    let mut storage = [0xFFu8; 16];
+    ChecksumMarshal::<_, Settings>::new(&mut storage[..]).write(&Settings { gain: 42 }).unwrap();
+    storage[2] ^= 0x01;
 
-      if len_buf[0] == 255 && len_buf[1] == 255 {
-        return Err(Self::Error::BlankEeprom);
-      }
+    let (provider, outcome) = load_config(ChecksumMarshal::<_, Settings>::new(&mut storage[..])).ok().unwrap();
+    assert_eq!(outcome, InitOutcome::Recovered { reason: RecoveryReason::Corrupt });
+    assert_eq!(provider.current(), &Settings::default());
+  }
 
-      let mut buf = vec![0u8; u16::from_le_bytes(len_buf) as usize];
-      self.eeprom.read(self.address_offset + 0x02, &mut buf[..]).map_err(|e| Self::Error::I2C(e))?;
-      match Config::read(&mut BitView::new(&buf), ()) {
-        Ok(c) => Ok(c),
-        Err(_) => Err(Self::Error::Serialisation),
-      }
+  #[test]
+  fn checksum_mismatch_is_recovered_through_validation() {
+    let mut storage = [0xFFu8; 16];
+    ChecksumMarshal::<_, Settings>::new(&mut storage[..]).write(&Settings { gain: 42 }).unwrap();
+    storage[2] ^= 0x01;
+
+    let marshal = ValidatedMarshal::new(ChecksumMarshal::<_, Settings>::new(&mut storage[..]));
+    let (_, outcome) = load_config(marshal).ok().unwrap();
+    assert_eq!(outcome, InitOutcome::Recovered { reason: RecoveryReason::Corrupt });
+  }
+
+  #[test]
+  fn failed_default_write_is_reported() {
+    let mut marshal = FaultInjectingMarshal::new(HeaplessMarshal::<Settings, 8>::new());
+    marshal.fail_write(1);
+    let result = ConfigurationProvider::<Settings, _>::new(marshal);
+    assert!(matches!(result, Err(ProviderInitError::DefaultWriteFailed(FaultError::Injected(Fault::Transient)))));
+  }
+
+  struct NoBackoff;
+
+  impl embedded_hal::blocking::delay::DelayMs<u16> for NoBackoff {
+    fn delay_ms(&mut self, _ms: u16) {
+      panic!("write_now shouldn't back off");
     }
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn write_now_is_not_retried() {
+    let mut marshal = RetryMarshal::new(stored(1), 3, NoBackoff, 5, |_: &FaultError<_>| true);
+    marshal.inner_mut().fail_write(1);
+    assert!(marshal.write_now(&Settings { gain: 2 }).is_err());
+    assert_eq!(marshal.inner_mut().writes(), 2);
+  }
+
+  #[test]
+  fn write_now_updates_the_cache() {
+    let mut marshal = CachingMarshal::new(stored(1));
+    assert_eq!(marshal.read().unwrap(), Settings { gain: 1 });
+    marshal.write_now(&Settings { gain: 2 }).unwrap();
+    assert_eq!(marshal.read().unwrap(), Settings { gain: 2 });
+  }
+
+  #[test]
+  fn deferred_load_failure_says_which_step_failed() {
+    let mut marshal = stored(42);
+    marshal.fail_reads(1, Fault::Transient);
+    let mut lazy = ConfigurationProvider::<Settings, _>::deferred(marshal);
+    assert!(matches!(lazy.provider(), Err(ProviderInitError::ReadFailed(FaultError::Injected(Fault::Transient)))));
+    assert!(!lazy.is_loaded());
+    assert_eq!(lazy.current().unwrap(), &Settings { gain: 42 });
+  }
+}
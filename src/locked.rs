@@ -0,0 +1,115 @@
+use crate::{store::ByteStore, ConfigurationMarshal, RecoveryReason};
+
+/// Serialises access to a bus shared between tasks, e.g. over an RTOS mutex or a critical section. Every
+/// other user of the bus needs to take the same lock.
+pub trait BusLock {
+  fn lock<R, F: FnOnce() -> R>(&self, f: F) -> R;
+}
+
+impl<T: BusLock> BusLock for &T {
+  fn lock<R, F: FnOnce() -> R>(&self, f: F) -> R {
+    (**self).lock(f)
+  }
+}
+
+/// Holds `lock` for the whole of each operation on the inner marshal or store, so that a multi-transfer
+/// read or write can't be interleaved with another task's use of the bus.
+pub struct LockedMarshal<Inner, Lock> {
+  inner: Inner,
+  lock: Lock,
+}
+
+impl<Inner, Lock> LockedMarshal<Inner, Lock> {
+  pub fn new(inner: Inner, lock: Lock) -> Self {
+    Self { inner, lock }
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+}
+
+impl<Config, Inner, Lock> ConfigurationMarshal<Config> for LockedMarshal<Inner, Lock>
+where
+  Inner: ConfigurationMarshal<Config>,
+  Lock: BusLock
+{
+  type Error = Inner::Error;
+  const RETRY_HINT: usize = Inner::RETRY_HINT;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.lock.lock(|| self.inner.write(config))
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.lock.lock(|| self.inner.write_now(config))
+  }
+
+  fn write_serialised(&mut self, config: &Config, bytes: &[u8]) -> Result<(), Self::Error> {
+    self.lock.lock(|| self.inner.write_serialised(config, bytes))
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.lock.lock(|| self.inner.read())
+  }
+
+  fn write_changed(&mut self, config: &Config) -> Result<bool, Self::Error> {
+    self.lock.lock(|| self.inner.write_changed(config))
+  }
+
+  fn capacity(&self) -> usize {
+    self.inner.capacity()
+  }
+
+  fn free(&mut self) -> Result<usize, Self::Error> {
+    self.lock.lock(|| self.inner.free())
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    Inner::is_blank(error)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    Inner::recovery_reason(error)
+  }
+
+  fn migrated(&self) -> bool {
+    self.inner.migrated()
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.inner.revision()
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.inner.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.inner.last_modified()
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    self.inner.written_externally(config)
+  }
+}
+
+impl<Inner, Lock> ByteStore for LockedMarshal<Inner, Lock>
+where
+  Inner: ByteStore,
+  Lock: BusLock
+{
+  type Error = Inner::Error;
+
+  fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Self::Error> {
+    self.lock.lock(|| self.inner.read_at(offset, buf))
+  }
+
+  fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Self::Error> {
+    self.lock.lock(|| self.inner.write_at(offset, bytes))
+  }
+
+  fn capacity(&self) -> usize {
+    self.inner.capacity()
+  }
+}
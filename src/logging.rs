@@ -0,0 +1,181 @@
+use crate::{store::ByteStore, ConfigurationMarshal, RecoveryReason};
+
+// Logs through `log` if it's enabled, otherwise `defmt`, otherwise compiles to nothing.
+macro_rules! trace {
+  ($fmt:literal $(, $arg:expr)*) => {{
+    #[cfg(feature = "log")]
+    log::debug!($fmt $(, $arg)*);
+    #[cfg(all(feature = "defmt", not(feature = "log")))]
+    defmt::debug!($fmt $(, $arg)*);
+    #[cfg(not(any(feature = "log", feature = "defmt")))]
+    { $(let _ = &$arg;)* }
+  }};
+}
+
+// As with trace!, but at warning level
+macro_rules! trace_error {
+  ($fmt:literal $(, $arg:expr)*) => {{
+    #[cfg(feature = "log")]
+    log::warn!($fmt $(, $arg)*);
+    #[cfg(all(feature = "defmt", not(feature = "log")))]
+    defmt::warn!($fmt $(, $arg)*);
+    #[cfg(not(any(feature = "log", feature = "defmt")))]
+    { $(let _ = &$arg;)* }
+  }};
+}
+
+/// Errors that can be logged by the enabled logging backend: `Debug` for `log`, `defmt::Format` for `defmt`,
+/// and anything at all when neither is enabled.
+#[cfg(feature = "log")]
+pub trait Loggable: core::fmt::Debug { }
+#[cfg(feature = "log")]
+impl<T: core::fmt::Debug> Loggable for T { }
+
+#[cfg(all(feature = "defmt", not(feature = "log")))]
+pub trait Loggable: defmt::Format { }
+#[cfg(all(feature = "defmt", not(feature = "log")))]
+impl<T: defmt::Format> Loggable for T { }
+
+#[cfg(not(any(feature = "log", feature = "defmt")))]
+pub trait Loggable { }
+#[cfg(not(any(feature = "log", feature = "defmt")))]
+impl<T> Loggable for T { }
+
+/// Logs every read and write through the inner marshal, and its outcome. Logs go to `log` or `defmt`,
+/// whichever feature is enabled, and this does nothing at all if neither is.
+pub struct LoggingMarshal<Inner> {
+  inner: Inner,
+  name: &'static str,
+}
+
+impl<Inner> LoggingMarshal<Inner> {
+  /// `name` identifies this marshal in the logs.
+  pub fn new(inner: Inner, name: &'static str) -> Self {
+    Self { inner, name }
+  }
+
+  pub fn inner_mut(&mut self) -> &mut Inner {
+    &mut self.inner
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+}
+
+impl<Config, Inner> ConfigurationMarshal<Config> for LoggingMarshal<Inner>
+where
+  Inner: ConfigurationMarshal<Config>,
+  Inner::Error: Loggable
+{
+  type Error = Inner::Error;
+  const RETRY_HINT: usize = Inner::RETRY_HINT;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    trace!("{}: write", self.name);
+    match self.inner.write(config) {
+      Ok(()) => {
+        trace!("{}: write ok", self.name);
+        Ok(())
+      },
+      Err(e) => {
+        trace_error!("{}: write failed: {:?}", self.name, e);
+        Err(e)
+      },
+    }
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    // Too urgent to log
+    self.inner.write_now(config)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    trace!("{}: read", self.name);
+    match self.inner.read() {
+      Ok(c) => {
+        trace!("{}: read ok, migrated = {}", self.name, self.inner.migrated());
+        Ok(c)
+      },
+      Err(e) => {
+        trace_error!("{}: read failed, blank = {}: {:?}", self.name, Inner::is_blank(&e), e);
+        Err(e)
+      },
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    Inner::is_blank(error)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    Inner::recovery_reason(error)
+  }
+
+  fn migrated(&self) -> bool {
+    self.inner.migrated()
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.inner.revision()
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.inner.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.inner.last_modified()
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    self.inner.written_externally(config)
+  }
+}
+
+/// Logs the offset and length of every read and write through a [`ByteStore`], and its outcome. See
+/// [`LoggingMarshal`].
+pub struct LoggingStore<Store> {
+  store: Store,
+  name: &'static str,
+}
+
+impl<Store> LoggingStore<Store> {
+  pub fn new(store: Store, name: &'static str) -> Self {
+    Self { store, name }
+  }
+
+  pub fn into_inner(self) -> Store {
+    self.store
+  }
+}
+
+impl<Store> ByteStore for LoggingStore<Store>
+where
+  Store: ByteStore,
+  Store::Error: Loggable
+{
+  type Error = Store::Error;
+
+  fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Self::Error> {
+    let result = self.store.read_at(offset, buf);
+    match &result {
+      Ok(()) => trace!("{}: read {} bytes at {}", self.name, buf.len(), offset),
+      Err(e) => trace_error!("{}: read {} bytes at {} failed: {:?}", self.name, buf.len(), offset, e),
+    }
+    result
+  }
+
+  fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Self::Error> {
+    let result = self.store.write_at(offset, bytes);
+    match &result {
+      Ok(()) => trace!("{}: wrote {} bytes at {}", self.name, bytes.len(), offset),
+      Err(e) => trace_error!("{}: write {} bytes at {} failed: {:?}", self.name, bytes.len(), offset, e),
+    }
+    result
+  }
+
+  fn capacity(&self) -> usize {
+    self.store.capacity()
+  }
+}
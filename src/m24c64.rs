@@ -0,0 +1,1621 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData};
+
+use binmarshal::{rw::{BitWriter, BitView, BufferBitWriter}, DemarshalOwned, Marshal};
+use core::convert::Infallible;
+
+use embedded_hal::{blocking::{i2c, delay::DelayMs}, digital::v2::OutputPin};
+use grapple_m24c64::M24C64;
+use alloc::{vec, vec::Vec};
+
+use regions::{EepromRegion, EepromRegionError};
+use crate::{crc::crc32, size::MaxSize, stats::MarshalStats, store::ByteStore, wipe::{serialise, serialise_into, Scratch}, ConfigurationMarshal, Migrate, MigrationError, RecoveryReason};
+
+#[cfg(feature = "async")]
+pub mod asynch;
+pub mod latest;
+pub mod no_alloc;
+pub mod regions;
+mod streaming;
+
+/// The size of the M24C64, in bytes.
+pub const M24C64_CAPACITY: usize = 8192;
+/// The bytes the marshal stores alongside each serialised configuration, for use with [`crate::assert_fits`].
+pub const M24C64_OVERHEAD: usize = HEADER_LEN + CRC_LEN;
+
+const MAGIC: [u8; 2] = *b"GR";
+const FORMAT_VERSION: u8 = 7;
+// [magic:2][format version:1][config version:1][seq:4][len:2][!len:2][flags:1][schema:4][timestamp:8],
+// little-endian except for len and !len when FLAG_BIG_ENDIAN_LEN is set
+const HEADER_LEN: usize = 25;
+const FLAGS_OFFSET: usize = 12;
+// Set when the header is written, and cleared once the body is, so an interrupted write can be told apart
+const FLAG_WRITE_IN_PROGRESS: u8 = 0x01;
+// Set when len and !len are big-endian, so that readers can tell without being told
+const FLAG_BIG_ENDIAN_LEN: u8 = 0x02;
+// Set when the timestamp was given, so that a timestamp of 0 can be told apart from none at all
+const FLAG_TIMESTAMP: u8 = 0x04;
+const CRC_LEN: usize = 4;
+// Writes wrap around within a page, so they must never cross a page boundary
+const PAGE_SIZE: usize = 32;
+const WRITE_CYCLE_MS: u16 = 10;
+
+// Serialise `config` into its header and body (payload followed by CRC)
+#[cfg(feature = "async")]
+fn encode<Config, Ctx, E>(config: &Config, ctx: Ctx, seq: u32) -> Result<([u8; HEADER_LEN], Scratch), M24C64ConfigurationError<E>>
+where
+  Config: Marshal<Ctx> + Migrate,
+  Ctx: Clone
+{
+  // let bytes = config.to_bytes().map_err(|e| Self::Error::Deku(e))?;
+  let payload = serialise(config, ctx).map_err(|_| M24C64ConfigurationError::Serialisation)?;
+  if payload.is_empty() {
+    return Err(M24C64ConfigurationError::EmptySerialisation);
+  }
+
+  let mut body = Scratch(Vec::with_capacity(payload.len() + CRC_LEN));
+  body.extend_from_slice(&payload);
+  body.extend_from_slice(&crc32(&payload).to_le_bytes());
+
+  Ok((encode_header::<Config>(seq, payload.len(), M24C64LengthEndianness::default(), None), body))
+}
+
+// As with `encode`, but serialising the body into `buf`. Returns the header and body length.
+fn encode_into<Config, E>(config: &Config, seq: u32, buf: &mut [u8]) -> Result<([u8; HEADER_LEN], usize), M24C64ConfigurationError<E>>
+where
+  Config: Marshal<()> + Migrate
+{
+  let available = buf.len();
+  let mut writer = BufferBitWriter::new(buf);
+  match config.write(&mut writer, ()) {
+    Ok(()) => (),
+    // We can't know how much space it would've taken, just that it's more than we have
+    Err(binmarshal::MarshalError::BufferTooSmall) => return Err(M24C64ConfigurationError::TooLarge { needed: available + 1, available }),
+    Err(_) => return Err(M24C64ConfigurationError::Serialisation),
+  }
+  let len = writer.slice().len();
+  if len == 0 {
+    return Err(M24C64ConfigurationError::EmptySerialisation);
+  }
+
+  let needed = len + CRC_LEN;
+  if needed > available {
+    return Err(M24C64ConfigurationError::TooLarge { needed, available });
+  }
+  let crc = crc32(&buf[..len]);
+  buf[len..needed].copy_from_slice(&crc.to_le_bytes());
+
+  Ok((encode_header::<Config>(seq, len, M24C64LengthEndianness::default(), None), needed))
+}
+
+fn encode_header<Config: Migrate>(seq: u32, payload_len: usize, endianness: M24C64LengthEndianness, timestamp: Option<u64>) -> [u8; HEADER_LEN] {
+  let mut header = [0u8; HEADER_LEN];
+  header[0..2].copy_from_slice(&MAGIC);
+  header[2] = FORMAT_VERSION;
+  header[3] = Config::VERSION;
+  header[4..8].copy_from_slice(&seq.to_le_bytes());
+  let len = payload_len as u16;
+  let (len, not_len, flags) = match endianness {
+    M24C64LengthEndianness::Little => (len.to_le_bytes(), (!len).to_le_bytes(), FLAG_WRITE_IN_PROGRESS),
+    M24C64LengthEndianness::Big => (len.to_be_bytes(), (!len).to_be_bytes(), FLAG_WRITE_IN_PROGRESS | FLAG_BIG_ENDIAN_LEN),
+  };
+  header[8..10].copy_from_slice(&len);
+  header[10..12].copy_from_slice(&not_len);
+  header[FLAGS_OFFSET] = flags;
+  header[13..17].copy_from_slice(&Config::SCHEMA.to_le_bytes());
+  if let Some(timestamp) = timestamp {
+    header[FLAGS_OFFSET] |= FLAG_TIMESTAMP;
+    header[17..25].copy_from_slice(&timestamp.to_le_bytes());
+  }
+  header
+}
+
+fn decode_timestamp(header: &[u8; HEADER_LEN]) -> Option<u64> {
+  match header[FLAGS_OFFSET] & FLAG_TIMESTAMP {
+    0 => None,
+    _ => Some(u64::from_le_bytes(header[17..25].try_into().unwrap())),
+  }
+}
+
+// The config version, schema, sequence number, and body length
+type DecodedHeader = (u8, u32, u32, usize);
+
+fn decode_header<E>(header: &[u8; HEADER_LEN]) -> Result<DecodedHeader, M24C64ConfigurationError<E>> {
+  if header[0..2] != MAGIC {
+    return Err(M24C64ConfigurationError::BlankEeprom);
+  }
+
+  if header[2] != FORMAT_VERSION {
+    return Err(M24C64ConfigurationError::UnsupportedFormat(header[2]));
+  }
+
+  let version = header[3];
+  let seq = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+  let from_bytes = match header[FLAGS_OFFSET] & FLAG_BIG_ENDIAN_LEN {
+    0 => u16::from_le_bytes,
+    _ => u16::from_be_bytes,
+  };
+  let len = from_bytes([header[8], header[9]]);
+  // Check the length before it's trusted to size the body read
+  if from_bytes([header[10], header[11]]) != !len {
+    return Err(M24C64ConfigurationError::ChecksumMismatch);
+  }
+  let len = len as usize;
+
+  if header[FLAGS_OFFSET] & FLAG_WRITE_IN_PROGRESS != 0 {
+    return Err(M24C64ConfigurationError::TornWrite);
+  }
+  let schema = u32::from_le_bytes([header[13], header[14], header[15], header[16]]);
+  Ok((version, schema, seq, len + CRC_LEN))
+}
+
+// Check that `len` bytes from `address` lie within an EEPROM of `capacity` bytes. Addresses past the end
+// would otherwise wrap around to the start, where they could clobber another region.
+fn check_range<E>(address: usize, len: usize, capacity: usize) -> Result<(), M24C64ConfigurationError<E>> {
+  match address.checked_add(len) {
+    Some(end) if end <= capacity => Ok(()),
+    _ => Err(M24C64ConfigurationError::AddressOutOfRange),
+  }
+}
+
+// Check and strip the CRC from the body, leaving just the payload
+fn check_body<E>(body: &[u8]) -> Result<&[u8], M24C64ConfigurationError<E>> {
+  let (payload, crc) = body.split_at(body.len() - CRC_LEN);
+  if crc32(payload).to_le_bytes() != crc {
+    return Err(M24C64ConfigurationError::ChecksumMismatch);
+  }
+  Ok(payload)
+}
+
+// Write `data`, split into page-aligned chunks, waiting for the write cycle after each
+fn write_paged<I2C, Delay, E>(eeprom: &mut M24C64<I2C>, delay: &mut Delay, write_cycle: M24C64WriteCycle, address: usize, data: &[u8]) -> Result<(), M24C64ConfigurationError<E>>
+where
+  I2C: i2c::Write<u8, Error = E> + i2c::WriteRead<u8, Error = E>,
+  Delay: DelayMs<u16>
+{
+  let mut written = 0;
+  while written < data.len() {
+    let page_remaining = PAGE_SIZE - ((address + written) % PAGE_SIZE);
+    let chunk = &data[written..(written + page_remaining).min(data.len())];
+    eeprom.write(address + written, chunk, delay).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+    wait_for_write_cycle(eeprom, delay, write_cycle, address + written)?;
+    written += chunk.len();
+  }
+  Ok(())
+}
+
+fn wait_for_write_cycle<I2C, Delay, E>(eeprom: &mut M24C64<I2C>, delay: &mut Delay, write_cycle: M24C64WriteCycle, address: usize) -> Result<(), M24C64ConfigurationError<E>>
+where
+  I2C: i2c::Write<u8, Error = E> + i2c::WriteRead<u8, Error = E>,
+  Delay: DelayMs<u16>
+{
+  match write_cycle {
+    M24C64WriteCycle::Delay(ms) => {
+      delay.delay_ms(ms);
+      Ok(())
+    },
+    M24C64WriteCycle::None => Ok(()),
+    M24C64WriteCycle::AckPolling { max_polls } => {
+      // The EEPROM won't acknowledge its address until the internal write cycle is complete
+      let mut probe = [0u8; 1];
+      let mut i = 0;
+      loop {
+        match eeprom.read(address, &mut probe) {
+          Ok(_) => return Ok(()),
+          Err(_) if i < max_polls => (),
+          Err(e) => return Err(M24C64ConfigurationError::I2C(e)),
+        }
+        i += 1;
+        delay.delay_ms(1u16);
+      }
+    },
+  }
+}
+
+// Deserialise the payload, migrating it if it was stored by an older version. Returns whether
+// a migration took place.
+// With `forward_compat`, newer versions are read as the current one, ignoring any trailing bytes. With
+// `lenient`, older versions without a migration are read as the current one, default-filling the missing tail.
+fn decode<Config, Ctx, E>(version: u8, schema: u32, payload: &[u8], ctx: Ctx, forward_compat: bool, lenient: bool) -> Result<(Config, bool), M24C64ConfigurationError<E>>
+where
+  Config: Marshal<Ctx> + DemarshalOwned<Ctx> + Migrate + Default,
+  Ctx: Clone
+{
+  // Other versions are expected to have a different layout. A schema of 0 wasn't recorded, so can't be checked.
+  if version == Config::VERSION && schema != 0 && Config::SCHEMA != 0 && schema != Config::SCHEMA {
+    return Err(M24C64ConfigurationError::SchemaMismatch);
+  }
+  if version > Config::VERSION && !forward_compat {
+    return Err(M24C64ConfigurationError::FutureVersion(version));
+  }
+  if version < Config::VERSION {
+    return match Config::migrate(version, payload) {
+      Ok(config) => Ok((config, true)),
+      Err(MigrationError::UnsupportedVersion(_)) if lenient => Ok((default_fill(payload, ctx)?, true)),
+      Err(e) => Err(M24C64ConfigurationError::Migration(e)),
+    };
+  }
+
+  let mut view = BitView::new(payload);
+  let config = Config::read(&mut view, ctx).map_err(|_| M24C64ConfigurationError::Serialisation)?;
+  // Newer versions are expected to leave bytes behind, but the current one should use up the whole payload,
+  // unless it's being read leniently
+  if version == Config::VERSION && view.remaining().0 != 0 && !(forward_compat || lenient) {
+    return Err(M24C64ConfigurationError::TrailingData);
+  }
+  Ok((config, false))
+}
+
+// Deserialise a payload that may be cut short, taking any bytes past its end from `Config::default()`
+fn default_fill<Config, Ctx, E>(payload: &[u8], ctx: Ctx) -> Result<Config, M24C64ConfigurationError<E>>
+where
+  Config: Marshal<Ctx> + DemarshalOwned<Ctx> + Default,
+  Ctx: Clone
+{
+  let mut bytes = serialise(&Config::default(), ctx.clone()).map_err(|_| M24C64ConfigurationError::Serialisation)?;
+  if payload.len() < bytes.len() {
+    bytes[..payload.len()].copy_from_slice(payload);
+  } else {
+    bytes = Scratch(payload.to_vec());
+  }
+
+  Config::read(&mut BitView::new(&bytes), ctx).map_err(|_| M24C64ConfigurationError::Serialisation)
+}
+
+/// How the configuration image is laid out on the EEPROM, starting at `address_offset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum M24C64Layout {
+  /// A single image, rewritten in place on every write.
+  Single,
+  /// Two slots of `slot_size` bytes each. Writes alternate between the slots, and reads pick the
+  /// valid slot with the highest sequence number, so a torn write leaves the previous image intact.
+  /// Images larger than a slot fail to write with [`M24C64ConfigurationError::TooLarge`], before either slot is
+  /// touched, e.g. once a configuration has grown across a firmware update.
+  WearLevelled { slot_size: usize },
+  /// A one-byte active marker followed by two slots of `slot_size` bytes each. New images are
+  /// written to the inactive slot and verified before the marker is flipped to point at them.
+  Atomic { slot_size: usize },
+}
+
+impl M24C64Layout {
+  /// [`Self::WearLevelled`], with slots just large enough for `Config`.
+  pub const fn wear_levelled_for<Config: MaxSize>() -> Self {
+    Self::WearLevelled { slot_size: image_size::<Config>() }
+  }
+
+  /// [`Self::Atomic`], with slots just large enough for `Config`.
+  pub const fn atomic_for<Config: MaxSize>() -> Self {
+    Self::Atomic { slot_size: image_size::<Config>() }
+  }
+
+  /// The bytes a region must reserve to store `Config` with this layout, e.g. for
+  /// [`regions::EepromRegions::allocate`]: an image for [`Self::Single`], and both slots, plus any marker, for
+  /// the others. Slots must be at least [`image_size`] bytes for `Config` to fit.
+  pub const fn region_size<Config: MaxSize>(&self) -> usize {
+    match self {
+      Self::Single => image_size::<Config>(),
+      Self::WearLevelled { slot_size } => 2 * *slot_size,
+      Self::Atomic { slot_size } => 1 + 2 * *slot_size,
+    }
+  }
+}
+
+/// The most bytes an image of `Config` takes up on the EEPROM, header and all.
+pub const fn image_size<Config: MaxSize>() -> usize {
+  Config::MAX_SERIALISED_LEN + M24C64_OVERHEAD
+}
+
+/// How to wait for the EEPROM's internal write cycle to finish after each page is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum M24C64WriteCycle {
+  /// Wait a fixed number of milliseconds.
+  Delay(u16),
+  /// Poll the EEPROM every millisecond until it acknowledges again, giving up after `max_polls`.
+  AckPolling { max_polls: u16 },
+  /// Don't wait at all, for pin-compatible FRAM parts such as the FM24CL64 that commit writes immediately.
+  None,
+}
+
+impl Default for M24C64WriteCycle {
+  fn default() -> Self {
+    Self::AckPolling { max_polls: WRITE_CYCLE_MS }
+  }
+}
+
+/// The byte order of the length in each image's header. The rest of the header is always little-endian.
+/// Which one was used is recorded in the header's flags, so images of either can be read back regardless.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum M24C64LengthEndianness {
+  #[default]
+  Little,
+  /// For regions shared with other tools that expect a big-endian length.
+  Big,
+}
+
+/// The default write-protect "pin" for [`M24C64ConfigurationMarshal`], for boards with WP tied low.
+pub struct NoWriteProtect;
+
+impl OutputPin for NoWriteProtect {
+  type Error = Infallible;
+
+  fn set_low(&mut self) -> Result<(), Self::Error> {
+    Ok(())
+  }
+
+  fn set_high(&mut self) -> Result<(), Self::Error> {
+    Ok(())
+  }
+}
+
+/// The result of [`M24C64ConfigurationMarshal::check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConfigHealth {
+  /// The stored [`Migrate::VERSION`], if the header could be read.
+  pub version: Option<u8>,
+  /// The length of the stored serialised configuration, if the header could be read.
+  pub len: Option<u16>,
+  /// Whether the image a read would return is intact.
+  pub healthy: bool,
+}
+
+/// `Ctx` is the binmarshal context the configuration is written and read with. See [`Self::with_context`].
+///
+/// The EEPROM needn't have the bus to itself: any embedded-hal 0.2 I2C will do, bus sharing proxies included,
+/// as will an embedded-hal 1.0 one, e.g. a shared bus device from embedded-hal-bus, through `compat::I2cCompat`
+/// with the `embedded-hal-1` feature.
+pub struct M24C64ConfigurationMarshal<Config, I2C, Delay, Wp = NoWriteProtect, Ctx = ()> {
+  delay: Delay,
+  address_offset: usize,
+  layout: M24C64Layout,
+  write_cycle: M24C64WriteCycle,
+  capacity: usize,
+  verify: bool,
+  elide_writes: bool,
+  diff_writes: bool,
+  length_endianness: M24C64LengthEndianness,
+  forward_compat: bool,
+  lenient_read: bool,
+  stats: MarshalStats,
+  // (slot, sequence number) of the most recent image, if known
+  latest: Option<(usize, u32)>,
+  // The payload of the most recent image, if known and of the current version, to elide writes against
+  cached: Option<Scratch>,
+  // Serialised into and read into, and swapped with `cached` after each write, so it's only ever allocated
+  // while growing
+  buffer: Scratch,
+  // The timestamp in each slot's header, as of the last read or write
+  timestamps: [Option<u64>; 2],
+  // Recorded in the next image written. See ConfigurationMarshal::stamp.
+  stamp: Option<u64>,
+  migrated: bool,
+  eeprom: M24C64<I2C>,
+  write_protect: Wp,
+  ctx: Ctx,
+  marker: PhantomData<Config>
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum M24C64ConfigurationError<E> {
+  Serialisation,
+  /// The configuration serialised to nothing at all, which no valid configuration should, e.g. because of a
+  /// buggy [`Marshal`] impl.
+  EmptySerialisation,
+  I2C(E),
+  BlankEeprom,
+  ChecksumMismatch,
+  /// The last write was interrupted, e.g. by a power loss, before it completed.
+  TornWrite,
+  UnsupportedFormat(u8),
+  TooLarge { needed: usize, available: usize },
+  VerifyFailed,
+  /// The stored length doesn't match the configuration, or runs past the end of the EEPROM.
+  LengthMismatch,
+  /// The stored configuration has the current version, but deserialising it left bytes unread, e.g. because
+  /// fields were removed without bumping [`Migrate::VERSION`]. Ignored with [`M24C64ConfigurationMarshal::with_forward_compat`]
+  /// or [`M24C64ConfigurationMarshal::with_lenient_read`].
+  TrailingData,
+  /// The configuration's address, as configured, lies past the end of the EEPROM.
+  AddressOutOfRange,
+  /// The write-protect pin couldn't be driven.
+  WriteProtected,
+  /// The stored configuration has the current version, but was written with a different [`Migrate::SCHEMA`].
+  SchemaMismatch,
+  /// The stored configuration was written by newer firmware, with this version.
+  FutureVersion(u8),
+  Migration(MigrationError)
+}
+
+impl<E: fmt::Debug> fmt::Display for M24C64ConfigurationError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::EmptySerialisation => f.write_str("the configuration serialised to nothing"),
+      Self::I2C(e) => write!(f, "I2C error: {:?}", e),
+      Self::BlankEeprom => f.write_str("the EEPROM is blank"),
+      Self::ChecksumMismatch => f.write_str("the stored configuration failed its checksum"),
+      Self::TornWrite => f.write_str("the last write was interrupted before it completed"),
+      Self::UnsupportedFormat(format) => write!(f, "unsupported storage format {}", format),
+      Self::TooLarge { needed, available } => write!(f, "the image needs {} bytes, but only {} are available", needed, available),
+      Self::VerifyFailed => f.write_str("the image read back differently from how it was written"),
+      Self::LengthMismatch => f.write_str("the stored length doesn't match the configuration"),
+      Self::TrailingData => f.write_str("the stored configuration has bytes left over after deserialising"),
+      Self::AddressOutOfRange => f.write_str("the configuration's address lies past the end of the EEPROM"),
+      Self::WriteProtected => f.write_str("the write-protect pin couldn't be driven"),
+      Self::SchemaMismatch => f.write_str("the stored configuration was written with a different schema"),
+      Self::FutureVersion(version) => write!(f, "the stored configuration is of version {}, written by newer firmware", version),
+      Self::Migration(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for M24C64ConfigurationError<E> { }
+
+impl<E> M24C64ConfigurationError<E> {
+  // See ConfigurationMarshal::recovery_reason
+  pub(crate) fn recovery_reason(&self) -> RecoveryReason {
+    match self {
+      Self::BlankEeprom => RecoveryReason::Blank,
+      Self::ChecksumMismatch | Self::LengthMismatch => RecoveryReason::Corrupt,
+      Self::TornWrite => RecoveryReason::TornWrite,
+      Self::UnsupportedFormat(_) | Self::SchemaMismatch | Self::TrailingData | Self::FutureVersion(_) | Self::Migration(_) => RecoveryReason::UnsupportedVersion,
+      _ => RecoveryReason::Unreadable,
+    }
+  }
+}
+
+impl<Config, I2C, Delay> M24C64ConfigurationMarshal<Config, I2C, Delay> {
+  #[allow(unused)]
+  pub fn new(eeprom: M24C64<I2C>, address: usize, delay: Delay, marker: PhantomData<Config>) -> Self {
+    Self::new_with_layout(eeprom, address, M24C64Layout::Single, delay, marker)
+  }
+
+  pub fn new_with_layout(eeprom: M24C64<I2C>, address: usize, layout: M24C64Layout, delay: Delay, marker: PhantomData<Config>) -> Self {
+    Self { delay, address_offset: address, layout, write_cycle: M24C64WriteCycle::default(), capacity: M24C64_CAPACITY, verify: false, elide_writes: true, diff_writes: false, length_endianness: M24C64LengthEndianness::Little, forward_compat: false, lenient_read: false, stats: MarshalStats::default(), latest: None, cached: None, buffer: Scratch(Vec::new()), timestamps: [None; 2], stamp: None, migrated: false, eeprom, write_protect: NoWriteProtect, ctx: (), marker }
+  }
+
+  /// Store the configuration on a pin-compatible FRAM part, such as the FM24CL64. FRAM has no write cycle to
+  /// wait on and effectively unlimited endurance, so writes go back-to-back into a single image.
+  pub fn new_fram(eeprom: M24C64<I2C>, address: usize, delay: Delay, marker: PhantomData<Config>) -> Self {
+    Self::new(eeprom, address, delay, marker).with_write_cycle(M24C64WriteCycle::None)
+  }
+
+  /// Store the configuration within `region`, refusing any write that would extend past its end.
+  pub fn new_in_region(eeprom: M24C64<I2C>, region: EepromRegion, layout: M24C64Layout, delay: Delay, marker: PhantomData<Config>) -> Self {
+    Self::new_with_layout(eeprom, region.start, layout, delay, marker).with_capacity(region.end())
+  }
+
+  /// Store the configuration within `region`, wear levelled across two slots of `slot_size` bytes each. Size
+  /// the slots for the largest the configuration will ever grow to, e.g. the [`image_size`] of a future
+  /// version, so that growing it later doesn't change where the second slot starts. Size the region with
+  /// [`M24C64Layout::region_size`]. Fails with [`EepromRegionError::OutOfSpace`] if both slots don't fit.
+  pub fn new_wear_levelled(eeprom: M24C64<I2C>, region: EepromRegion, slot_size: usize, delay: Delay, marker: PhantomData<Config>) -> Result<Self, EepromRegionError> {
+    if slot_size < M24C64_OVERHEAD || slot_size > region.len / 2 {
+      return Err(EepromRegionError::OutOfSpace);
+    }
+    Ok(Self::new_in_region(eeprom, region, M24C64Layout::WearLevelled { slot_size }, delay, marker))
+  }
+
+  /// Like [`Self::new`], but reads every write back to check that it stuck.
+  pub fn new_with_verify(eeprom: M24C64<I2C>, address: usize, delay: Delay, marker: PhantomData<Config>) -> Self {
+    Self::new(eeprom, address, delay, marker).with_verify(true)
+  }
+
+  /// Like [`Self::new`], but skips writes that wouldn't change what's stored.
+  pub fn new_with_write_elision(eeprom: M24C64<I2C>, address: usize, delay: Delay, marker: PhantomData<Config>) -> Self {
+    Self::new(eeprom, address, delay, marker).with_write_elision(true)
+  }
+
+  /// Like [`Self::new`], but waits a fixed `ms` after each page is written rather than polling for the write
+  /// cycle to finish, e.g. for parts with a shorter or longer write time than the M24C64's 10ms.
+  pub fn new_with_write_delay_ms(eeprom: M24C64<I2C>, address: usize, ms: u16, delay: Delay, marker: PhantomData<Config>) -> Self {
+    Self::new(eeprom, address, delay, marker).with_write_cycle(M24C64WriteCycle::Delay(ms))
+  }
+
+  /// Like [`Self::new`], but writing and reading the configuration with the binmarshal context `ctx`.
+  pub fn new_with_context<Ctx: Clone>(eeprom: M24C64<I2C>, address: usize, ctx: Ctx, delay: Delay, marker: PhantomData<Config>) -> M24C64ConfigurationMarshal<Config, I2C, Delay, NoWriteProtect, Ctx> {
+    Self::new(eeprom, address, delay, marker).with_context(ctx)
+  }
+
+  /// Drive `write_protect`, wired to the EEPROM's WP pin, low around each write and high again afterwards,
+  /// keeping the EEPROM read-only the rest of the time.
+  pub fn with_write_protect<Wp: OutputPin>(self, write_protect: Wp) -> M24C64ConfigurationMarshal<Config, I2C, Delay, Wp> {
+    M24C64ConfigurationMarshal {
+      delay: self.delay,
+      address_offset: self.address_offset,
+      layout: self.layout,
+      write_cycle: self.write_cycle,
+      capacity: self.capacity,
+      verify: self.verify,
+      elide_writes: self.elide_writes,
+      diff_writes: self.diff_writes,
+      length_endianness: self.length_endianness,
+      forward_compat: self.forward_compat,
+      lenient_read: self.lenient_read,
+      stats: self.stats,
+      latest: self.latest,
+      cached: self.cached,
+      buffer: self.buffer,
+      timestamps: self.timestamps,
+      stamp: self.stamp,
+      migrated: self.migrated,
+      eeprom: self.eeprom,
+      write_protect,
+      ctx: self.ctx,
+      marker: self.marker,
+    }
+  }
+}
+
+impl<Config, I2C, Delay, Wp> M24C64ConfigurationMarshal<Config, I2C, Delay, Wp> {
+  /// Write and read the configuration with the binmarshal context `ctx`, for configurations whose
+  /// binmarshal impls need one, e.g. a board type. It's cloned for every write and read.
+  pub fn with_context<Ctx: Clone>(self, ctx: Ctx) -> M24C64ConfigurationMarshal<Config, I2C, Delay, Wp, Ctx> {
+    M24C64ConfigurationMarshal {
+      delay: self.delay,
+      address_offset: self.address_offset,
+      layout: self.layout,
+      write_cycle: self.write_cycle,
+      capacity: self.capacity,
+      verify: self.verify,
+      elide_writes: self.elide_writes,
+      diff_writes: self.diff_writes,
+      length_endianness: self.length_endianness,
+      forward_compat: self.forward_compat,
+      lenient_read: self.lenient_read,
+      stats: self.stats,
+      latest: self.latest,
+      // Bytes serialised under another context can't be compared against
+      cached: None,
+      buffer: self.buffer,
+      timestamps: self.timestamps,
+      stamp: self.stamp,
+      migrated: self.migrated,
+      eeprom: self.eeprom,
+      write_protect: self.write_protect,
+      ctx,
+      marker: self.marker,
+    }
+  }
+}
+
+impl<Config, I2C, Delay, Wp, Ctx> M24C64ConfigurationMarshal<Config, I2C, Delay, Wp, Ctx> {
+  /// Skip writes that wouldn't change what's stored, saving EEPROM wear. The stored configuration is read
+  /// back on the first write, and after that compared against the last one read or written. Enabled by default.
+  pub fn with_write_elision(mut self, elide_writes: bool) -> Self {
+    self.elide_writes = elide_writes;
+    self
+  }
+
+  /// Only rewrite the pages of the stored configuration that changed, rather than all of it, cutting wear for
+  /// large configurations that mostly stay the same. This applies to [`M24C64Layout::Single`] when the last
+  /// image is known, and the new one is the same length. Anything else is written in full.
+  ///
+  /// Fields shifting between writes just makes more pages differ, so this is always safe, but it only saves
+  /// wear if the schema changes by appending fields.
+  pub fn with_diff_writes(mut self, diff_writes: bool) -> Self {
+    self.diff_writes = diff_writes;
+    self
+  }
+
+  /// Store the length in each header in the given byte order, e.g. for a region shared with firmware that
+  /// reads it big-endian. Defaults to [`M24C64LengthEndianness::Little`]. Either is read back.
+  pub fn with_length_endianness(mut self, length_endianness: M24C64LengthEndianness) -> Self {
+    self.length_endianness = length_endianness;
+    self
+  }
+
+  /// Read configurations written with a newer [`Migrate::VERSION`] as if they were the current version,
+  /// instead of returning [`M24C64ConfigurationError::FutureVersion`]. Any bytes past the end of the current
+  /// configuration are ignored.
+  ///
+  /// This is only safe if every newer version just appends fields to the end of the configuration. Note that
+  /// writing the configuration back drops those fields. Bytes left over from the current version are ignored
+  /// too, rather than returning [`M24C64ConfigurationError::TrailingData`].
+  pub fn with_forward_compat(mut self, forward_compat: bool) -> Self {
+    self.forward_compat = forward_compat;
+    self
+  }
+
+  /// Read configurations written with an older [`Migrate::VERSION`] that [`Migrate::migrate`] doesn't support
+  /// as if they were the current version, filling any fields missing from the end from `Config::default()`.
+  /// The upgraded configuration is written back, as with any other migration.
+  ///
+  /// Like [`Self::with_forward_compat`], this is only safe if every version just appends fields to the end, and
+  /// ignores bytes left over from the current version.
+  pub fn with_lenient_read(mut self, lenient_read: bool) -> Self {
+    self.lenient_read = lenient_read;
+    self
+  }
+
+  /// Read every write back, returning [`M24C64ConfigurationError::VerifyFailed`] if it doesn't match.
+  /// [`M24C64Layout::Atomic`] always verifies.
+  pub fn with_verify(mut self, verify: bool) -> Self {
+    self.verify = verify;
+    self
+  }
+
+  /// Set the size of the EEPROM in bytes, for parts other than the M24C64. Defaults to [`M24C64_CAPACITY`].
+  pub fn with_capacity(mut self, capacity: usize) -> Self {
+    self.capacity = capacity;
+    self
+  }
+
+  pub fn with_write_cycle(mut self, write_cycle: M24C64WriteCycle) -> Self {
+    self.write_cycle = write_cycle;
+    self
+  }
+
+  /// Counts of configuration reads and writes since this marshal was created. Elided writes aren't counted,
+  /// and `bytes_written` includes every byte physically written, e.g. by [`Self::erase`].
+  pub fn stats(&self) -> MarshalStats {
+    self.stats
+  }
+
+  /// Forget what's known about the stored configuration, e.g. after something else has written to the
+  /// EEPROM, so that the next write reads it back rather than eliding against a stale copy.
+  pub fn invalidate(&mut self) {
+    self.latest = None;
+    self.cached = None;
+  }
+
+  fn slot_count(&self) -> usize {
+    match self.layout {
+      M24C64Layout::Single => 1,
+      M24C64Layout::WearLevelled { .. } | M24C64Layout::Atomic { .. } => 2,
+    }
+  }
+
+  fn slot_address(&self, slot: usize) -> usize {
+    match self.layout {
+      M24C64Layout::Single => self.address_offset,
+      M24C64Layout::WearLevelled { slot_size } => self.address_offset + slot * slot_size,
+      M24C64Layout::Atomic { slot_size } => self.address_offset + 1 + slot * slot_size,
+    }
+  }
+
+  // The space available for a whole image, header and all, in the smallest slot
+  fn image_capacity(&self) -> usize {
+    (0..self.slot_count())
+      .map(|slot| {
+        let available = self.capacity.saturating_sub(self.slot_address(slot));
+        self.slot_capacity().map(|slot| slot.min(available)).unwrap_or(available)
+      })
+      .min()
+      .unwrap_or(0)
+  }
+
+  fn slot_capacity(&self) -> Option<usize> {
+    match self.layout {
+      M24C64Layout::Single => None,
+      M24C64Layout::WearLevelled { slot_size } | M24C64Layout::Atomic { slot_size } => Some(slot_size),
+    }
+  }
+
+  /// Give back the EEPROM driver and delay, e.g. to hand the I2C bus to something else.
+  pub fn release(self) -> (M24C64<I2C>, Delay) {
+    (self.eeprom, self.delay)
+  }
+
+  /// As with [`Self::release`], but also giving back the write-protect pin.
+  pub fn release_with_write_protect(self) -> (M24C64<I2C>, Delay, Wp) {
+    (self.eeprom, self.delay, self.write_protect)
+  }
+}
+
+impl<I2C, Delay> M24C64ConfigurationMarshal<(), I2C, Delay> {
+  /// Configure a marshal step by step, starting from the defaults of [`Self::new`] at address 0.
+  pub fn builder(eeprom: M24C64<I2C>, delay: Delay) -> M24C64Builder<I2C, Delay> {
+    M24C64Builder {
+      eeprom,
+      delay,
+      address: 0,
+      capacity: M24C64_CAPACITY,
+      layout: M24C64Layout::Single,
+      wear_level: false,
+      write_cycle: M24C64WriteCycle::default(),
+      verify: false,
+      elide_writes: true,
+      diff_writes: false,
+      length_endianness: M24C64LengthEndianness::Little,
+      forward_compat: false,
+      lenient_read: false,
+    }
+  }
+}
+
+/// Builds an [`M24C64ConfigurationMarshal`]. See [`M24C64ConfigurationMarshal::builder`].
+pub struct M24C64Builder<I2C, Delay> {
+  eeprom: M24C64<I2C>,
+  delay: Delay,
+  address: usize,
+  capacity: usize,
+  layout: M24C64Layout,
+  wear_level: bool,
+  write_cycle: M24C64WriteCycle,
+  verify: bool,
+  elide_writes: bool,
+  diff_writes: bool,
+  length_endianness: M24C64LengthEndianness,
+  forward_compat: bool,
+  lenient_read: bool,
+}
+
+impl<I2C, Delay> M24C64Builder<I2C, Delay> {
+  pub fn address(mut self, address: usize) -> Self {
+    self.address = address;
+    self
+  }
+
+  /// See [`M24C64ConfigurationMarshal::with_capacity`].
+  pub fn capacity(mut self, capacity: usize) -> Self {
+    self.capacity = capacity;
+    self
+  }
+
+  /// Store the configuration within `region`. Replaces any address and capacity set so far.
+  pub fn region(mut self, region: EepromRegion) -> Self {
+    self.address = region.start;
+    self.capacity = region.end();
+    self
+  }
+
+  pub fn layout(mut self, layout: M24C64Layout) -> Self {
+    self.layout = layout;
+    self
+  }
+
+  /// Use [`M24C64Layout::WearLevelled`], splitting everything from the address to the capacity into two
+  /// slots. Ignored if a layout other than [`M24C64Layout::Single`] is set.
+  pub fn wear_level(mut self, wear_level: bool) -> Self {
+    self.wear_level = wear_level;
+    self
+  }
+
+  pub fn write_cycle(mut self, write_cycle: M24C64WriteCycle) -> Self {
+    self.write_cycle = write_cycle;
+    self
+  }
+
+  /// See [`M24C64ConfigurationMarshal::with_verify`].
+  pub fn verify(mut self, verify: bool) -> Self {
+    self.verify = verify;
+    self
+  }
+
+  /// See [`M24C64ConfigurationMarshal::with_write_elision`].
+  pub fn write_elision(mut self, elide_writes: bool) -> Self {
+    self.elide_writes = elide_writes;
+    self
+  }
+
+  /// See [`M24C64ConfigurationMarshal::with_diff_writes`].
+  pub fn diff_writes(mut self, diff_writes: bool) -> Self {
+    self.diff_writes = diff_writes;
+    self
+  }
+
+  /// See [`M24C64ConfigurationMarshal::with_length_endianness`].
+  pub fn length_endianness(mut self, length_endianness: M24C64LengthEndianness) -> Self {
+    self.length_endianness = length_endianness;
+    self
+  }
+
+  /// See [`M24C64ConfigurationMarshal::with_forward_compat`].
+  pub fn forward_compat(mut self, forward_compat: bool) -> Self {
+    self.forward_compat = forward_compat;
+    self
+  }
+
+  /// See [`M24C64ConfigurationMarshal::with_lenient_read`].
+  pub fn lenient_read(mut self, lenient_read: bool) -> Self {
+    self.lenient_read = lenient_read;
+    self
+  }
+
+  pub fn build<Config>(self) -> M24C64ConfigurationMarshal<Config, I2C, Delay> {
+    let layout = match (self.layout, self.wear_level) {
+      (M24C64Layout::Single, true) => M24C64Layout::WearLevelled { slot_size: self.capacity.saturating_sub(self.address) / 2 },
+      (layout, _) => layout,
+    };
+
+    M24C64ConfigurationMarshal::new_with_layout(self.eeprom, self.address, layout, self.delay, PhantomData)
+      .with_capacity(self.capacity)
+      .with_write_cycle(self.write_cycle)
+      .with_verify(self.verify)
+      .with_write_elision(self.elide_writes)
+      .with_diff_writes(self.diff_writes)
+      .with_length_endianness(self.length_endianness)
+      .with_forward_compat(self.forward_compat)
+      .with_lenient_read(self.lenient_read)
+  }
+}
+
+impl<Config, I2C, Delay, Wp, Ctx, E> M24C64ConfigurationMarshal<Config, I2C, Delay, Wp, Ctx>
+where
+  I2C: i2c::Write<u8, Error = E> + i2c::WriteRead<u8, Error = E>,
+  Delay: DelayMs<u16>,
+  Wp: OutputPin
+{
+  fn write_paged(&mut self, address: usize, data: &[u8]) -> Result<(), M24C64ConfigurationError<E>> {
+    write_paged(&mut self.eeprom, &mut self.delay, self.write_cycle, address, data)?;
+    self.stats.bytes_written += data.len() as u64;
+    Ok(())
+  }
+
+  // Write the pages of `data`, at `address`, that differ from `previous`
+  fn write_changed_pages(&mut self, address: usize, previous: &[u8], data: &[u8]) -> Result<(), M24C64ConfigurationError<E>> {
+    let mut start = 0;
+    while start < data.len() {
+      let end = (start + PAGE_SIZE - ((address + start) % PAGE_SIZE)).min(data.len());
+      if previous[start..end] != data[start..end] {
+        self.write_paged(address + start, &data[start..end])?;
+      }
+      start = end;
+    }
+    Ok(())
+  }
+
+  // Whether `data` is what's stored at `address`, read back a page at a time rather than into a buffer
+  fn matches_stored(&mut self, address: usize, data: &[u8]) -> Result<bool, M24C64ConfigurationError<E>> {
+    let mut chunk = [0u8; PAGE_SIZE];
+    for (i, expected) in data.chunks(PAGE_SIZE).enumerate() {
+      let chunk = &mut chunk[..expected.len()];
+      self.eeprom.read(address + i * PAGE_SIZE, chunk).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+      if chunk != expected {
+        return Ok(false);
+      }
+    }
+    Ok(true)
+  }
+
+  // Take the spare buffer to fill
+  fn take_buffer(&mut self) -> Scratch {
+    core::mem::replace(&mut self.buffer, Scratch(Vec::new()))
+  }
+
+  // Hand a buffer back for reuse, keeping whichever of it and the spare is the larger
+  fn recycle(&mut self, buf: Scratch) {
+    if buf.capacity() > self.buffer.capacity() {
+      self.buffer = buf;
+    }
+  }
+
+  // Run `f` with the write-protect pin driven low, enabling writes
+  fn unprotected<T, F>(&mut self, f: F) -> Result<T, M24C64ConfigurationError<E>>
+  where
+    F: FnOnce(&mut Self) -> Result<T, M24C64ConfigurationError<E>>
+  {
+    self.write_protect.set_low().map_err(|_| M24C64ConfigurationError::WriteProtected)?;
+    let result = f(self);
+    // Even if `f` failed, a pin left unprotected is the bigger problem
+    self.write_protect.set_high().map_err(|_| M24C64ConfigurationError::WriteProtected)?;
+    result
+  }
+
+  /// Overwrite the stored configuration with blank (0xFF) bytes, so that no stale data lingers when
+  /// a smaller configuration is written in its place.
+  pub fn erase(&mut self) -> Result<(), M24C64ConfigurationError<E>> {
+    let len = match self.layout {
+      M24C64Layout::Single => {
+        check_range(self.address_offset, HEADER_LEN, self.capacity)?;
+        let mut header = [0u8; HEADER_LEN];
+        self.eeprom.read(self.address_offset, &mut header[..]).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+        match decode_header::<E>(&header) {
+          Ok((_, _, _, len)) => HEADER_LEN + len,
+          Err(_) => HEADER_LEN,
+        }
+      },
+      M24C64Layout::WearLevelled { slot_size } => 2 * slot_size,
+      M24C64Layout::Atomic { slot_size } => 1 + 2 * slot_size,
+    };
+
+    check_range(self.address_offset, len, self.capacity)?;
+    self.unprotected(|s| s.write_paged(s.address_offset, &vec![0xFFu8; len]))?;
+    self.invalidate();
+    Ok(())
+  }
+
+  /// Lay down a known, empty starting state, e.g. when provisioning a device. Everything [`Self::erase`] would
+  /// blank is blanked (0xFF), which for [`M24C64Layout::WearLevelled`] and [`M24C64Layout::Atomic`] is both
+  /// slots and any active marker, and for [`M24C64Layout::Single`] is the header and any image it describes.
+  /// Reads then fail with [`M24C64ConfigurationError::BlankEeprom`], and the next write goes to the first slot
+  /// with a sequence number of 0. Nothing outside the layout is touched; see [`Self::format_with_backup`].
+  pub fn format(&mut self) -> Result<(), M24C64ConfigurationError<E>> {
+    self.erase()
+  }
+
+  /// Like [`Self::format`], but also blanks `backup`, the region given to [`Self::backup`], so that a
+  /// [`Self::restore_backup`] before the first backup restores a blank configuration rather than whatever was
+  /// there before.
+  pub fn format_with_backup(&mut self, backup: EepromRegion) -> Result<(), M24C64ConfigurationError<E>> {
+    check_range(backup.start, backup.len, self.capacity)?;
+    self.format()?;
+    self.unprotected(|s| s.write_paged(backup.start, &vec![0xFFu8; backup.len]))
+  }
+
+  /// The length of the stored serialised configuration, read from its header without reading or
+  /// checking the configuration itself.
+  pub fn stored_len(&mut self) -> Result<u16, M24C64ConfigurationError<E>> {
+    let (_, _, _, len) = self.latest_header()?;
+    Ok((len - CRC_LEN) as u16)
+  }
+
+  /// The [`Migrate::VERSION`] of the stored configuration, read from its header without reading or
+  /// checking the configuration itself.
+  pub fn stored_version(&mut self) -> Result<u8, M24C64ConfigurationError<E>> {
+    let (version, _, _, _) = self.latest_header()?;
+    Ok(version)
+  }
+
+  /// The sequence number of the stored configuration, which increases with every write, read from its header
+  /// without reading or checking the configuration itself.
+  pub fn stored_seq(&mut self) -> Result<u32, M24C64ConfigurationError<E>> {
+    let (_, _, seq, _) = self.latest_header()?;
+    Ok(seq)
+  }
+
+  /// Check that the stored configuration is intact, validating its header and CRC without deserialising it
+  /// or touching the storage, e.g. for a power-on self test. A blank EEPROM, a bus error, or a misconfigured
+  /// address is returned as an error, while a corrupt image comes back as unhealthy.
+  pub fn check(&mut self) -> Result<ConfigHealth, M24C64ConfigurationError<E>> {
+    match self.read_latest() {
+      Ok((_, version, _, _, payload)) => Ok(ConfigHealth { version: Some(version), len: Some(payload.len() as u16), healthy: true }),
+      Err(e @ (M24C64ConfigurationError::I2C(_) | M24C64ConfigurationError::BlankEeprom | M24C64ConfigurationError::AddressOutOfRange)) => Err(e),
+      Err(_) => {
+        let header = self.latest_header().ok();
+        Ok(ConfigHealth {
+          version: header.map(|(version, _, _, _)| version),
+          len: header.map(|(_, _, _, len)| (len - CRC_LEN) as u16),
+          healthy: false,
+        })
+      },
+    }
+  }
+
+  // The decoded header of the latest image
+  fn latest_header(&mut self) -> Result<(u8, u32, u32, usize), M24C64ConfigurationError<E>> {
+    self.latest_slot().map(|(_, header)| header)
+  }
+
+  // The slot holding the latest image, and its decoded header
+  fn latest_slot(&mut self) -> Result<(usize, DecodedHeader), M24C64ConfigurationError<E>> {
+    let slots = match (self.layout, self.latest) {
+      (M24C64Layout::Single, _) => 0..1,
+      (_, Some((slot, _))) => slot..slot + 1,
+      (M24C64Layout::Atomic { .. }, None) => {
+        check_range(self.address_offset, 1, self.capacity)?;
+        let mut marker = [0u8; 1];
+        self.eeprom.read(self.address_offset, &mut marker[..]).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+        let slot = marker[0] as usize;
+        if slot >= self.slot_count() {
+          return Err(M24C64ConfigurationError::BlankEeprom);
+        }
+        slot..slot + 1
+      },
+      (M24C64Layout::WearLevelled { .. }, None) => 0..self.slot_count(),
+    };
+
+    let mut latest: Option<(usize, DecodedHeader)> = None;
+    let mut error = M24C64ConfigurationError::BlankEeprom;
+    for slot in slots {
+      check_range(self.slot_address(slot), HEADER_LEN, self.capacity)?;
+      let mut header = [0u8; HEADER_LEN];
+      self.eeprom.read(self.slot_address(slot), &mut header[..]).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+      match decode_header(&header) {
+        Ok(h) if latest.map(|(_, l)| h.2 > l.2).unwrap_or(true) => latest = Some((slot, h)),
+        Ok(_) | Err(M24C64ConfigurationError::BlankEeprom) => (),
+        Err(e) => error = e,
+      }
+    }
+
+    latest.ok_or(error)
+  }
+
+  /// Dump the raw stored bytes, starting at the configuration's address, for diagnostics and recovery.
+  /// Nothing is checked, so this works even if the stored configuration is corrupt. Returns how many bytes
+  /// were read, which is less than `buf.len()` if `buf` runs past the end of the EEPROM.
+  pub fn read_raw(&mut self, buf: &mut [u8]) -> Result<usize, M24C64ConfigurationError<E>> {
+    let len = buf.len().min(self.capacity.saturating_sub(self.address_offset));
+    self.eeprom.read(self.address_offset, &mut buf[..len]).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+    Ok(len)
+  }
+
+  /// Write raw bytes, as returned by [`Self::read_raw`], starting at the configuration's address.
+  pub fn write_raw(&mut self, bytes: &[u8]) -> Result<(), M24C64ConfigurationError<E>> {
+    let available = self.capacity.saturating_sub(self.address_offset);
+    if bytes.len() > available {
+      return Err(M24C64ConfigurationError::TooLarge { needed: bytes.len(), available });
+    }
+    self.unprotected(|s| s.write_paged(s.address_offset, bytes))?;
+    self.invalidate();
+    Ok(())
+  }
+
+  /// Copy the raw bytes of this configuration's storage into `backup`, which must not overlap it. As much
+  /// as fits in `backup` is copied, so it should be at least as large as the stored image.
+  pub fn backup(&mut self, backup: EepromRegion) -> Result<(), M24C64ConfigurationError<E>> {
+    let len = backup.len.min(self.capacity.saturating_sub(self.address_offset));
+    self.copy(self.address_offset, backup.start, len)
+  }
+
+  /// Copy a [`Self::backup`] back over this configuration's storage.
+  pub fn restore_backup(&mut self, backup: EepromRegion) -> Result<(), M24C64ConfigurationError<E>> {
+    let len = backup.len.min(self.capacity.saturating_sub(self.address_offset));
+    self.copy(backup.start, self.address_offset, len)?;
+    self.invalidate();
+    Ok(())
+  }
+
+  fn copy(&mut self, from: usize, to: usize, len: usize) -> Result<(), M24C64ConfigurationError<E>> {
+    self.unprotected(|s| {
+      let mut chunk = [0u8; PAGE_SIZE];
+      let mut copied = 0;
+      while copied < len {
+        let n = PAGE_SIZE.min(len - copied);
+        s.eeprom.read(from + copied, &mut chunk[..n]).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+        s.write_paged(to + copied, &chunk[..n])?;
+        copied += n;
+      }
+      Ok(())
+    })
+  }
+
+  // Returns the config version, schema, sequence number, and payload
+  fn read_slot(&mut self, slot: usize) -> Result<(u8, u32, u32, Scratch), M24C64ConfigurationError<E>> {
+    let address = self.slot_address(slot);
+    check_range(address, HEADER_LEN, self.capacity)?;
+
+    let mut header = [0u8; HEADER_LEN];
+    self.eeprom.read(address, &mut header[..]).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+    let (version, schema, seq, len) = decode_header(&header)?;
+    self.timestamps[slot] = decode_timestamp(&header);
+    // Reads past the end of the EEPROM wrap around to the start, rather than coming back short
+    if address + HEADER_LEN + len > self.capacity {
+      return Err(M24C64ConfigurationError::LengthMismatch);
+    }
+
+    let mut body = self.take_buffer();
+    body.clear();
+    body.resize(len, 0);
+    let payload_len = match self.eeprom.read(address + HEADER_LEN, &mut body[..]) {
+      Ok(()) => check_body(&body).map(|payload| payload.len()),
+      Err(e) => Err(M24C64ConfigurationError::I2C(e)),
+    };
+    match payload_len {
+      Ok(payload_len) => {
+        body.truncate(payload_len);
+        Ok((version, schema, seq, body))
+      },
+      Err(e) => {
+        self.recycle(body);
+        Err(e)
+      },
+    }
+  }
+
+  fn read_latest(&mut self) -> Result<(usize, u8, u32, u32, Scratch), M24C64ConfigurationError<E>> {
+    if let M24C64Layout::Atomic { .. } = self.layout {
+      check_range(self.address_offset, 1, self.capacity)?;
+      let mut marker = [0u8; 1];
+      self.eeprom.read(self.address_offset, &mut marker[..]).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+
+      let slot = marker[0] as usize;
+      if slot >= self.slot_count() {
+        return Err(M24C64ConfigurationError::BlankEeprom);
+      }
+
+      let (version, schema, seq, payload) = self.read_slot(slot)?;
+      return Ok((slot, version, schema, seq, payload));
+    }
+
+    let mut latest: Option<(usize, u8, u32, u32, Scratch)> = None;
+    let mut error = None;
+
+    for slot in 0..self.slot_count() {
+      match self.read_slot(slot) {
+        Ok((version, schema, seq, payload)) => {
+          if latest.as_ref().map(|l| seq > l.3).unwrap_or(true) {
+            if let Some((.., superseded)) = latest.replace((slot, version, schema, seq, payload)) {
+              self.recycle(superseded);
+            }
+          } else {
+            self.recycle(payload);
+          }
+        },
+        Err(e @ M24C64ConfigurationError::I2C(_)) => return Err(e),
+        // Prefer reporting a corrupt slot over a blank one
+        Err(M24C64ConfigurationError::BlankEeprom) if error.is_some() => (),
+        Err(e) => error = Some(e),
+      }
+    }
+
+    match (latest, error) {
+      (Some(latest), _) => Ok(latest),
+      (None, Some(e)) => Err(e),
+      (None, None) => Err(M24C64ConfigurationError::BlankEeprom),
+    }
+  }
+}
+
+/// Raw byte access to an M24C64, for use with the generic marshals such as [`crate::FramedMarshal`].
+/// Writes are split into pages, waiting for the write cycle to complete after each.
+pub struct M24C64Store<I2C, Delay> {
+  eeprom: M24C64<I2C>,
+  delay: Delay,
+  write_cycle: M24C64WriteCycle,
+  capacity: usize,
+}
+
+impl<I2C, Delay> M24C64Store<I2C, Delay> {
+  pub fn new(eeprom: M24C64<I2C>, delay: Delay) -> Self {
+    Self { eeprom, delay, write_cycle: M24C64WriteCycle::default(), capacity: M24C64_CAPACITY }
+  }
+
+  /// See [`M24C64ConfigurationMarshal::with_capacity`].
+  pub fn with_capacity(mut self, capacity: usize) -> Self {
+    self.capacity = capacity;
+    self
+  }
+
+  pub fn with_write_cycle(mut self, write_cycle: M24C64WriteCycle) -> Self {
+    self.write_cycle = write_cycle;
+    self
+  }
+
+  /// See [`M24C64ConfigurationMarshal::release`].
+  pub fn release(self) -> (M24C64<I2C>, Delay) {
+    (self.eeprom, self.delay)
+  }
+}
+
+impl<I2C, Delay, E> ByteStore for M24C64Store<I2C, Delay>
+where
+  I2C: i2c::Write<u8, Error = E> + i2c::WriteRead<u8, Error = E>,
+  Delay: DelayMs<u16>
+{
+  type Error = M24C64ConfigurationError<E>;
+
+  fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Self::Error> {
+    if offset + buf.len() > self.capacity {
+      return Err(M24C64ConfigurationError::TooLarge { needed: offset + buf.len(), available: self.capacity });
+    }
+    self.eeprom.read(offset, buf).map_err(|e| M24C64ConfigurationError::I2C(e))
+  }
+
+  fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Self::Error> {
+    if offset + bytes.len() > self.capacity {
+      return Err(M24C64ConfigurationError::TooLarge { needed: offset + bytes.len(), available: self.capacity });
+    }
+    write_paged(&mut self.eeprom, &mut self.delay, self.write_cycle, offset, bytes)
+  }
+
+  fn capacity(&self) -> usize {
+    self.capacity
+  }
+}
+
+impl<I2C, Delay, Wp, Ctx, Config, E> M24C64ConfigurationMarshal<Config, I2C, Delay, Wp, Ctx>
+where
+  Config: Marshal<Ctx> + Migrate,
+  Ctx: Clone,
+  I2C: i2c::Write<u8, Error = E> + i2c::WriteRead<u8, Error = E>,
+  Delay: DelayMs<u16>,
+  Wp: OutputPin
+{
+  // Write `config`, or with `elide` skip the write if it's already stored. Returns whether it was written.
+  // `next_seq` overrides the sequence number, which otherwise follows on from the latest image.
+  fn write_elided(&mut self, config: &Config, elide: bool, next_seq: Option<u32>) -> Result<bool, M24C64ConfigurationError<E>> {
+    let mut bytes = self.take_buffer();
+    let result = match serialise_into(config, self.ctx.clone(), &mut bytes) {
+      Ok(()) => Ok(()),
+      Err(_) => Err(M24C64ConfigurationError::Serialisation),
+    };
+    self.write_buffer(bytes, result, elide, next_seq)
+  }
+
+  // Write the serialised `bytes` taken from `take_buffer`, unless serialising them failed with `serialised`
+  fn write_buffer(&mut self, mut bytes: Scratch, serialised: Result<(), M24C64ConfigurationError<E>>, elide: bool, next_seq: Option<u32>) -> Result<bool, M24C64ConfigurationError<E>> {
+    let result = match serialised {
+      Ok(()) if bytes.is_empty() => Err(M24C64ConfigurationError::EmptySerialisation),
+      Ok(()) => self.write_payload(&mut bytes, elide, next_seq),
+      Err(e) => Err(e),
+    };
+    match result {
+      // What was written is now the cached copy, and the last cached copy is free to serialise into next time
+      Ok(true) => {
+        if let Some(previous) = self.cached.replace(bytes) {
+          self.recycle(previous);
+        }
+      },
+      _ => self.recycle(bytes),
+    }
+    result
+  }
+
+  // Write the serialised `bytes`, leaving them as they were if the write went ahead
+  fn write_payload(&mut self, bytes: &mut Scratch, elide: bool, next_seq: Option<u32>) -> Result<bool, M24C64ConfigurationError<E>> {
+    let payload_len = bytes.len();
+
+    // Every slot is the same size, so an image too large for one can be turned away before anything is read,
+    // let alone written over
+    if let Some(available) = self.slot_capacity() {
+      let needed = HEADER_LEN + payload_len + CRC_LEN;
+      if needed > available {
+        return Err(M24C64ConfigurationError::TooLarge { needed, available });
+      }
+    }
+
+    let latest = match (self.latest, &self.cached) {
+      (Some(latest), _) if !elide => Some(latest),
+      (Some(latest), Some(cached)) => {
+        if cached[..] == bytes[..] {
+          self.migrated = false;
+          return Ok(false);
+        }
+        Some(latest)
+      },
+      _ => match self.read_latest() {
+        Ok((slot, version, schema, seq, stored)) => {
+          if elide && version == Config::VERSION && schema == Config::SCHEMA && stored[..] == bytes[..] {
+            self.latest = Some((slot, seq));
+            if let Some(previous) = self.cached.replace(stored) {
+              self.recycle(previous);
+            }
+            self.migrated = false;
+            return Ok(false);
+          }
+          self.recycle(stored);
+          Some((slot, seq))
+        },
+        Err(e @ M24C64ConfigurationError::I2C(_)) => return Err(e),
+        Err(_) => None,
+      },
+    };
+
+    // The last image, so that only the pages that differ from it need writing
+    let previous = match self.cached.take() {
+      Some(mut previous) if self.diff_writes && matches!(self.layout, M24C64Layout::Single) && latest.is_some() && previous.len() == payload_len => {
+        let crc = crc32(&previous);
+        previous.extend_from_slice(&crc.to_le_bytes());
+        Some(previous)
+      },
+      Some(previous) => {
+        self.recycle(previous);
+        None
+      },
+      None => None,
+    };
+
+    let crc = crc32(bytes);
+    bytes.extend_from_slice(&crc.to_le_bytes());
+
+    // Always overwrite the oldest slot, leaving the latest image intact until this one is complete.
+    let (slot, seq) = match latest {
+      Some((slot, seq)) => ((slot + 1) % self.slot_count(), seq.wrapping_add(1)),
+      None => (0, 0),
+    };
+    let seq = next_seq.unwrap_or(seq);
+    let address = self.slot_address(slot);
+    let mut header = encode_header::<Config>(seq, payload_len, self.length_endianness, self.stamp);
+
+    check_range(address, HEADER_LEN, self.capacity)?;
+    let needed = HEADER_LEN + bytes.len();
+    let available = self.capacity.saturating_sub(address);
+    let available = self.slot_capacity().map(|slot| slot.min(available)).unwrap_or(available);
+    if needed > available {
+      return Err(M24C64ConfigurationError::TooLarge { needed, available });
+    }
+
+    self.write_paged(address, &header)?;
+    let written = match &previous {
+      Some(previous) => self.write_changed_pages(address + HEADER_LEN, previous, bytes),
+      None => self.write_paged(address + HEADER_LEN, bytes),
+    };
+    if let Some(previous) = previous {
+      self.recycle(previous);
+    }
+    written?;
+    header[FLAGS_OFFSET] &= !FLAG_WRITE_IN_PROGRESS;
+    self.write_paged(address + FLAGS_OFFSET, &header[FLAGS_OFFSET..=FLAGS_OFFSET])?;
+
+    let atomic = matches!(self.layout, M24C64Layout::Atomic { .. });
+    if self.verify || atomic {
+      let mut readback = [0u8; HEADER_LEN];
+      self.eeprom.read(address, &mut readback[..]).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+      if readback != header || !self.matches_stored(address + HEADER_LEN, bytes)? {
+        return Err(M24C64ConfigurationError::VerifyFailed);
+      }
+    }
+
+    if atomic {
+      // The image is known good, so flip the marker over to it. A single byte write can't be torn.
+      self.write_paged(self.address_offset, &[slot as u8])?;
+    }
+
+    bytes.truncate(payload_len);
+    self.latest = Some((slot, seq));
+    self.timestamps[slot] = self.stamp.take();
+    self.migrated = false;
+    self.stats.writes = self.stats.writes.wrapping_add(1);
+    Ok(true)
+  }
+}
+
+impl<I2C, Delay, Wp, Ctx, Config, E> ConfigurationMarshal<Config> for M24C64ConfigurationMarshal<Config, I2C, Delay, Wp, Ctx>
+where
+  Config: Marshal<Ctx> + DemarshalOwned<Ctx> + Migrate + Default,
+  Ctx: Clone,
+  I2C: i2c::Write<u8, Error = E> + i2c::WriteRead<u8, Error = E>,
+  Delay: DelayMs<u16>,
+  Wp: OutputPin
+{
+  type Error = M24C64ConfigurationError<E>;
+  // Bus errors from contention or a write cycle still in progress are usually transient
+  const RETRY_HINT: usize = 3;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.unprotected(|s| s.write_elided(config, s.elide_writes, None)).map(|_| ())
+  }
+
+  fn write_serialised(&mut self, _config: &Config, bytes: &[u8]) -> Result<(), Self::Error> {
+    let mut buffer = self.take_buffer();
+    buffer.clear();
+    buffer.extend_from_slice(bytes);
+    self.unprotected(|s| s.write_buffer(buffer, Ok(()), s.elide_writes, None)).map(|_| ())
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    // Only elide against what's already known, rather than reading the stored image back, and don't verify.
+    // The atomic layout still verifies before switching over to the new image.
+    let verify = core::mem::replace(&mut self.verify, false);
+    let elide = self.elide_writes && self.cached.is_some();
+    let result = self.unprotected(|s| s.write_elided(config, elide, None)).map(|_| ());
+    self.verify = verify;
+    result
+  }
+
+  fn write_changed(&mut self, config: &Config) -> Result<bool, Self::Error> {
+    // Telling whether anything changed needs the same read-back as write elision
+    self.unprotected(|s| s.write_elided(config, true, None))
+  }
+
+  fn capacity(&self) -> usize {
+    self.image_capacity().saturating_sub(HEADER_LEN + CRC_LEN)
+  }
+
+  fn free(&mut self) -> Result<usize, Self::Error> {
+    let len = match self.stored_len() {
+      Ok(len) => len as usize,
+      Err(Self::Error::BlankEeprom) => 0,
+      Err(e) => return Err(e),
+    };
+    Ok(ConfigurationMarshal::<Config>::capacity(self).saturating_sub(len))
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.migrated = false;
+    self.stats.reads = self.stats.reads.wrapping_add(1);
+    if let Some(cached) = self.cached.take() {
+      self.recycle(cached);
+    }
+    let (slot, version, schema, seq, payload) = self.read_latest()?;
+    self.latest = Some((slot, seq));
+
+    let (config, migrated) = decode(version, schema, &payload, self.ctx.clone(), self.forward_compat, self.lenient_read)?;
+    self.migrated = migrated;
+    if version == Config::VERSION && schema == Config::SCHEMA {
+      self.cached = Some(payload);
+    } else {
+      self.recycle(payload);
+    }
+    Ok(config)
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::BlankEeprom)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    error.recovery_reason()
+  }
+
+  fn migrated(&self) -> bool {
+    self.migrated
+  }
+
+  // The sequence number in the header already counts writes
+  fn revision(&self) -> Option<u32> {
+    self.latest.map(|(_, seq)| seq)
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.stamp = Some(now);
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.latest.and_then(|(slot, _)| self.timestamps[slot])
+  }
+
+  fn written_externally(&mut self, _config: &Config) {
+    // Which slot was written, and with what sequence number, isn't known, so read it all back next time
+    self.invalidate();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  extern crate std;
+
+  use std::{cell::RefCell, rc::Rc};
+
+  use binmarshal::Demarshal;
+
+  use crate::load_config;
+  use super::*;
+
+  #[derive(Debug, Clone, Default, PartialEq, Marshal, Demarshal)]
+  struct Settings {
+    gain: u32,
+  }
+
+  impl Migrate for Settings { }
+
+  impl crate::Validate for Settings {
+    fn validate(&self) -> Result<(), crate::ValidationError> {
+      Ok(())
+    }
+  }
+
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  struct Nack;
+
+  struct EepromState {
+    mem: Vec<u8>,
+    // Page writes to let through before NACKing the rest, simulating power being lost part way through
+    writes_left: Option<usize>,
+  }
+
+  // An M24C64 on the bus, shared so that its contents outlive the marshal
+  #[derive(Clone)]
+  struct Eeprom(Rc<RefCell<EepromState>>);
+
+  impl Eeprom {
+    fn new() -> Self {
+      Self(Rc::new(RefCell::new(EepromState { mem: vec![0xFF; M24C64_CAPACITY], writes_left: None })))
+    }
+
+    fn fail_after(&self, writes: usize) {
+      self.0.borrow_mut().writes_left = Some(writes);
+    }
+
+    fn power_on(&self) {
+      self.0.borrow_mut().writes_left = None;
+    }
+
+    fn byte(&self, address: usize) -> u8 {
+      self.0.borrow().mem[address]
+    }
+
+    fn flip(&self, address: usize) {
+      self.0.borrow_mut().mem[address] ^= 0x01;
+    }
+  }
+
+  impl i2c::Write<u8> for Eeprom {
+    type Error = Nack;
+
+    fn write(&mut self, _address: u8, bytes: &[u8]) -> Result<(), Nack> {
+      let mut state = self.0.borrow_mut();
+      let (address, data) = bytes.split_at(2);
+      if data.is_empty() {
+        return Ok(());
+      }
+      match state.writes_left {
+        Some(0) => return Err(Nack),
+        Some(n) => state.writes_left = Some(n - 1),
+        None => (),
+      }
+      let address = u16::from_be_bytes([address[0], address[1]]) as usize;
+      let page = address - address % PAGE_SIZE;
+      for (i, b) in data.iter().enumerate() {
+        state.mem[page + (address - page + i) % PAGE_SIZE] = *b;
+      }
+      Ok(())
+    }
+  }
+
+  impl i2c::WriteRead<u8> for Eeprom {
+    type Error = Nack;
+
+    fn write_read(&mut self, _address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Nack> {
+      let state = self.0.borrow();
+      let address = u16::from_be_bytes([bytes[0], bytes[1]]) as usize;
+      for (i, b) in buffer.iter_mut().enumerate() {
+        *b = state.mem[(address + i) % M24C64_CAPACITY];
+      }
+      Ok(())
+    }
+  }
+
+  struct NoDelay;
+
+  impl DelayMs<u16> for NoDelay {
+    fn delay_ms(&mut self, _ms: u16) { }
+  }
+
+  const ADDRESS: usize = 0x10;
+
+  fn marshal(eeprom: &Eeprom, layout: M24C64Layout) -> M24C64ConfigurationMarshal<Settings, Eeprom, NoDelay> {
+    M24C64ConfigurationMarshal::new_with_layout(M24C64::new(eeprom.clone(), 0), ADDRESS, layout, NoDelay, PhantomData)
+  }
+
+  fn gain(eeprom: &Eeprom, layout: M24C64Layout) -> Result<u32, M24C64ConfigurationError<Nack>> {
+    marshal(eeprom, layout).read().map(|c| c.gain)
+  }
+
+  #[test]
+  fn checksum_mismatch_surfaces() {
+    let eeprom = Eeprom::new();
+    marshal(&eeprom, M24C64Layout::Single).write(&Settings { gain: 42 }).unwrap();
+    assert_eq!(gain(&eeprom, M24C64Layout::Single).unwrap(), 42);
+
+    eeprom.flip(ADDRESS + HEADER_LEN);
+    let error = gain(&eeprom, M24C64Layout::Single).unwrap_err();
+    assert!(matches!(error, M24C64ConfigurationError::ChecksumMismatch));
+    assert_eq!(error.recovery_reason(), RecoveryReason::Corrupt);
+  }
+
+  #[test]
+  fn interrupted_write_is_flagged_as_torn() {
+    let eeprom = Eeprom::new();
+    marshal(&eeprom, M24C64Layout::Single).write(&Settings { gain: 1 }).unwrap();
+
+    // The header spans two pages, so the third page write is the first of the body
+    eeprom.fail_after(2);
+    assert!(marshal(&eeprom, M24C64Layout::Single).write(&Settings { gain: 2 }).is_err());
+    eeprom.power_on();
+    assert!(matches!(gain(&eeprom, M24C64Layout::Single), Err(M24C64ConfigurationError::TornWrite)));
+
+    let (provider, outcome) = load_config::<Settings, _>(marshal(&eeprom, M24C64Layout::Single)).ok().unwrap();
+    assert_eq!(outcome, crate::InitOutcome::Recovered { reason: RecoveryReason::TornWrite });
+    assert_eq!(crate::GenericConfigurationProvider::current(&provider), &Settings::default());
+  }
+
+  #[test]
+  fn wear_levelled_writes_alternate_and_survive_a_torn_write() {
+    let eeprom = Eeprom::new();
+    let layout = M24C64Layout::WearLevelled { slot_size: 64 };
+    let mut m = marshal(&eeprom, layout);
+    m.write(&Settings { gain: 1 }).unwrap();
+    m.write(&Settings { gain: 2 }).unwrap();
+    assert_eq!([eeprom.byte(ADDRESS), eeprom.byte(ADDRESS + 64)], [MAGIC[0], MAGIC[0]]);
+    assert_eq!(gain(&eeprom, layout).unwrap(), 2);
+
+    // The next write goes over the older image in the first slot, leaving the latest one alone
+    eeprom.fail_after(2);
+    assert!(m.write(&Settings { gain: 3 }).is_err());
+    eeprom.power_on();
+    assert_eq!(gain(&eeprom, layout).unwrap(), 2);
+  }
+
+  #[test]
+  fn atomic_marker_only_moves_once_a_write_completes() {
+    let eeprom = Eeprom::new();
+    let layout = M24C64Layout::Atomic { slot_size: 64 };
+    let mut m = marshal(&eeprom, layout);
+    m.write(&Settings { gain: 1 }).unwrap();
+    assert_eq!(eeprom.byte(ADDRESS), 0);
+    m.write(&Settings { gain: 2 }).unwrap();
+    assert_eq!(eeprom.byte(ADDRESS), 1);
+
+    eeprom.fail_after(2);
+    assert!(m.write(&Settings { gain: 3 }).is_err());
+    eeprom.power_on();
+    assert_eq!(eeprom.byte(ADDRESS), 1);
+    assert_eq!(gain(&eeprom, layout).unwrap(), 2);
+  }
+}
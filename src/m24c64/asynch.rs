@@ -0,0 +1,151 @@
+extern crate alloc;
+
+use core::marker::PhantomData;
+
+use alloc::vec;
+use binmarshal::{DemarshalOwned, Marshal};
+use embedded_hal_async::{delay::DelayNs, i2c::I2c};
+
+use crate::{asynch::AsyncConfigurationMarshal, wipe::Scratch, Migrate};
+
+use super::{check_body, check_range, decode, decode_header, encode, M24C64ConfigurationError, FLAGS_OFFSET, M24C64_CAPACITY, FLAG_WRITE_IN_PROGRESS, HEADER_LEN, PAGE_SIZE, WRITE_CYCLE_MS};
+
+/// An async M24C64 marshal over `embedded-hal-async`, sharing the on-EEPROM format of
+/// [`super::M24C64ConfigurationMarshal`]. Only [`super::M24C64Layout::Single`] is supported.
+pub struct AsyncM24C64ConfigurationMarshal<Config, I2C, Delay> {
+  i2c: I2C,
+  e_addr: u8,
+  delay: Delay,
+  address_offset: usize,
+  write_delay_ms: u16,
+  seq: Option<u32>,
+  migrated: bool,
+  marker: PhantomData<Config>
+}
+
+impl<Config, I2C, Delay> AsyncM24C64ConfigurationMarshal<Config, I2C, Delay> {
+  pub fn new(i2c: I2C, e_addr: u8, address: usize, delay: Delay, marker: PhantomData<Config>) -> Self {
+    Self { i2c, e_addr, delay, address_offset: address, write_delay_ms: WRITE_CYCLE_MS, seq: None, migrated: false, marker }
+  }
+
+  /// How long to wait after each page is written for the write cycle to finish. Defaults to 10ms.
+  pub fn with_write_delay_ms(mut self, ms: u16) -> Self {
+    self.write_delay_ms = ms;
+    self
+  }
+}
+
+impl<Config, I2C, Delay> AsyncM24C64ConfigurationMarshal<Config, I2C, Delay>
+where
+  I2C: I2c,
+  Delay: DelayNs
+{
+  async fn write_page(&mut self, address: usize, bytes: &[u8]) -> Result<(), I2C::Error> {
+    let mut cmd_buf = [0u8; PAGE_SIZE + 2];
+    cmd_buf[0] = (address >> 8) as u8;
+    cmd_buf[1] = (address & 0xFF) as u8;
+    cmd_buf[2..(bytes.len() + 2)].copy_from_slice(bytes);
+
+    // The EEPROM doesn't acknowledge while it's busy with an internal write cycle, so retry for a bit
+    let mut i = 0;
+    loop {
+      match self.i2c.write(self.e_addr | 0x50, &cmd_buf[0..bytes.len() + 2]).await {
+        Ok(_) => return Ok(()),
+        Err(_) if i < 10 => (),
+        Err(e) => return Err(e)
+      }
+      i += 1;
+      self.delay.delay_ms(1).await
+    }
+  }
+
+  async fn write_eeprom(&mut self, address: usize, data: &[u8]) -> Result<(), I2C::Error> {
+    let mut i = address;
+    while i < (address + data.len()) {
+      let page_offset = i % PAGE_SIZE;
+      let end = (i - address + (PAGE_SIZE - page_offset)).min(data.len());
+      self.write_page(i, &data[(i - address)..end]).await?;
+      self.delay.delay_ms(self.write_delay_ms as u32).await;
+      i += PAGE_SIZE - page_offset;
+    }
+    Ok(())
+  }
+
+  async fn read_eeprom(&mut self, address: usize, data: &mut [u8]) -> Result<(), I2C::Error> {
+    let addr = [(address >> 8) as u8, (address & 0xFF) as u8];
+    self.i2c.write_read(self.e_addr | 0x50, &addr, data).await
+  }
+
+  /// See [`super::M24C64ConfigurationMarshal::stored_version`].
+  pub async fn stored_version(&mut self) -> Result<u8, M24C64ConfigurationError<I2C::Error>> {
+    let mut header = [0u8; HEADER_LEN];
+    self.read_eeprom(self.address_offset, &mut header).await.map_err(M24C64ConfigurationError::I2C)?;
+    let (version, _, _, _) = decode_header(&header)?;
+    Ok(version)
+  }
+}
+
+impl<Config, I2C, Delay> AsyncConfigurationMarshal<Config> for AsyncM24C64ConfigurationMarshal<Config, I2C, Delay>
+where
+  Config: Marshal<()> + DemarshalOwned + Migrate + Default,
+  I2C: I2c,
+  Delay: DelayNs
+{
+  type Error = M24C64ConfigurationError<I2C::Error>;
+
+  async fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let seq = match self.seq {
+      Some(seq) => seq.wrapping_add(1),
+      None => 0,
+    };
+    let (mut header, bytes) = encode(config, (), seq)?;
+    let needed = HEADER_LEN + bytes.len();
+    let available = M24C64_CAPACITY.saturating_sub(self.address_offset);
+    if needed > available {
+      return Err(Self::Error::TooLarge { needed, available });
+    }
+
+    self.write_eeprom(self.address_offset, &header).await.map_err(Self::Error::I2C)?;
+    self.write_eeprom(self.address_offset + HEADER_LEN, &bytes).await.map_err(Self::Error::I2C)?;
+    header[FLAGS_OFFSET] &= !FLAG_WRITE_IN_PROGRESS;
+    self.write_eeprom(self.address_offset + FLAGS_OFFSET, &header[FLAGS_OFFSET..=FLAGS_OFFSET]).await.map_err(Self::Error::I2C)?;
+
+    self.seq = Some(seq);
+    self.migrated = false;
+    Ok(())
+  }
+
+  async fn read(&mut self) -> Result<Config, Self::Error> {
+    self.migrated = false;
+
+    check_range(self.address_offset, HEADER_LEN, M24C64_CAPACITY)?;
+    let mut header = [0u8; HEADER_LEN];
+    self.read_eeprom(self.address_offset, &mut header).await.map_err(Self::Error::I2C)?;
+    let (version, schema, seq, len) = decode_header(&header)?;
+    // Reads past the end of the EEPROM wrap around to the start, rather than coming back short
+    if self.address_offset + HEADER_LEN + len > M24C64_CAPACITY {
+      return Err(Self::Error::LengthMismatch);
+    }
+
+    let mut body = Scratch(vec![0u8; len]);
+    self.read_eeprom(self.address_offset + HEADER_LEN, &mut body).await.map_err(Self::Error::I2C)?;
+    let payload = check_body(&body)?;
+    self.seq = Some(seq);
+
+    let (config, migrated) = decode(version, schema, payload, (), false, false)?;
+    self.migrated = migrated;
+    Ok(config)
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::BlankEeprom)
+  }
+
+  fn migrated(&self) -> bool {
+    self.migrated
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.seq
+  }
+}
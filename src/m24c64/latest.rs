@@ -0,0 +1,150 @@
+use binmarshal::{DemarshalOwned, Marshal};
+use embedded_hal::{blocking::{i2c, delay::DelayMs}, digital::v2::OutputPin};
+
+use crate::{ConfigurationMarshal, Migrate, RecoveryReason};
+
+use super::{M24C64ConfigurationError, M24C64ConfigurationMarshal};
+
+/// Keeps the same configuration on several M24C64s, e.g. one per hot-swappable daughterboard. Reads pick the
+/// board with the highest sequence number, falling back to older ones if its image is corrupt, and writes go
+/// to every board that's present.
+///
+/// Sequence numbers are kept in step across the boards, so whichever was written most recently wins, even
+/// if it's since been moved to another device.
+pub struct LatestWinsMarshal<Config, I2C, Delay, Wp, const N: usize> {
+  boards: [M24C64ConfigurationMarshal<Config, I2C, Delay, Wp>; N],
+  seq: Option<u32>,
+  last_source: Option<usize>,
+}
+
+impl<Config, I2C, Delay, Wp, const N: usize> LatestWinsMarshal<Config, I2C, Delay, Wp, N> {
+  pub fn new(boards: [M24C64ConfigurationMarshal<Config, I2C, Delay, Wp>; N]) -> Self {
+    Self { boards, seq: None, last_source: None }
+  }
+
+  /// The index of the board the last successful read came from.
+  pub fn last_source(&self) -> Option<usize> {
+    self.last_source
+  }
+
+  pub fn boards_mut(&mut self) -> &mut [M24C64ConfigurationMarshal<Config, I2C, Delay, Wp>; N] {
+    &mut self.boards
+  }
+
+  pub fn into_inner(self) -> [M24C64ConfigurationMarshal<Config, I2C, Delay, Wp>; N] {
+    self.boards
+  }
+}
+
+// Pick the error worth reporting when no board could be read. A missing board is expected, so a blank one
+// outranks a bus error, but anything else may mean a damaged configuration that shouldn't be overwritten.
+fn worse<E>(current: Option<M24C64ConfigurationError<E>>, new: M24C64ConfigurationError<E>) -> Option<M24C64ConfigurationError<E>> {
+  let rank = |e: &M24C64ConfigurationError<E>| match e {
+    M24C64ConfigurationError::I2C(_) => 0,
+    M24C64ConfigurationError::BlankEeprom => 1,
+    _ => 2,
+  };
+  match current {
+    Some(current) if rank(&current) >= rank(&new) => Some(current),
+    _ => Some(new),
+  }
+}
+
+impl<Config, I2C, Delay, Wp, E, const N: usize> ConfigurationMarshal<Config> for LatestWinsMarshal<Config, I2C, Delay, Wp, N>
+where
+  Config: Marshal<()> + DemarshalOwned + Migrate + Default,
+  I2C: i2c::Write<u8, Error = E> + i2c::WriteRead<u8, Error = E>,
+  Delay: DelayMs<u16>,
+  Wp: OutputPin
+{
+  type Error = M24C64ConfigurationError<E>;
+  // Bus errors from contention or a write cycle still in progress are usually transient
+  const RETRY_HINT: usize = 3;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let seq = match self.seq {
+      Some(seq) => Some(seq),
+      None => self.boards.iter_mut().filter_map(|board| board.stored_seq().ok()).max(),
+    };
+    let next_seq = seq.map(|seq| seq.wrapping_add(1)).unwrap_or(0);
+
+    // Boards that aren't plugged in will fail, which is fine as long as one of them took the write
+    let mut written = false;
+    let mut changed = false;
+    let mut error = None;
+    for board in self.boards.iter_mut() {
+      match board.unprotected(|b| b.write_elided(config, b.elide_writes, Some(next_seq))) {
+        Ok(c) => {
+          written = true;
+          changed |= c;
+        },
+        Err(e) => error = error.or(Some(e)),
+      }
+    }
+
+    match (written, error) {
+      (false, Some(e)) => Err(e),
+      _ => {
+        // Elided writes leave the stored sequence number as it was
+        if changed {
+          self.seq = Some(next_seq);
+        } else {
+          self.seq = seq;
+        }
+        Ok(())
+      },
+    }
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.last_source = None;
+
+    let mut seqs = [None; N];
+    let mut error = None;
+    for (board, seq) in self.boards.iter_mut().zip(seqs.iter_mut()) {
+      match board.stored_seq() {
+        Ok(s) => *seq = Some(s),
+        Err(e) => error = worse(error, e),
+      }
+    }
+    self.seq = seqs.iter().flatten().copied().max();
+
+    // Newest first, since a valid header doesn't guarantee a valid body
+    while let Some((i, _)) = seqs.iter().enumerate().filter_map(|(i, seq)| seq.map(|seq| (i, seq))).max_by_key(|&(_, seq)| seq) {
+      seqs[i] = None;
+      match self.boards[i].read() {
+        Ok(c) => {
+          self.last_source = Some(i);
+          return Ok(c);
+        },
+        Err(e) => error = worse(error, e),
+      }
+    }
+
+    Err(error.unwrap_or(M24C64ConfigurationError::BlankEeprom))
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::BlankEeprom)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    error.recovery_reason()
+  }
+
+  fn migrated(&self) -> bool {
+    self.last_source.map(|i| self.boards[i].migrated()).unwrap_or(false)
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.seq
+  }
+
+  fn written_externally(&mut self, _config: &Config) {
+    self.seq = None;
+    self.last_source = None;
+    for board in self.boards.iter_mut() {
+      board.invalidate();
+    }
+  }
+}
@@ -0,0 +1,198 @@
+use core::marker::PhantomData;
+
+use binmarshal::{DemarshalOwned, Marshal};
+use embedded_hal::blocking::{i2c, delay::DelayMs};
+use grapple_m24c64::M24C64;
+
+use crate::{size::MaxSize, wipe::Scratch, ConfigurationMarshal, Migrate, RecoveryReason};
+
+use super::{check_body, check_range, decode, decode_header, encode_into, write_paged, M24C64ConfigurationError, M24C64WriteCycle, CRC_LEN, FLAGS_OFFSET, FLAG_WRITE_IN_PROGRESS, HEADER_LEN, M24C64_CAPACITY, PAGE_SIZE};
+
+/// A variant of [`super::M24C64ConfigurationMarshal`] that doesn't allocate, serialising into a fixed
+/// `N`-byte stack buffer instead. Configurations that don't fit return
+/// [`M24C64ConfigurationError::TooLarge`]. Only [`super::M24C64Layout::Single`] is supported.
+pub struct NoAllocM24C64ConfigurationMarshal<Config, I2C, Delay, const N: usize> {
+  delay: Delay,
+  address_offset: usize,
+  write_cycle: M24C64WriteCycle,
+  capacity: usize,
+  verify: bool,
+  seq: Option<u32>,
+  migrated: bool,
+  eeprom: M24C64<I2C>,
+  marker: PhantomData<Config>
+}
+
+/// The smallest `N` that a [`NoAllocM24C64ConfigurationMarshal`] can store any `Config` with, going by its
+/// [`MaxSize`] bound. Rust can't yet size an array from a generic type's constant, so `N` has to be spelled
+/// out, but this can fill it in for a concrete type, e.g.
+/// `NoAllocM24C64ConfigurationMarshal<MyConfig, I2C, Delay, { buffer_len::<MyConfig>() }>`.
+pub const fn buffer_len<Config: MaxSize>() -> usize {
+  Config::MAX_SERIALISED_LEN + CRC_LEN
+}
+
+impl<Config, I2C, Delay, const N: usize> NoAllocM24C64ConfigurationMarshal<Config, I2C, Delay, N> {
+  pub fn new(eeprom: M24C64<I2C>, address: usize, delay: Delay, marker: PhantomData<Config>) -> Self {
+    Self { delay, address_offset: address, write_cycle: M24C64WriteCycle::default(), capacity: M24C64_CAPACITY, verify: false, seq: None, migrated: false, eeprom, marker }
+  }
+
+  /// See [`super::M24C64ConfigurationMarshal::with_verify`].
+  pub fn with_verify(mut self, verify: bool) -> Self {
+    self.verify = verify;
+    self
+  }
+
+  /// See [`super::M24C64ConfigurationMarshal::with_capacity`].
+  pub fn with_capacity(mut self, capacity: usize) -> Self {
+    self.capacity = capacity;
+    self
+  }
+
+  /// Like [`Self::new`], but failing the build if `N` is smaller than [`buffer_len`], so that no `Config` can
+  /// be too large for the buffer.
+  pub fn new_bounded(eeprom: M24C64<I2C>, address: usize, delay: Delay, marker: PhantomData<Config>) -> Self
+  where
+    Config: MaxSize
+  {
+    const { assert!(N >= buffer_len::<Config>(), "N is too small for the configuration's MaxSize") };
+    Self::new(eeprom, address, delay, marker)
+  }
+
+  pub fn with_write_cycle(mut self, write_cycle: M24C64WriteCycle) -> Self {
+    self.write_cycle = write_cycle;
+    self
+  }
+
+  /// See [`super::M24C64ConfigurationMarshal::release`].
+  pub fn release(self) -> (M24C64<I2C>, Delay) {
+    (self.eeprom, self.delay)
+  }
+}
+
+impl<Config, I2C, Delay, E, const N: usize> NoAllocM24C64ConfigurationMarshal<Config, I2C, Delay, N>
+where
+  I2C: i2c::Write<u8, Error = E> + i2c::WriteRead<u8, Error = E>
+{
+  /// See [`super::M24C64ConfigurationMarshal::stored_version`].
+  pub fn stored_version(&mut self) -> Result<u8, M24C64ConfigurationError<E>> {
+    check_range(self.address_offset, HEADER_LEN, self.capacity)?;
+    let mut header = [0u8; HEADER_LEN];
+    self.eeprom.read(self.address_offset, &mut header).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+    let (version, _, _, _) = decode_header(&header)?;
+    Ok(version)
+  }
+}
+
+impl<I2C, Delay, Config, E, const N: usize> ConfigurationMarshal<Config> for NoAllocM24C64ConfigurationMarshal<Config, I2C, Delay, N>
+where
+  Config: Marshal<()> + DemarshalOwned + Migrate + Default,
+  I2C: i2c::Write<u8, Error = E> + i2c::WriteRead<u8, Error = E>,
+  Delay: DelayMs<u16>
+{
+  type Error = M24C64ConfigurationError<E>;
+  // Bus errors from contention or a write cycle still in progress are usually transient
+  const RETRY_HINT: usize = 3;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let seq = match self.seq {
+      Some(seq) => seq.wrapping_add(1),
+      None => 0,
+    };
+
+    let mut body = Scratch([0u8; N]);
+    let (mut header, len) = encode_into(config, seq, &mut body[..])?;
+    let body = &body[..len];
+
+    check_range(self.address_offset, HEADER_LEN, self.capacity)?;
+    let needed = HEADER_LEN + len;
+    let available = self.capacity.saturating_sub(self.address_offset);
+    if needed > available {
+      return Err(Self::Error::TooLarge { needed, available });
+    }
+
+    write_paged(&mut self.eeprom, &mut self.delay, self.write_cycle, self.address_offset, &header)?;
+    write_paged(&mut self.eeprom, &mut self.delay, self.write_cycle, self.address_offset + HEADER_LEN, body)?;
+    header[FLAGS_OFFSET] &= !FLAG_WRITE_IN_PROGRESS;
+    write_paged(&mut self.eeprom, &mut self.delay, self.write_cycle, self.address_offset + FLAGS_OFFSET, &header[FLAGS_OFFSET..=FLAGS_OFFSET])?;
+
+    if self.verify {
+      let mut readback = [0u8; HEADER_LEN];
+      self.eeprom.read(self.address_offset, &mut readback).map_err(|e| Self::Error::I2C(e))?;
+      if readback != header {
+        return Err(Self::Error::VerifyFailed);
+      }
+
+      // Compare a page at a time, rather than needing a second N-byte buffer
+      let mut page = Scratch([0u8; PAGE_SIZE]);
+      for (i, chunk) in body.chunks(PAGE_SIZE).enumerate() {
+        let readback = &mut page[..chunk.len()];
+        self.eeprom.read(self.address_offset + HEADER_LEN + i * PAGE_SIZE, readback).map_err(|e| Self::Error::I2C(e))?;
+        if readback != chunk {
+          return Err(Self::Error::VerifyFailed);
+        }
+      }
+    }
+
+    self.seq = Some(seq);
+    self.migrated = false;
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.migrated = false;
+
+    check_range(self.address_offset, HEADER_LEN, self.capacity)?;
+    let mut header = [0u8; HEADER_LEN];
+    self.eeprom.read(self.address_offset, &mut header).map_err(|e| Self::Error::I2C(e))?;
+    let (version, schema, seq, len) = decode_header(&header)?;
+
+    if len > N {
+      return Err(Self::Error::TooLarge { needed: len, available: N });
+    }
+    if self.address_offset + HEADER_LEN + len > self.capacity {
+      return Err(Self::Error::LengthMismatch);
+    }
+
+    let mut body = Scratch([0u8; N]);
+    self.eeprom.read(self.address_offset + HEADER_LEN, &mut body[..len]).map_err(|e| Self::Error::I2C(e))?;
+    let payload = check_body(&body[..len])?;
+    self.seq = Some(seq);
+
+    let (config, migrated) = decode(version, schema, payload, (), false, false)?;
+    self.migrated = migrated;
+    Ok(config)
+  }
+
+  fn capacity(&self) -> usize {
+    let available = self.capacity.saturating_sub(self.address_offset + HEADER_LEN);
+    available.min(N).saturating_sub(CRC_LEN)
+  }
+
+  fn free(&mut self) -> Result<usize, Self::Error> {
+    check_range(self.address_offset, HEADER_LEN, self.capacity)?;
+    let mut header = [0u8; HEADER_LEN];
+    self.eeprom.read(self.address_offset, &mut header).map_err(|e| Self::Error::I2C(e))?;
+    let len = match decode_header(&header) {
+      Ok((_, _, _, len)) => len - CRC_LEN,
+      Err(Self::Error::BlankEeprom) => 0,
+      Err(e) => return Err(e),
+    };
+    Ok(ConfigurationMarshal::<Config>::capacity(self).saturating_sub(len))
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::BlankEeprom)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    error.recovery_reason()
+  }
+
+  fn migrated(&self) -> bool {
+    self.migrated
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.seq
+  }
+}
@@ -0,0 +1,144 @@
+extern crate alloc;
+
+use core::fmt;
+
+use alloc::{vec, vec::Vec};
+use embedded_hal::blocking::i2c;
+use grapple_m24c64::M24C64;
+
+use crate::RecoveryReason;
+
+use super::{check_body, decode_header, M24C64ConfigurationError, HEADER_LEN, M24C64_CAPACITY};
+
+/// A named, bounded slice of an EEPROM, handed out by [`EepromRegions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EepromRegion {
+  pub name: &'static str,
+  pub start: usize,
+  pub len: usize,
+}
+
+impl EepromRegion {
+  pub fn end(&self) -> usize {
+    self.start + self.len
+  }
+
+  fn overlaps(&self, other: &EepromRegion) -> bool {
+    self.start < other.end() && other.start < self.end()
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EepromRegionError {
+  /// The region would overlap the named, previously defined, region.
+  Overlap(&'static str),
+  /// The region would extend past the end of the EEPROM.
+  OutOfSpace,
+}
+
+impl fmt::Display for EepromRegionError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Overlap(name) => write!(f, "the region overlaps {}", name),
+      Self::OutOfSpace => f.write_str("the region extends past the end of the EEPROM"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EepromRegionError { }
+
+/// Carves an EEPROM into non-overlapping regions, so several configurations can share one part.
+/// Give each region to [`super::M24C64ConfigurationMarshal::new_in_region`], which won't write outside of it.
+pub struct EepromRegions {
+  capacity: usize,
+  regions: Vec<EepromRegion>,
+}
+
+impl EepromRegions {
+  pub fn new(capacity: usize) -> Self {
+    Self { capacity, regions: Vec::new() }
+  }
+
+  pub fn m24c64() -> Self {
+    Self::new(M24C64_CAPACITY)
+  }
+
+  /// Define a region of `len` bytes, starting immediately after the furthest region defined so far.
+  pub fn allocate(&mut self, name: &'static str, len: usize) -> Result<EepromRegion, EepromRegionError> {
+    let start = self.regions.iter().map(EepromRegion::end).max().unwrap_or(0);
+    self.define(name, start, len)
+  }
+
+  /// Define a region of `len` bytes at a fixed `start` address.
+  pub fn define(&mut self, name: &'static str, start: usize, len: usize) -> Result<EepromRegion, EepromRegionError> {
+    let region = EepromRegion { name, start, len };
+    if region.end() > self.capacity {
+      return Err(EepromRegionError::OutOfSpace);
+    }
+    if let Some(other) = self.regions.iter().find(|r| r.overlaps(&region)) {
+      return Err(EepromRegionError::Overlap(other.name));
+    }
+    self.regions.push(region);
+    Ok(region)
+  }
+
+  pub fn get(&self, name: &str) -> Option<EepromRegion> {
+    self.regions.iter().find(|r| r.name == name).copied()
+  }
+
+  pub fn regions(&self) -> &[EepromRegion] {
+    &self.regions
+  }
+
+  /// Read back every region, in the order they were defined, e.g. for a manufacturing or RMA dump, without
+  /// needing to know the configuration stored in each. A region that can't be read comes back as an error
+  /// without ending the dump.
+  pub fn dump<'a, I2C, E>(&'a self, eeprom: &'a mut M24C64<I2C>) -> impl Iterator<Item = Result<RegionDump, M24C64ConfigurationError<E>>> + 'a
+  where
+    I2C: i2c::Write<u8, Error = E> + i2c::WriteRead<u8, Error = E>
+  {
+    self.regions.iter().map(move |region| {
+      let mut bytes = vec![0u8; region.len];
+      eeprom.read(region.start, &mut bytes[..]).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+      Ok(RegionDump { region: *region, status: RegionStatus::of(&bytes), bytes })
+    })
+  }
+}
+
+/// One region's worth of [`EepromRegions::dump`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionDump {
+  pub region: EepromRegion,
+  /// Everything in the region, whether or not it's in use.
+  pub bytes: Vec<u8>,
+  pub status: RegionStatus,
+}
+
+/// Whether a region starts with an intact image, as written by [`super::M24C64ConfigurationMarshal`] with
+/// [`super::M24C64Layout::Single`]. For the other layouts, this is the status of the first slot only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RegionStatus {
+  Valid { version: u8, seq: u32, len: usize },
+  /// The image is unusable, or there isn't one, and why.
+  Invalid(RecoveryReason),
+}
+
+impl RegionStatus {
+  fn of(bytes: &[u8]) -> Self {
+    let Some(header) = bytes.get(..HEADER_LEN) else {
+      return Self::Invalid(RecoveryReason::Blank);
+    };
+    let (version, _, seq, len) = match decode_header::<()>(header.try_into().unwrap()) {
+      Ok(decoded) => decoded,
+      Err(e) => return Self::Invalid(e.recovery_reason()),
+    };
+    match bytes[HEADER_LEN..].get(..len).map(check_body::<()>) {
+      Some(Ok(payload)) => Self::Valid { version, seq, len: payload.len() },
+      Some(Err(e)) => Self::Invalid(e.recovery_reason()),
+      None => Self::Invalid(RecoveryReason::Corrupt),
+    }
+  }
+}
@@ -0,0 +1,170 @@
+use embedded_hal::{blocking::{i2c, delay::DelayMs}, digital::v2::OutputPin};
+
+use crate::{crc::{crc32_update, CRC32_POLY}, Migrate};
+
+use super::{check_range, encode_header, M24C64ConfigurationError, M24C64ConfigurationMarshal, M24C64Layout, CRC_LEN, FLAGS_OFFSET, FLAG_WRITE_IN_PROGRESS, HEADER_LEN};
+
+impl<Config, I2C, Delay, Wp, Ctx, E> M24C64ConfigurationMarshal<Config, I2C, Delay, Wp, Ctx>
+where
+  I2C: i2c::Write<u8, Error = E> + i2c::WriteRead<u8, Error = E>,
+  Delay: DelayMs<u16>,
+  Wp: OutputPin
+{
+  /// Read the stored payload a chunk at a time through `buf`, for configurations too large to buffer whole.
+  /// `f` is handed each chunk along with its offset into the payload, but only once the whole payload has
+  /// passed its CRC, so the payload is read twice. Returns the stored [`Migrate::VERSION`] and payload length.
+  ///
+  /// binmarshal can only deserialise from a contiguous buffer, so it's up to `f` to decode the payload as
+  /// it arrives.
+  pub fn read_streaming<F>(&mut self, buf: &mut [u8], mut f: F) -> Result<(u8, usize), M24C64ConfigurationError<E>>
+  where
+    F: FnMut(usize, &[u8])
+  {
+    if buf.is_empty() {
+      return Err(M24C64ConfigurationError::TooLarge { needed: 1, available: 0 });
+    }
+
+    let (slot, (version, _, _, len)) = self.latest_slot()?;
+    let address = self.slot_address(slot) + HEADER_LEN;
+    check_range(address, len, self.capacity)?;
+    let payload_len = len - CRC_LEN;
+
+    let mut crc = 0xFFFF_FFFF;
+    self.stream(address, payload_len, buf, |_, chunk| crc = crc32_update(CRC32_POLY, crc, chunk))?;
+    if self.stored_crc(address + payload_len)? != !crc {
+      return Err(M24C64ConfigurationError::ChecksumMismatch);
+    }
+
+    self.stream(address, payload_len, buf, &mut f)?;
+    self.stats.reads = self.stats.reads.wrapping_add(1);
+    Ok((version, payload_len))
+  }
+
+  // Read `len` bytes from `address` through `buf`, handing each chunk to `f` along with its offset
+  fn stream<F>(&mut self, address: usize, len: usize, buf: &mut [u8], mut f: F) -> Result<(), M24C64ConfigurationError<E>>
+  where
+    F: FnMut(usize, &[u8])
+  {
+    let mut offset = 0;
+    while offset < len {
+      let n = (len - offset).min(buf.len());
+      let chunk = &mut buf[..n];
+      self.eeprom.read(address + offset, chunk).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+      f(offset, chunk);
+      offset += chunk.len();
+    }
+    Ok(())
+  }
+
+  fn stored_crc(&mut self, address: usize) -> Result<u32, M24C64ConfigurationError<E>> {
+    let mut crc = [0u8; CRC_LEN];
+    self.eeprom.read(address, &mut crc[..]).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+    Ok(u32::from_le_bytes(crc))
+  }
+}
+
+impl<Config, I2C, Delay, Wp, Ctx, E> M24C64ConfigurationMarshal<Config, I2C, Delay, Wp, Ctx>
+where
+  Config: Migrate,
+  I2C: i2c::Write<u8, Error = E> + i2c::WriteRead<u8, Error = E>,
+  Delay: DelayMs<u16>,
+  Wp: OutputPin
+{
+  /// Write a `len`-byte payload a chunk at a time through `buf`, for configurations too large to serialise
+  /// whole. `f` fills each chunk, given its offset into the payload. The image is framed just as
+  /// [`crate::ConfigurationMarshal::write`] frames it, so it can be read back either way, but the write is
+  /// never elided.
+  pub fn write_streaming<F>(&mut self, len: usize, buf: &mut [u8], f: F) -> Result<(), M24C64ConfigurationError<E>>
+  where
+    F: FnMut(usize, &mut [u8])
+  {
+    self.unprotected(|s| s.write_streaming_unprotected(len, buf, f))
+  }
+
+  fn write_streaming_unprotected<F>(&mut self, len: usize, buf: &mut [u8], mut f: F) -> Result<(), M24C64ConfigurationError<E>>
+  where
+    F: FnMut(usize, &mut [u8])
+  {
+    if buf.is_empty() {
+      return Err(M24C64ConfigurationError::TooLarge { needed: 1, available: 0 });
+    }
+    if len == 0 {
+      return Err(M24C64ConfigurationError::EmptySerialisation);
+    }
+    if len > u16::MAX as usize {
+      return Err(M24C64ConfigurationError::TooLarge { needed: len, available: u16::MAX as usize });
+    }
+    if let Some(available) = self.slot_capacity() {
+      let needed = HEADER_LEN + len + CRC_LEN;
+      if needed > available {
+        return Err(M24C64ConfigurationError::TooLarge { needed, available });
+      }
+    }
+
+    let latest = match self.latest {
+      Some(latest) => Some(latest),
+      None => match self.latest_slot() {
+        Ok((slot, (_, _, seq, _))) => Some((slot, seq)),
+        Err(e @ M24C64ConfigurationError::I2C(_)) => return Err(e),
+        Err(_) => None,
+      },
+    };
+
+    // As with a regular write, leave the latest image intact until this one is complete
+    let (slot, seq) = match latest {
+      Some((slot, seq)) => ((slot + 1) % self.slot_count(), seq.wrapping_add(1)),
+      None => (0, 0),
+    };
+    let address = self.slot_address(slot);
+    let mut header = encode_header::<Config>(seq, len, self.length_endianness, self.stamp);
+
+    check_range(address, HEADER_LEN, self.capacity)?;
+    let needed = HEADER_LEN + len + CRC_LEN;
+    let available = self.capacity.saturating_sub(address);
+    let available = self.slot_capacity().map(|slot| slot.min(available)).unwrap_or(available);
+    if needed > available {
+      return Err(M24C64ConfigurationError::TooLarge { needed, available });
+    }
+
+    // The stored image no longer matches what we know of it
+    self.cached = None;
+    self.write_paged(address, &header)?;
+
+    let mut crc = 0xFFFF_FFFF;
+    let mut offset = 0;
+    while offset < len {
+      let n = (len - offset).min(buf.len());
+      let chunk = &mut buf[..n];
+      f(offset, chunk);
+      crc = crc32_update(CRC32_POLY, crc, chunk);
+      self.write_paged(address + HEADER_LEN + offset, chunk)?;
+      offset += chunk.len();
+    }
+    let crc = !crc;
+    self.write_paged(address + HEADER_LEN + len, &crc.to_le_bytes())?;
+
+    header[FLAGS_OFFSET] &= !FLAG_WRITE_IN_PROGRESS;
+    self.write_paged(address + FLAGS_OFFSET, &header[FLAGS_OFFSET..=FLAGS_OFFSET])?;
+
+    let atomic = matches!(self.layout, M24C64Layout::Atomic { .. });
+    if self.verify || atomic {
+      let mut readback = [0u8; HEADER_LEN];
+      self.eeprom.read(address, &mut readback[..]).map_err(|e| M24C64ConfigurationError::I2C(e))?;
+      let mut check = 0xFFFF_FFFF;
+      self.stream(address + HEADER_LEN, len, buf, |_, chunk| check = crc32_update(CRC32_POLY, check, chunk))?;
+      if readback != header || !check != crc || self.stored_crc(address + HEADER_LEN + len)? != crc {
+        return Err(M24C64ConfigurationError::VerifyFailed);
+      }
+    }
+
+    if atomic {
+      self.write_paged(self.address_offset, &[slot as u8])?;
+    }
+
+    self.latest = Some((slot, seq));
+    self.timestamps[slot] = self.stamp.take();
+    self.migrated = false;
+    self.stats.writes = self.stats.writes.wrapping_add(1);
+    Ok(())
+  }
+}
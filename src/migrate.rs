@@ -0,0 +1,47 @@
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MigrationError {
+  /// There's no migration from the stored version to the current one.
+  UnsupportedVersion(u8),
+  Serialisation,
+}
+
+impl fmt::Display for MigrationError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::UnsupportedVersion(version) => write!(f, "there's no migration from version {}", version),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MigrationError { }
+
+/// A configuration whose stored representation is versioned, and can be upgraded from older versions.
+///
+/// The default implementation stores everything as version 0 and refuses to migrate, so configurations
+/// that have never changed shape only need an empty `impl Migrate for MyConfig {}`.
+pub trait Migrate: Sized {
+  /// The version written alongside the configuration.
+  const VERSION: u8 = 0;
+
+  /// A hash of the configuration's layout, written alongside the version so that a layout change without a
+  /// version bump is caught on read, e.g. `const SCHEMA: u32 = <Self as Schema>::SCHEMA_HASH;`. 0 skips the check.
+  const SCHEMA: u32 = 0;
+
+  /// Upgrade `bytes`, serialised as `version`, into the current configuration. Implementations will
+  /// typically walk a chain of per-version upgrade steps until they reach [`Self::VERSION`].
+  fn migrate(version: u8, bytes: &[u8]) -> Result<Self, MigrationError> {
+    let _ = bytes;
+    Err(MigrationError::UnsupportedVersion(version))
+  }
+}
+
+/// A hash of a type's layout. `#[derive(Schema)]`, with the `derive` feature, hashes the type of every field
+/// in order. It can't see through type aliases or into nested types, so changes to those aren't caught.
+pub trait Schema {
+  const SCHEMA_HASH: u32;
+}
@@ -0,0 +1,250 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::vec::Vec;
+use binmarshal::{rw::{BitView, BitWriter, VecBitWriter}, DemarshalOwned, Marshal};
+
+use crate::{migrate::MigrationError, store::ByteStore, ConfigurationMarshal, RecoveryReason};
+
+const MAGIC: [u8; 2] = *b"GR";
+// [magic:2][version:1][len:2]
+const HEADER_LEN: usize = 5;
+
+/// Upgrades a serialised configuration by one version.
+pub type MigrationStep = fn(&[u8]) -> Result<Vec<u8>, MigrationError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MigratingError<E> {
+  Store(E),
+  Serialisation,
+  Blank,
+  Migration(MigrationError)
+}
+
+impl<E: fmt::Debug> fmt::Display for MigratingError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Store(e) => write!(f, "store error: {:?}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Blank => f.write_str("nothing is stored"),
+      Self::Migration(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for MigratingError<E> { }
+
+/// Returned by [`MigratingMarshal::new_checked`] when there's no chain of steps upgrading version `from`
+/// to version `to`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MigrationGap {
+  pub from: u8,
+  pub to: u8,
+}
+
+impl fmt::Display for MigrationGap {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "there's no chain of migrations from version {} to version {}", self.from, self.to)
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MigrationGap { }
+
+#[derive(Clone, Copy)]
+enum Steps<'a> {
+  // steps[n] upgrades version n
+  Indexed(&'a [MigrationStep]),
+  // (version, step), contiguous up to the current version
+  Versioned(&'a [(u8, MigrationStep)], u8),
+}
+
+/// Stores a versioned configuration in a [`ByteStore`], upgrading older versions on read with a list of
+/// migration steps. `steps[n]` upgrades version `n` to `n + 1`, so the current version is `steps.len()`.
+///
+/// Upgraded configurations are reported by [`ConfigurationMarshal::migrated`], so providers write them back
+/// when they're constructed, and each migration only runs once. Unlike
+/// [`crate::Migrate`], the migrations live alongside the marshal rather than the configuration type.
+pub struct MigratingMarshal<'a, Inner, Config> {
+  inner: Inner,
+  steps: Steps<'a>,
+  // Whether the configuration last read was upgraded, and hasn't been written since
+  migrated: bool,
+  marker: PhantomData<Config>
+}
+
+impl<'a, Inner, Config> MigratingMarshal<'a, Inner, Config> {
+  pub fn new(inner: Inner, steps: &'a [MigrationStep]) -> Self {
+    Self { inner, steps: Steps::Indexed(steps), migrated: false, marker: PhantomData }
+  }
+
+  /// Like [`Self::new`], but with each step listed alongside the version it upgrades from, in ascending order.
+  /// The steps must run contiguously up to `version`, the current version, so that a forgotten step is caught
+  /// here rather than skipped in the field. Versions older than the first step can't be read.
+  pub fn new_checked(inner: Inner, steps: &'a [(u8, MigrationStep)], version: u8) -> Result<Self, MigrationGap> {
+    let mut expected = steps.first().map(|(from, _)| *from).unwrap_or(version);
+    for (from, _) in steps.iter() {
+      if *from != expected {
+        return Err(MigrationGap { from: expected, to: *from });
+      }
+      expected = expected.wrapping_add(1);
+    }
+    if expected != version {
+      return Err(MigrationGap { from: expected, to: version });
+    }
+    Ok(Self { inner, steps: Steps::Versioned(steps, version), migrated: false, marker: PhantomData })
+  }
+
+  pub fn version(&self) -> u8 {
+    match self.steps {
+      Steps::Indexed(steps) => steps.len() as u8,
+      Steps::Versioned(_, version) => version,
+    }
+  }
+
+  // The step upgrading `version`, if there is one
+  fn step(&self, version: u8) -> Option<MigrationStep> {
+    match self.steps {
+      Steps::Indexed(steps) => steps.get(version as usize).copied(),
+      Steps::Versioned(steps, _) => {
+        let first = steps.first()?.0;
+        steps.get(version.checked_sub(first)? as usize).map(|(_, step)| *step)
+      },
+    }
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+}
+
+impl<'a, Inner: ByteStore, Config> MigratingMarshal<'a, Inner, Config> {
+  /// The version of the stored configuration, read from its header without reading the configuration.
+  pub fn stored_version(&mut self) -> Result<u8, MigratingError<Inner::Error>> {
+    let mut header = [0u8; HEADER_LEN];
+    self.inner.read_at(0, &mut header).map_err(MigratingError::Store)?;
+    if header[0..2] != MAGIC {
+      return Err(MigratingError::Blank);
+    }
+    Ok(header[2])
+  }
+
+  fn write_bytes(&mut self, payload: &[u8]) -> Result<(), MigratingError<Inner::Error>> {
+    if payload.len() > u16::MAX as usize {
+      return Err(MigratingError::Serialisation);
+    }
+    let mut image = Vec::with_capacity(HEADER_LEN + payload.len());
+    image.extend_from_slice(&MAGIC);
+    image.push(self.version());
+    image.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    image.extend_from_slice(payload);
+    self.inner.write_at(0, &image).map_err(MigratingError::Store)
+  }
+}
+
+impl<'a, Inner, Config> ConfigurationMarshal<Config> for MigratingMarshal<'a, Inner, Config>
+where
+  Inner: ByteStore,
+  Config: Marshal<()> + DemarshalOwned
+{
+  type Error = MigratingError<Inner::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let mut writer = VecBitWriter::new();
+    if config.write(&mut writer, ()).is_err() {
+      return Err(Self::Error::Serialisation);
+    }
+    self.write_bytes(writer.slice())?;
+    self.migrated = false;
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.migrated = false;
+
+    let mut header = [0u8; HEADER_LEN];
+    self.inner.read_at(0, &mut header).map_err(Self::Error::Store)?;
+
+    if header[0..2] != MAGIC {
+      return Err(Self::Error::Blank);
+    }
+
+    let version = header[2];
+    if version > self.version() {
+      return Err(Self::Error::Migration(MigrationError::UnsupportedVersion(version)));
+    }
+
+    let len = u16::from_le_bytes([header[3], header[4]]) as usize;
+    if HEADER_LEN + len > self.inner.capacity() {
+      return Err(Self::Error::Serialisation);
+    }
+    let mut payload = alloc::vec![0u8; len];
+    self.inner.read_at(HEADER_LEN, &mut payload).map_err(Self::Error::Store)?;
+
+    for v in version..self.version() {
+      let step = self.step(v).ok_or(Self::Error::Migration(MigrationError::UnsupportedVersion(version)))?;
+      payload = step(&payload).map_err(Self::Error::Migration)?;
+    }
+
+    let config = match Config::read(&mut BitView::new(&payload), ()) {
+      Ok(c) => c,
+      Err(_) => return Err(Self::Error::Serialisation),
+    };
+
+    self.migrated = version < self.version();
+    Ok(config)
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      Self::Error::Blank => RecoveryReason::Blank,
+      Self::Error::Serialisation | Self::Error::Migration(MigrationError::Serialisation) => RecoveryReason::Corrupt,
+      Self::Error::Migration(MigrationError::UnsupportedVersion(_)) => RecoveryReason::UnsupportedVersion,
+      Self::Error::Store(_) => RecoveryReason::Unreadable,
+    }
+  }
+
+  fn migrated(&self) -> bool {
+    self.migrated
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use binmarshal::{Demarshal, Marshal};
+
+  use super::*;
+
+  #[derive(Debug, Clone, Default, PartialEq, Marshal, Demarshal)]
+  struct Settings {
+    gain: u32,
+  }
+
+  #[test]
+  fn round_trip() {
+    let mut storage = [0xFFu8; 16];
+    let mut m = MigratingMarshal::<_, Settings>::new(&mut storage[..], &[]);
+    m.write(&Settings { gain: 42 }).unwrap();
+    assert_eq!(m.read().unwrap(), Settings { gain: 42 });
+  }
+
+  #[test]
+  fn length_past_the_store_is_corrupt() {
+    let mut storage = [0xFFu8; 16];
+    MigratingMarshal::<_, Settings>::new(&mut storage[..], &[]).write(&Settings { gain: 42 }).unwrap();
+    storage[3..5].copy_from_slice(&12u16.to_le_bytes());
+
+    let mut m = MigratingMarshal::<_, Settings>::new(&mut storage[..], &[]);
+    let e = m.read().unwrap_err();
+    assert_eq!(e, MigratingError::Serialisation);
+    assert_eq!(MigratingMarshal::<&mut [u8], Settings>::recovery_reason(&e), RecoveryReason::Corrupt);
+  }
+}
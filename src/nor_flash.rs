@@ -0,0 +1,153 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::vec;
+use binmarshal::{rw::{BitView, BitWriter, VecBitWriter}, DemarshalOwned, Marshal};
+use embedded_storage::nor_flash::NorFlash;
+
+use crate::{store::ByteStore, ConfigurationMarshal};
+
+const MAGIC: [u8; 2] = *b"GR";
+// [magic:2][len:2]
+const HEADER_LEN: usize = 4;
+
+fn round_up(len: usize, granularity: usize) -> usize {
+  len.div_ceil(granularity) * granularity
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NorFlashConfigError<E> {
+  Flash(E),
+  Serialisation,
+  Blank
+}
+
+impl<E: fmt::Debug> fmt::Display for NorFlashConfigError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Flash(e) => write!(f, "flash error: {:?}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Blank => f.write_str("nothing is stored"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for NorFlashConfigError<E> { }
+
+/// Stores the configuration in NOR flash (e.g. the MCU's internal flash), starting at `offset`.
+/// `offset` must be aligned to the flash's erase size, and the sectors covering the configuration
+/// are erased on every write.
+pub struct NorFlashConfigurationMarshal<Config, Flash> {
+  flash: Flash,
+  offset: u32,
+  marker: PhantomData<Config>
+}
+
+impl<Config, Flash> NorFlashConfigurationMarshal<Config, Flash> {
+  pub fn new(flash: Flash, offset: u32, marker: PhantomData<Config>) -> Self {
+    Self { flash, offset, marker }
+  }
+}
+
+impl<Config, Flash> ConfigurationMarshal<Config> for NorFlashConfigurationMarshal<Config, Flash>
+where
+  Config: Marshal<()> + DemarshalOwned,
+  Flash: NorFlash
+{
+  type Error = NorFlashConfigError<Flash::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let mut writer = VecBitWriter::new();
+    if config.write(&mut writer, ()).is_err() {
+      return Err(Self::Error::Serialisation);
+    }
+    let payload = writer.slice();
+
+    let mut image = vec![0xFFu8; round_up(HEADER_LEN + payload.len(), Flash::WRITE_SIZE)];
+    image[0..2].copy_from_slice(&MAGIC);
+    image[2..4].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+    image[HEADER_LEN..HEADER_LEN + payload.len()].copy_from_slice(payload);
+
+    let erase_len = round_up(image.len(), Flash::ERASE_SIZE) as u32;
+    self.flash.erase(self.offset, self.offset + erase_len).map_err(Self::Error::Flash)?;
+    self.flash.write(self.offset, &image).map_err(Self::Error::Flash)?;
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let mut header = vec![0u8; round_up(HEADER_LEN, Flash::READ_SIZE)];
+    self.flash.read(self.offset, &mut header).map_err(Self::Error::Flash)?;
+
+    if header[0..2] != MAGIC {
+      return Err(Self::Error::Blank);
+    }
+
+    let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+    let mut buf = vec![0u8; round_up(HEADER_LEN + len, Flash::READ_SIZE)];
+    self.flash.read(self.offset, &mut buf).map_err(Self::Error::Flash)?;
+
+    match Config::read(&mut BitView::new(&buf[HEADER_LEN..HEADER_LEN + len]), ()) {
+      Ok(c) => Ok(c),
+      Err(_) => Err(Self::Error::Serialisation),
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+}
+
+/// Raw byte access to NOR flash from `offset` onwards, for use with the generic marshals. Writes
+/// read, erase and rewrite every sector they touch, so `offset` must be aligned to the erase size.
+pub struct NorFlashStore<Flash> {
+  flash: Flash,
+  offset: u32,
+}
+
+impl<Flash> NorFlashStore<Flash> {
+  pub fn new(flash: Flash, offset: u32) -> Self {
+    Self { flash, offset }
+  }
+
+  pub fn into_inner(self) -> Flash {
+    self.flash
+  }
+}
+
+impl<Flash: NorFlash> ByteStore for NorFlashStore<Flash> {
+  type Error = Flash::Error;
+
+  fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Self::Error> {
+    let start = offset - offset % Flash::READ_SIZE;
+    let mut aligned = vec![0u8; round_up(offset + buf.len() - start, Flash::READ_SIZE)];
+    self.flash.read(self.offset + start as u32, &mut aligned)?;
+    buf.copy_from_slice(&aligned[offset - start..offset - start + buf.len()]);
+    Ok(())
+  }
+
+  fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Self::Error> {
+    let mut sector = vec![0u8; Flash::ERASE_SIZE];
+    let mut written = 0;
+    while written < bytes.len() {
+      let sector_start = (offset + written) - (offset + written) % Flash::ERASE_SIZE;
+      let within = offset + written - sector_start;
+      let n = (Flash::ERASE_SIZE - within).min(bytes.len() - written);
+
+      let address = self.offset + sector_start as u32;
+      self.flash.read(address, &mut sector)?;
+      sector[within..within + n].copy_from_slice(&bytes[written..written + n]);
+      self.flash.erase(address, address + Flash::ERASE_SIZE as u32)?;
+      self.flash.write(address, &sector)?;
+
+      written += n;
+    }
+    Ok(())
+  }
+
+  fn capacity(&self) -> usize {
+    self.flash.capacity().saturating_sub(self.offset as usize)
+  }
+}
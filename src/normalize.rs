@@ -0,0 +1,115 @@
+use crate::{ConfigurationMarshal, RecoveryReason};
+
+/// A configuration that can repair stale values in place, e.g. clamping a field into a range that a newer
+/// firmware narrowed, rather than having it fail [`crate::Validate::validate`] and lose every other setting.
+pub trait Normalize {
+  fn normalize(&mut self);
+}
+
+/// Runs [`Normalize::normalize`] on every configuration read, so that stored values are repaired before they
+/// reach the application, or [`crate::load_config`]'s validation. A configuration that normalising changed
+/// is reported by [`ConfigurationMarshal::migrated`], so providers write the repaired configuration back
+/// when they're constructed, and report [`crate::InitOutcome::Migrated`]. Unlike a migration, there's no
+/// version to bump.
+pub struct NormalizingMarshal<Inner> {
+  inner: Inner,
+  // Whether the configuration last read was changed by normalising it, and hasn't been written since
+  normalized: bool,
+}
+
+impl<Inner> NormalizingMarshal<Inner> {
+  pub fn new(inner: Inner) -> Self {
+    Self { inner, normalized: false }
+  }
+
+  /// Whether the configuration last read had values that normalising changed, and hasn't been written since.
+  pub fn normalized(&self) -> bool {
+    self.normalized
+  }
+
+  pub fn inner_mut(&mut self) -> &mut Inner {
+    &mut self.inner
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+}
+
+impl<Config, Inner> ConfigurationMarshal<Config> for NormalizingMarshal<Inner>
+where
+  Config: Normalize + Clone + PartialEq,
+  Inner: ConfigurationMarshal<Config>
+{
+  type Error = Inner::Error;
+  const RETRY_HINT: usize = Inner::RETRY_HINT;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.inner.write(config)?;
+    self.normalized = false;
+    Ok(())
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.inner.write_now(config)?;
+    self.normalized = false;
+    Ok(())
+  }
+
+  fn write_serialised(&mut self, config: &Config, bytes: &[u8]) -> Result<(), Self::Error> {
+    self.inner.write_serialised(config, bytes)?;
+    self.normalized = false;
+    Ok(())
+  }
+
+  fn write_changed(&mut self, config: &Config) -> Result<bool, Self::Error> {
+    let changed = self.inner.write_changed(config)?;
+    self.normalized = false;
+    Ok(changed)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let stored = self.inner.read()?;
+    let mut config = stored.clone();
+    config.normalize();
+    self.normalized = config != stored;
+    Ok(config)
+  }
+
+  fn capacity(&self) -> usize {
+    self.inner.capacity()
+  }
+
+  fn free(&mut self) -> Result<usize, Self::Error> {
+    self.inner.free()
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    Inner::is_blank(error)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    Inner::recovery_reason(error)
+  }
+
+  fn migrated(&self) -> bool {
+    self.normalized || self.inner.migrated()
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.inner.revision()
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.inner.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.inner.last_modified()
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    self.normalized = false;
+    self.inner.written_externally(config)
+  }
+}
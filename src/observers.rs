@@ -0,0 +1,90 @@
+use core::fmt;
+
+use crate::{CommitObserver, ConfigurationMarshal, ConfigurationProvider};
+
+/// There's no room left in a [`CommitObservers`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ObserversFull;
+
+impl fmt::Display for ObserversFull {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("there's no room for another observer")
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ObserversFull { }
+
+/// Identifies an observer registered with [`CommitObservers::register`], to unregister it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ObserverHandle(usize);
+
+/// Up to `OBS` commit observers, without an allocator, for when several subsystems each want to react to
+/// changes, e.g. a display, a control loop, and telemetry. Install it with
+/// [`ConfigurationProvider::on_commit`], then register observers through
+/// [`ConfigurationProvider::add_observer`].
+///
+/// Observers are called in the order of their slots, which is the order they were registered in, unless a
+/// later one took the slot of one that was unregistered.
+pub struct CommitObservers<Config, const OBS: usize> {
+  observers: [Option<fn(&Config)>; OBS],
+}
+
+impl<Config, const OBS: usize> CommitObservers<Config, OBS> {
+  pub const fn new() -> Self {
+    Self { observers: [None; OBS] }
+  }
+
+  pub fn register(&mut self, observer: fn(&Config)) -> Result<ObserverHandle, ObserversFull> {
+    let (i, slot) = self.observers.iter_mut().enumerate().find(|(_, slot)| slot.is_none()).ok_or(ObserversFull)?;
+    *slot = Some(observer);
+    Ok(ObserverHandle(i))
+  }
+
+  /// Stop calling the observer registered under `handle`, freeing its slot.
+  pub fn unregister(&mut self, handle: ObserverHandle) {
+    if let Some(slot) = self.observers.get_mut(handle.0) {
+      *slot = None;
+    }
+  }
+
+  /// How many observers are registered.
+  pub fn len(&self) -> usize {
+    self.observers.iter().filter(|slot| slot.is_some()).count()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+impl<Config, const OBS: usize> Default for CommitObservers<Config, OBS> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<Config, const OBS: usize> CommitObserver<Config> for CommitObservers<Config, OBS> {
+  fn committed(&mut self, config: &Config) {
+    for observer in self.observers.iter().flatten() {
+      observer(config)
+    }
+  }
+}
+
+impl<Config, Marshal, const OBS: usize> ConfigurationProvider<Config, Marshal, CommitObservers<Config, OBS>>
+where
+  Marshal: ConfigurationMarshal<Config>
+{
+  /// Register `observer` to be called with the new configuration after each successful commit, alongside
+  /// those already registered.
+  pub fn add_observer(&mut self, observer: fn(&Config)) -> Result<ObserverHandle, ObserversFull> {
+    self.observer.register(observer)
+  }
+
+  pub fn remove_observer(&mut self, handle: ObserverHandle) {
+    self.observer.unregister(handle)
+  }
+}
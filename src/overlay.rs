@@ -0,0 +1,86 @@
+extern crate alloc;
+
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::GenericConfigurationProvider;
+
+type Override<Config> = Box<dyn Fn(&mut Config)>;
+
+/// Layers temporary, RAM-only overrides over a provider's configuration, e.g. for a tuning session. The
+/// overrides are applied on top of the saved configuration to give the [`Self::live`] one, but never reach
+/// [`Self::commit`], and [`Self::clear_overrides`] snaps back to the saved values.
+pub struct OverlayProvider<Config, Provider> {
+  provider: Provider,
+  overrides: Vec<Override<Config>>,
+  live: Config,
+}
+
+impl<Config, Provider> OverlayProvider<Config, Provider>
+where
+  Config: Clone,
+  Provider: GenericConfigurationProvider<Config>
+{
+  pub fn new(provider: Provider) -> Self {
+    let live = provider.current().clone();
+    Self { provider, overrides: Vec::new(), live }
+  }
+
+  /// Override part of the configuration, e.g. `overlay.set_override(|c| c.kp = 1.2)`. Later overrides are
+  /// applied after earlier ones.
+  pub fn set_override<F: Fn(&mut Config) + 'static>(&mut self, f: F) {
+    f(&mut self.live);
+    self.overrides.push(Box::new(f));
+  }
+
+  /// Drop every override, reverting the live configuration to the saved one.
+  pub fn clear_overrides(&mut self) {
+    self.overrides.clear();
+    self.refresh();
+  }
+
+  pub fn has_overrides(&self) -> bool {
+    !self.overrides.is_empty()
+  }
+
+  /// The saved configuration, with the overrides applied.
+  pub fn live(&self) -> &Config {
+    &self.live
+  }
+
+  /// The saved configuration, without any overrides.
+  pub fn saved(&self) -> &Config {
+    self.provider.current()
+  }
+
+  /// Change the saved configuration, reapplying the overrides on top. Nothing is committed.
+  pub fn update_saved<F: FnOnce(&mut Config)>(&mut self, f: F) {
+    f(self.provider.current_mut());
+    self.refresh();
+  }
+
+  /// Commit the saved configuration. The overrides stay in place, but aren't written.
+  pub fn commit(&mut self) -> Result<(), Provider::Error> {
+    self.provider.commit()
+  }
+
+  /// Rebuild the live configuration from the saved one, e.g. after changing it through [`Self::provider_mut`].
+  pub fn refresh(&mut self) {
+    self.live = self.provider.current().clone();
+    for f in self.overrides.iter() {
+      f(&mut self.live);
+    }
+  }
+
+  pub fn provider(&self) -> &Provider {
+    &self.provider
+  }
+
+  pub fn provider_mut(&mut self) -> &mut Provider {
+    &mut self.provider
+  }
+
+  /// Drop the overrides, returning the wrapped provider.
+  pub fn into_inner(self) -> Provider {
+    self.provider
+  }
+}
@@ -0,0 +1,232 @@
+use core::{fmt, marker::PhantomData};
+
+use binmarshal::{rw::{BitView, BitWriter, BufferBitWriter}, DemarshalOwned, Marshal, MarshalError};
+
+use crate::ConfigurationMarshal;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HeaplessMarshalError {
+  /// Nothing has been written yet, or the buffer was cleared.
+  Blank,
+  BufferTooSmall,
+  Serialisation,
+}
+
+impl fmt::Display for HeaplessMarshalError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Blank => f.write_str("nothing is stored"),
+      Self::BufferTooSmall => f.write_str("the buffer is too small for the configuration"),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for HeaplessMarshalError { }
+
+impl From<MarshalError> for HeaplessMarshalError {
+  fn from(e: MarshalError) -> Self {
+    match e {
+      MarshalError::BufferTooSmall => Self::BufferTooSmall,
+      _ => Self::Serialisation,
+    }
+  }
+}
+
+/// Serialises the configuration into an owned buffer of `N` bytes, and deserialises it back on read, without
+/// an allocator or a borrowed buffer. Unlike [`crate::VolatileMarshal`], the configuration round-trips through
+/// binmarshal like it would with real storage, which makes this the go-to test double for a provider's
+/// commit, reload, and discard logic.
+///
+/// Reads fail with [`HeaplessMarshalError::Blank`] until the first write, like erased storage would.
+pub struct HeaplessMarshal<Config, const N: usize> {
+  buffer: [u8; N],
+  len: Option<usize>,
+  marker: PhantomData<Config>
+}
+
+impl<Config, const N: usize> HeaplessMarshal<Config, N> {
+  pub const fn new() -> Self {
+    Self { buffer: [0u8; N], len: None, marker: PhantomData }
+  }
+
+  /// The bytes of the last write, or `None` if nothing has been written.
+  pub fn bytes(&self) -> Option<&[u8]> {
+    self.len.map(|len| &self.buffer[..len])
+  }
+
+  /// The bytes of the last write, to corrupt for testing.
+  pub fn bytes_mut(&mut self) -> Option<&mut [u8]> {
+    self.len.map(|len| &mut self.buffer[..len])
+  }
+
+  /// Forget the last write, as if the storage were erased.
+  pub fn clear(&mut self) {
+    self.len = None;
+  }
+}
+
+impl<Config, const N: usize> Default for HeaplessMarshal<Config, N> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<Config, const N: usize> ConfigurationMarshal<Config> for HeaplessMarshal<Config, N>
+where
+  Config: Marshal<()> + DemarshalOwned
+{
+  type Error = HeaplessMarshalError;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    // Serialise to the side, so that a failed write leaves the last one in place
+    let mut scratch = [0u8; N];
+    let mut writer = BufferBitWriter::new(&mut scratch);
+    config.write(&mut writer, ())?;
+    let len = writer.slice().len();
+
+    self.buffer[..len].copy_from_slice(&scratch[..len]);
+    self.len = Some(len);
+    Ok(())
+  }
+
+  fn write_changed(&mut self, config: &Config) -> Result<bool, Self::Error> {
+    let mut scratch = [0u8; N];
+    let mut writer = BufferBitWriter::new(&mut scratch);
+    config.write(&mut writer, ())?;
+    let len = writer.slice().len();
+
+    if self.bytes() == Some(&scratch[..len]) {
+      return Ok(false);
+    }
+    self.buffer[..len].copy_from_slice(&scratch[..len]);
+    self.len = Some(len);
+    Ok(true)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let bytes = self.bytes().ok_or(Self::Error::Blank)?;
+    Ok(Config::read(&mut BitView::new(bytes), ())?)
+  }
+
+  fn capacity(&self) -> usize {
+    N
+  }
+
+  fn free(&mut self) -> Result<usize, Self::Error> {
+    Ok(N - self.len.unwrap_or(0))
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use core::cell::Cell;
+
+  use binmarshal::Demarshal;
+
+  use crate::{fault::FaultInjectingMarshal, CommitPolicy, ConfigurationProvider, GenericConfigurationProvider, InitOutcome};
+  use super::*;
+
+  #[derive(Debug, Clone, Default, PartialEq, Marshal, Demarshal)]
+  struct Settings {
+    gain: u32,
+  }
+
+  type Counted = FaultInjectingMarshal<HeaplessMarshal<Settings, 8>>;
+
+  fn provider(policy: CommitPolicy) -> ConfigurationProvider<Settings, Counted> {
+    ConfigurationProvider::new(FaultInjectingMarshal::new(HeaplessMarshal::new())).ok().unwrap().with_commit_policy(policy)
+  }
+
+  #[test]
+  fn commit_survives_a_reload() {
+    let mut p = provider(CommitPolicy::AlwaysWrite);
+    assert_eq!(p.init_outcome(), InitOutcome::Defaulted);
+    p.current_mut().gain = 5;
+    p.commit().unwrap();
+
+    let p = ConfigurationProvider::<Settings, _>::new(p.into_inner()).ok().unwrap();
+    assert_eq!(p.init_outcome(), InitOutcome::Loaded);
+    assert_eq!(p.current(), &Settings { gain: 5 });
+  }
+
+  #[test]
+  fn discard_restores_the_committed_configuration() {
+    let mut p = provider(CommitPolicy::AlwaysWrite);
+    p.current_mut().gain = 5;
+    p.commit().unwrap();
+    p.current_mut().gain = 6;
+    p.discard().unwrap();
+    assert_eq!(p.current(), &Settings { gain: 5 });
+    assert!(!p.is_dirty());
+  }
+
+  #[test]
+  fn batch_is_committed_once_at_the_end() {
+    let mut p = provider(CommitPolicy::AlwaysWrite);
+    let writes = p.marshal().writes();
+    p.begin();
+    p.current_mut().gain = 1;
+    p.commit().unwrap();
+    p.current_mut().gain = 2;
+    p.commit().unwrap();
+    assert_eq!(p.marshal().writes(), writes);
+    assert!(p.is_dirty());
+
+    p.end().unwrap();
+    assert_eq!(p.marshal().writes(), writes + 1);
+    assert_eq!(p.peek_stored().unwrap(), Settings { gain: 2 });
+  }
+
+  #[test]
+  fn always_write_writes_every_commit() {
+    let mut p = provider(CommitPolicy::AlwaysWrite);
+    let writes = p.marshal().writes();
+    p.commit().unwrap();
+    p.commit().unwrap();
+    assert_eq!(p.marshal().writes(), writes + 2);
+  }
+
+  #[test]
+  fn elide_unless_dirty_skips_clean_commits() {
+    let mut p = provider(CommitPolicy::ElideUnlessDirty);
+    let writes = p.marshal().writes();
+    p.commit().unwrap();
+    assert_eq!(p.marshal().writes(), writes);
+
+    p.current_mut().gain = 3;
+    p.commit().unwrap();
+    assert_eq!(p.marshal().writes(), writes + 1);
+    assert_eq!(p.peek_stored().unwrap(), Settings { gain: 3 });
+  }
+
+  #[test]
+  fn factory_reset_writes_under_elide_unless_dirty() {
+    let mut p = provider(CommitPolicy::ElideUnlessDirty);
+    p.current_mut().gain = 3;
+    p.commit().unwrap();
+    p.factory_reset().unwrap();
+    assert_eq!(p.peek_stored().unwrap(), Settings::default());
+  }
+
+  #[test]
+  fn elide_identical_only_notifies_on_change() {
+    let commits = Cell::new(0);
+    let mut p = provider(CommitPolicy::ElideIdentical).on_commit(|_: &Settings| commits.set(commits.get() + 1));
+    p.current_mut();
+    p.commit().unwrap();
+    assert_eq!(commits.get(), 0);
+    assert!(!p.is_dirty());
+
+    p.current_mut().gain = 4;
+    p.commit().unwrap();
+    assert_eq!(commits.get(), 1);
+    assert_eq!(p.peek_stored().unwrap(), Settings { gain: 4 });
+  }
+}
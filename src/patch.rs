@@ -0,0 +1,95 @@
+extern crate alloc;
+
+use core::fmt;
+
+use alloc::vec::Vec;
+use binmarshal::MarshalError;
+
+use crate::{kv::Entries, validate::{Validate, ValidationError}, CommitObserver, ConfigurationMarshal, ConfigurationProvider};
+
+// [key:2][len:2], before each value
+const ENTRY_HEADER_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PatchError<E> {
+  /// The patch was cut short, part way through an entry.
+  Malformed,
+  /// The patch changes a field the configuration doesn't have, under this key.
+  UnknownField(u16),
+  /// A value couldn't be deserialised into its field.
+  Serialisation,
+  /// The patched configuration failed [`Validate::validate`].
+  Invalid(ValidationError),
+  Marshal(E),
+}
+
+impl<E: fmt::Debug> fmt::Display for PatchError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Malformed => f.write_str("the patch was cut short"),
+      Self::UnknownField(key) => write!(f, "the patch changes unknown field {:#06x}", key),
+      Self::Serialisation => f.write_str("a value couldn't be deserialised into its field"),
+      Self::Invalid(e) => write!(f, "{}", e),
+      Self::Marshal(e) => write!(f, "marshal error: {:?}", e),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for PatchError<E> { }
+
+/// Build a patch setting the fields under `keys` to their values in `config`, for
+/// [`ConfigurationProvider::apply_patch`].
+///
+/// A patch is a sequence of entries, each `[key:2][len:2][value:len]`, little-endian. The key is the field's
+/// [`Entries`] key, as given by [`crate::kv::key_for`] for `#[derive(Entries)]`, and the value is the field
+/// serialised with binmarshal, so patches can be built without this crate too.
+pub fn encode_patch<Config: Entries>(config: &Config, keys: &[u16]) -> Result<Vec<u8>, MarshalError> {
+  let mut patch = Vec::new();
+  for key in keys.iter().copied() {
+    let value = config.write_entry(key)?;
+    if value.len() > u16::MAX as usize {
+      return Err(MarshalError::BufferTooSmall);
+    }
+    patch.extend_from_slice(&key.to_le_bytes());
+    patch.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    patch.extend_from_slice(&value);
+  }
+  Ok(patch)
+}
+
+// The key and value of the first entry in `bytes`, and whatever follows it, or `None` if it's cut short
+pub(crate) fn split_entry(bytes: &[u8]) -> Option<(u16, &[u8], &[u8])> {
+  let header = bytes.get(..ENTRY_HEADER_LEN)?;
+  let key = u16::from_le_bytes([header[0], header[1]]);
+  let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+  let value = bytes.get(ENTRY_HEADER_LEN..ENTRY_HEADER_LEN + len)?;
+  Some((key, value, &bytes[ENTRY_HEADER_LEN + len..]))
+}
+
+impl<Config, Marshal, Observer> ConfigurationProvider<Config, Marshal, Observer>
+where
+  Config: Entries + Validate + Clone,
+  Marshal: ConfigurationMarshal<Config>,
+  Observer: CommitObserver<Config>
+{
+  /// Apply a patch built as for [`encode_patch`] to the current configuration, validate it, and commit it, e.g.
+  /// to change a single field over a constrained radio link. If any of that fails, the current configuration
+  /// is left as it was, uncommitted changes and all.
+  pub fn apply_patch(&mut self, patch: &[u8]) -> Result<(), PatchError<Marshal::Error>> {
+    let mut config = self.snapshot();
+    let mut rest = patch;
+    while !rest.is_empty() {
+      let (key, value, next) = split_entry(rest).ok_or(PatchError::Malformed)?;
+      if !Config::KEYS.contains(&key) {
+        return Err(PatchError::UnknownField(key));
+      }
+      config.read_entry(key, value).map_err(|_| PatchError::Serialisation)?;
+      rest = next;
+    }
+
+    config.validate().map_err(PatchError::Invalid)?;
+    self.commit_value(config).map_err(PatchError::Marshal)
+  }
+}
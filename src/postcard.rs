@@ -0,0 +1,88 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::vec;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{store::ByteStore, ConfigurationMarshal};
+
+const MAGIC: [u8; 2] = *b"GR";
+// [magic:2][len:2]
+const HEADER_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PostcardMarshalError<E> {
+  Store(E),
+  Serialisation,
+  Blank
+}
+
+impl<E: fmt::Debug> fmt::Display for PostcardMarshalError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Store(e) => write!(f, "store error: {:?}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Blank => f.write_str("nothing is stored"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for PostcardMarshalError<E> { }
+
+/// Stores the configuration in a [`ByteStore`] using serde and postcard, as an alternative to binmarshal.
+pub struct PostcardMarshal<Config, Backend> {
+  backend: Backend,
+  marker: PhantomData<Config>
+}
+
+impl<Config, Backend> PostcardMarshal<Config, Backend> {
+  pub fn new(backend: Backend) -> Self {
+    Self { backend, marker: PhantomData }
+  }
+
+  pub fn into_inner(self) -> Backend {
+    self.backend
+  }
+}
+
+impl<Config, Backend> ConfigurationMarshal<Config> for PostcardMarshal<Config, Backend>
+where
+  Config: Serialize + DeserializeOwned,
+  Backend: ByteStore
+{
+  type Error = PostcardMarshalError<Backend::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let payload = postcard::to_allocvec(config).map_err(|_| Self::Error::Serialisation)?;
+
+    let mut header = [0u8; HEADER_LEN];
+    header[0..2].copy_from_slice(&MAGIC);
+    header[2..4].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+
+    self.backend.write_at(0, &header).map_err(Self::Error::Store)?;
+    self.backend.write_at(HEADER_LEN, &payload).map_err(Self::Error::Store)?;
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let mut header = [0u8; HEADER_LEN];
+    self.backend.read_at(0, &mut header).map_err(Self::Error::Store)?;
+
+    if header[0..2] != MAGIC {
+      return Err(Self::Error::Blank);
+    }
+
+    let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+    let mut buf = vec![0u8; len];
+    self.backend.read_at(HEADER_LEN, &mut buf).map_err(Self::Error::Store)?;
+
+    postcard::from_bytes(&buf).map_err(|_| Self::Error::Serialisation)
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+}
@@ -0,0 +1,146 @@
+use crate::{ConfigurationMarshal, RecoveryReason};
+
+/// A monotonic millisecond clock, e.g. a timer's tick count.
+pub trait MonotonicClock {
+  fn now_ms(&mut self) -> u64;
+}
+
+impl<F: FnMut() -> u64> MonotonicClock for F {
+  fn now_ms(&mut self) -> u64 {
+    self()
+  }
+}
+
+/// Writes to the inner marshal at most once every `interval_ms`. Writes made sooner than that are held
+/// until the next write, [`Self::poll`] or [`Self::flush`] after the interval, with only the latest kept.
+/// Reads see held writes.
+pub struct RateLimitedMarshal<Inner, Config, Clock> {
+  inner: Inner,
+  clock: Clock,
+  interval_ms: u64,
+  last_write: Option<u64>,
+  pending: Option<Config>,
+}
+
+impl<Inner, Config, Clock> RateLimitedMarshal<Inner, Config, Clock> {
+  pub fn new(inner: Inner, clock: Clock, interval_ms: u64) -> Self {
+    Self { inner, clock, interval_ms, last_write: None, pending: None }
+  }
+
+  /// Whether there's a write waiting to be flushed.
+  pub fn is_pending(&self) -> bool {
+    self.pending.is_some()
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+}
+
+impl<Inner, Config, Clock> RateLimitedMarshal<Inner, Config, Clock>
+where
+  Inner: ConfigurationMarshal<Config>,
+  Clock: MonotonicClock
+{
+  /// Write any held configuration now, regardless of the interval, e.g. before shutting down.
+  pub fn flush(&mut self) -> Result<(), Inner::Error> {
+    match self.pending.take() {
+      Some(config) => self.write_through(config),
+      None => Ok(()),
+    }
+  }
+
+  /// Write any held configuration if the interval has passed. Call this periodically so held writes
+  /// aren't left waiting for the next write.
+  pub fn poll(&mut self) -> Result<(), Inner::Error> {
+    match self.ready() {
+      true => self.flush(),
+      false => Ok(()),
+    }
+  }
+
+  fn ready(&mut self) -> bool {
+    match self.last_write {
+      Some(last) => self.clock.now_ms().saturating_sub(last) >= self.interval_ms,
+      None => true,
+    }
+  }
+
+  fn write_through(&mut self, config: Config) -> Result<(), Inner::Error> {
+    if let Err(e) = self.inner.write(&config) {
+      // Keep it around to try again
+      self.pending = Some(config);
+      return Err(e);
+    }
+    self.last_write = Some(self.clock.now_ms());
+    Ok(())
+  }
+}
+
+impl<Inner, Config, Clock> ConfigurationMarshal<Config> for RateLimitedMarshal<Inner, Config, Clock>
+where
+  Inner: ConfigurationMarshal<Config>,
+  Config: Clone,
+  Clock: MonotonicClock
+{
+  type Error = Inner::Error;
+  const RETRY_HINT: usize = Inner::RETRY_HINT;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    match self.ready() {
+      true => {
+        self.pending = None;
+        self.write_through(config.clone())
+      },
+      false => {
+        self.pending = Some(config.clone());
+        Ok(())
+      },
+    }
+  }
+
+  /// Write straight through to the inner marshal, regardless of the interval, replacing any held write.
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.inner.write_now(config)?;
+    self.pending = None;
+    self.last_write = Some(self.clock.now_ms());
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    match &self.pending {
+      Some(config) => Ok(config.clone()),
+      None => self.inner.read(),
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    Inner::is_blank(error)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    Inner::recovery_reason(error)
+  }
+
+  fn migrated(&self) -> bool {
+    self.pending.is_none() && self.inner.migrated()
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    // Storage is already up to date, so there's nothing left to flush
+    self.pending = None;
+    self.inner.written_externally(config)
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.inner.revision()
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.inner.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.inner.last_modified()
+  }
+}
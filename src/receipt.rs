@@ -0,0 +1,75 @@
+extern crate alloc;
+
+use core::fmt;
+
+use alloc::vec::Vec;
+use binmarshal::rw::{BitWriter, VecBitWriter};
+
+use crate::{crc::crc32, CommitObserver, ConfigurationMarshal, ConfigurationProvider};
+
+/// What a commit wrote: the length and CRC-32 of the serialised configuration, without any framing added by
+/// the marshal. The CRC matches the one the M24C64 marshal stores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CommitReceipt {
+  pub len: usize,
+  pub crc: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReceiptError<E> {
+  Marshal(E),
+  Serialisation,
+  /// The configuration read back doesn't match the receipt.
+  Mismatch,
+}
+
+impl<E: fmt::Debug> fmt::Display for ReceiptError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Marshal(e) => write!(f, "marshal error: {:?}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Mismatch => f.write_str("the configuration read back doesn't match the receipt"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for ReceiptError<E> { }
+
+fn serialise<Config: binmarshal::Marshal<()>, E>(config: &Config) -> Result<Vec<u8>, ReceiptError<E>> {
+  let mut writer = VecBitWriter::new();
+  config.write(&mut writer, ()).map_err(|_| ReceiptError::Serialisation)?;
+  Ok(writer.slice().to_vec())
+}
+
+impl<Config, Marshal, Observer> ConfigurationProvider<Config, Marshal, Observer>
+where
+  Config: binmarshal::Marshal<()> + Clone,
+  Marshal: ConfigurationMarshal<Config>,
+  Observer: CommitObserver<Config>
+{
+  /// Commit the configuration straight away, even if deferred by [`Self::begin`], then read it back to check
+  /// that it landed. Returns a receipt for what was written, which [`Self::verify_receipt`] can check later.
+  pub fn commit_with_receipt(&mut self) -> Result<CommitReceipt, ReceiptError<Marshal::Error>> {
+    let bytes = serialise(&self.volatile)?;
+    self.marshal.write(&self.volatile).map_err(ReceiptError::Marshal)?;
+    self.dirty = false;
+    self.observer.committed(&self.volatile);
+
+    let receipt = CommitReceipt { len: bytes.len(), crc: crc32(&bytes) };
+    self.verify_receipt(&receipt)?;
+    Ok(receipt)
+  }
+
+  /// Read the stored configuration back and check it against `receipt`, without changing the current one.
+  pub fn verify_receipt(&mut self, receipt: &CommitReceipt) -> Result<(), ReceiptError<Marshal::Error>> {
+    let stored = self.marshal.read().map_err(ReceiptError::Marshal)?;
+    let bytes = serialise(&stored)?;
+    match bytes.len() == receipt.len && crc32(&bytes) == receipt.crc {
+      true => Ok(()),
+      false => Err(ReceiptError::Mismatch),
+    }
+  }
+}
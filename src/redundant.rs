@@ -0,0 +1,225 @@
+use core::fmt;
+
+use crate::{ConfigurationMarshal, RecoveryReason};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedundantSource {
+  Primary,
+  Secondary,
+}
+
+/// Returned when both backends of a [`RedundantMarshal`] fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RedundantError<A, B> {
+  pub primary: A,
+  pub secondary: B,
+}
+
+impl<A: fmt::Debug, B: fmt::Debug> fmt::Display for RedundantError<A, B> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "primary error: {:?}, and secondary error: {:?}", self.primary, self.secondary)
+  }
+}
+
+#[cfg(feature = "std")]
+impl<A: fmt::Debug, B: fmt::Debug> std::error::Error for RedundantError<A, B> { }
+
+/// Keeps the same configuration in two marshals. Writes go to both, and reads fall back to the
+/// secondary if the primary fails, repairing the primary with the recovered configuration.
+pub struct RedundantMarshal<A, B> {
+  primary: A,
+  secondary: B,
+  last_source: Option<RedundantSource>,
+  // Whether the primary was repaired from the secondary after the last read fell back
+  repaired: bool,
+}
+
+impl<A, B> RedundantMarshal<A, B> {
+  pub fn new(primary: A, secondary: B) -> Self {
+    Self { primary, secondary, last_source: None, repaired: false }
+  }
+
+  /// Which backend the last successful read came from.
+  pub fn last_source(&self) -> Option<RedundantSource> {
+    self.last_source
+  }
+
+  pub fn primary_mut(&mut self) -> &mut A {
+    &mut self.primary
+  }
+
+  pub fn secondary_mut(&mut self) -> &mut B {
+    &mut self.secondary
+  }
+
+  pub fn into_inner(self) -> (A, B) {
+    (self.primary, self.secondary)
+  }
+}
+
+impl<Config, A, B> ConfigurationMarshal<Config> for RedundantMarshal<A, B>
+where
+  A: ConfigurationMarshal<Config>,
+  B: ConfigurationMarshal<Config>
+{
+  type Error = RedundantError<A::Error, B::Error>;
+  const RETRY_HINT: usize = if A::RETRY_HINT > B::RETRY_HINT { A::RETRY_HINT } else { B::RETRY_HINT };
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    match (self.primary.write(config), self.secondary.write(config)) {
+      (Err(primary), Err(secondary)) => Err(RedundantError { primary, secondary }),
+      _ => Ok(()),
+    }
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    match (self.primary.write_now(config), self.secondary.write_now(config)) {
+      (Err(primary), Err(secondary)) => Err(RedundantError { primary, secondary }),
+      _ => Ok(()),
+    }
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let primary = match self.primary.read() {
+      Ok(c) => {
+        self.last_source = Some(RedundantSource::Primary);
+        return Ok(c);
+      },
+      Err(e) => e,
+    };
+
+    match self.secondary.read() {
+      Ok(c) => {
+        self.last_source = Some(RedundantSource::Secondary);
+        // Best effort - if the repair fails we'll just fall back again next time.
+        self.repaired = self.primary.write(&c).is_ok();
+        Ok(c)
+      },
+      Err(secondary) => {
+        self.last_source = None;
+        Err(RedundantError { primary, secondary })
+      },
+    }
+  }
+
+  /// Like [`Self::read`], but without repairing the primary from the secondary, so a configuration recovered
+  /// from the secondary is reported as needing writing back.
+  fn migrate_dry_run(&mut self) -> Result<(Config, bool), Self::Error> {
+    let primary = match self.primary.migrate_dry_run() {
+      Ok(r) => {
+        self.last_source = Some(RedundantSource::Primary);
+        return Ok(r);
+      },
+      Err(e) => e,
+    };
+
+    match self.secondary.migrate_dry_run() {
+      Ok((c, _)) => {
+        self.last_source = Some(RedundantSource::Secondary);
+        self.repaired = false;
+        Ok((c, true))
+      },
+      Err(secondary) => {
+        self.last_source = None;
+        Err(RedundantError { primary, secondary })
+      },
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    A::is_blank(&error.primary) && B::is_blank(&error.secondary)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    // A blank secondary says nothing about why the primary was abandoned
+    match B::is_blank(&error.secondary) {
+      true => A::recovery_reason(&error.primary),
+      false => B::recovery_reason(&error.secondary),
+    }
+  }
+
+  fn migrated(&self) -> bool {
+    match self.last_source {
+      Some(RedundantSource::Primary) => self.primary.migrated(),
+      // A configuration recovered from the secondary needs writing back, unless it's already repaired the primary
+      Some(RedundantSource::Secondary) => !self.repaired,
+      None => false,
+    }
+  }
+
+  fn revision(&self) -> Option<u32> {
+    match self.last_source {
+      Some(RedundantSource::Secondary) => self.secondary.revision(),
+      _ => self.primary.revision(),
+    }
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.primary.stamp(now);
+    self.secondary.stamp(now);
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    match self.last_source {
+      Some(RedundantSource::Secondary) => self.secondary.last_modified(),
+      _ => self.primary.last_modified(),
+    }
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    self.primary.written_externally(config);
+    self.secondary.written_externally(config);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use binmarshal::{Demarshal, Marshal};
+
+  use crate::{fault::{Fault, FaultInjectingMarshal}, HeaplessMarshal};
+  use super::*;
+
+  #[derive(Debug, Clone, Default, PartialEq, Marshal, Demarshal)]
+  struct Settings {
+    gain: u32,
+  }
+
+  type Primary = FaultInjectingMarshal<HeaplessMarshal<Settings, 8>>;
+
+  fn marshal(primary: u32, secondary: u32) -> RedundantMarshal<Primary, HeaplessMarshal<Settings, 8>> {
+    let mut a = FaultInjectingMarshal::new(HeaplessMarshal::new());
+    a.write(&Settings { gain: primary }).unwrap();
+    let mut b = HeaplessMarshal::new();
+    b.write(&Settings { gain: secondary }).unwrap();
+    RedundantMarshal::new(a, b)
+  }
+
+  #[test]
+  fn failed_primary_is_repaired_from_the_secondary() {
+    let mut m = marshal(1, 2);
+    m.primary_mut().fail_reads(1, Fault::Corrupt);
+    assert_eq!(m.read().unwrap(), Settings { gain: 2 });
+    assert_eq!(m.last_source(), Some(RedundantSource::Secondary));
+    assert!(!ConfigurationMarshal::<Settings>::migrated(&m));
+    assert_eq!(m.primary_mut().inner_mut().read().unwrap(), Settings { gain: 2 });
+  }
+
+  #[test]
+  fn failed_repair_is_reported_as_migrated() {
+    let mut m = marshal(1, 2);
+    m.primary_mut().fail_reads(1, Fault::Corrupt);
+    m.primary_mut().fail_write(1);
+    assert_eq!(m.read().unwrap(), Settings { gain: 2 });
+    assert!(ConfigurationMarshal::<Settings>::migrated(&m));
+  }
+
+  #[test]
+  fn dry_run_leaves_the_primary_alone() {
+    let mut m = marshal(1, 2);
+    m.primary_mut().fail_reads(1, Fault::Blank);
+    assert_eq!(m.migrate_dry_run().unwrap(), (Settings { gain: 2 }, true));
+    assert_eq!(m.primary_mut().writes(), 1);
+    assert_eq!(m.primary_mut().inner_mut().read().unwrap(), Settings { gain: 1 });
+  }
+}
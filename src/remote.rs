@@ -0,0 +1,125 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::vec::Vec;
+use binmarshal::{rw::{BitView, BitWriter, VecBitWriter}, DemarshalOwned, Marshal};
+
+use crate::{crc::crc32, ConfigurationMarshal, RecoveryReason};
+
+const CRC_LEN: usize = 4;
+
+/// What a [`RemoteMarshal`] asks of its [`Transport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Op {
+  /// Fetch the stored image. No bytes are sent, and an empty response means nothing is stored.
+  Read,
+  /// Store the image sent. The response is ignored.
+  Write,
+}
+
+/// Carries requests from a [`RemoteMarshal`] to wherever the configuration is kept, e.g. a cloud backend.
+pub trait Transport {
+  type Error;
+
+  fn request(&mut self, op: Op, bytes: &[u8]) -> Result<Vec<u8>, Self::Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RemoteError<E> {
+  /// The request didn't make it, e.g. the network is down. Nothing is known about the stored configuration.
+  Transport(E),
+  Serialisation,
+  Blank,
+  /// The image came back, but failed its checksum.
+  Corrupt,
+}
+
+impl<E: fmt::Debug> fmt::Display for RemoteError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Transport(e) => write!(f, "transport error: {:?}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Blank => f.write_str("nothing is stored"),
+      Self::Corrupt => f.write_str("the image failed its checksum"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for RemoteError<E> { }
+
+/// Stores the configuration remotely, over a [`Transport`]. The image sent is the serialised configuration
+/// followed by its CRC32, just as the M24C64 marshal stores it, so that corruption in transit is caught.
+pub struct RemoteMarshal<T, Config> {
+  transport: T,
+  marker: PhantomData<Config>
+}
+
+impl<T, Config> RemoteMarshal<T, Config> {
+  pub fn new(transport: T) -> Self {
+    Self { transport, marker: PhantomData }
+  }
+
+  pub fn transport_mut(&mut self) -> &mut T {
+    &mut self.transport
+  }
+
+  pub fn into_inner(self) -> T {
+    self.transport
+  }
+}
+
+impl<T, Config> ConfigurationMarshal<Config> for RemoteMarshal<T, Config>
+where
+  T: Transport,
+  Config: Marshal<()> + DemarshalOwned
+{
+  type Error = RemoteError<T::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let mut writer = VecBitWriter::new();
+    config.write(&mut writer, ()).map_err(|_| Self::Error::Serialisation)?;
+
+    let mut image = writer.slice().to_vec();
+    let crc = crc32(&image);
+    image.extend_from_slice(&crc.to_le_bytes());
+
+    self.transport.request(Op::Write, &image).map_err(Self::Error::Transport)?;
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let image = self.transport.request(Op::Read, &[]).map_err(Self::Error::Transport)?;
+    if image.is_empty() {
+      return Err(Self::Error::Blank);
+    }
+    if image.len() < CRC_LEN {
+      return Err(Self::Error::Corrupt);
+    }
+
+    let (payload, crc) = image.split_at(image.len() - CRC_LEN);
+    if crc32(payload).to_le_bytes() != crc {
+      return Err(Self::Error::Corrupt);
+    }
+
+    match Config::read(&mut BitView::new(payload), ()) {
+      Ok(c) => Ok(c),
+      Err(_) => Err(Self::Error::Serialisation),
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      Self::Error::Blank => RecoveryReason::Blank,
+      Self::Error::Corrupt | Self::Error::Serialisation => RecoveryReason::Corrupt,
+      Self::Error::Transport(_) => RecoveryReason::Unreadable,
+    }
+  }
+}
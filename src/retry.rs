@@ -0,0 +1,196 @@
+use embedded_hal::blocking::delay::DelayMs;
+#[cfg(feature = "async")]
+use embedded_hal_async::delay::DelayNs;
+
+#[cfg(feature = "async")]
+use crate::asynch::AsyncConfigurationMarshal;
+use crate::{ConfigurationMarshal, RecoveryReason};
+
+/// Retries failed reads and writes on the inner marshal, waiting `backoff_ms` between attempts.
+/// Only errors accepted by the `retryable` predicate are retried; anything else, or the error from the
+/// final attempt, is returned as-is.
+pub struct RetryMarshal<Inner, Delay, Retryable> {
+  inner: Inner,
+  retries: usize,
+  delay: Delay,
+  backoff_ms: u16,
+  exponential: bool,
+  retryable: Retryable,
+}
+
+impl<Inner, Delay, Retryable> RetryMarshal<Inner, Delay, Retryable> {
+  pub fn new(inner: Inner, retries: usize, delay: Delay, backoff_ms: u16, retryable: Retryable) -> Self {
+    Self { inner, retries, delay, backoff_ms, exponential: false, retryable }
+  }
+
+  /// Double the wait after each failed attempt, starting from `backoff_ms`.
+  pub fn with_exponential_backoff(mut self, exponential: bool) -> Self {
+    self.exponential = exponential;
+    self
+  }
+
+  pub fn inner_mut(&mut self) -> &mut Inner {
+    &mut self.inner
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+
+  fn retry<T, E>(&mut self, mut f: impl FnMut(&mut Inner) -> Result<T, E>) -> Result<T, E>
+  where
+    Delay: DelayMs<u16>,
+    Retryable: FnMut(&E) -> bool
+  {
+    let mut backoff = Backoff::default();
+    loop {
+      match f(&mut self.inner) {
+        Err(e) => match self.backoff(&mut backoff, &e) {
+          Some(wait) => self.delay.delay_ms(wait),
+          None => return Err(e),
+        },
+        result => return result,
+      }
+    }
+  }
+
+  // How long to wait before retrying after `error`, if it should be retried at all. Shared with the async impl.
+  fn backoff<E>(&mut self, backoff: &mut Backoff, error: &E) -> Option<u16>
+  where
+    Retryable: FnMut(&E) -> bool
+  {
+    if backoff.attempt >= self.retries || !(self.retryable)(error) {
+      return None;
+    }
+
+    let wait = match backoff.attempt {
+      0 => self.backoff_ms,
+      _ if self.exponential => backoff.wait.saturating_mul(2),
+      _ => backoff.wait,
+    };
+    backoff.attempt += 1;
+    backoff.wait = wait;
+    Some(wait)
+  }
+}
+
+#[derive(Default)]
+struct Backoff {
+  attempt: usize,
+  wait: u16,
+}
+
+impl<Config, Inner, Delay, Retryable> ConfigurationMarshal<Config> for RetryMarshal<Inner, Delay, Retryable>
+where
+  Inner: ConfigurationMarshal<Config>,
+  Delay: DelayMs<u16>,
+  Retryable: FnMut(&Inner::Error) -> bool
+{
+  type Error = Inner::Error;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.retry(|inner| inner.write(config))
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    // There's no time to back off when power is about to be lost
+    self.inner.write_now(config)
+  }
+
+  fn write_serialised(&mut self, config: &Config, bytes: &[u8]) -> Result<(), Self::Error> {
+    self.retry(|inner| inner.write_serialised(config, bytes))
+  }
+
+  fn write_changed(&mut self, config: &Config) -> Result<bool, Self::Error> {
+    self.retry(|inner| inner.write_changed(config))
+  }
+
+  fn capacity(&self) -> usize {
+    self.inner.capacity()
+  }
+
+  fn free(&mut self) -> Result<usize, Self::Error> {
+    self.retry(|inner| inner.free())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.retry(|inner| inner.read())
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    Inner::is_blank(error)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    Inner::recovery_reason(error)
+  }
+
+  fn migrated(&self) -> bool {
+    self.inner.migrated()
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.inner.revision()
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.inner.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.inner.last_modified()
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    self.inner.written_externally(config)
+  }
+}
+
+/// Backs off with an async delay, e.g. `embassy_time::Delay`, rather than blocking.
+#[cfg(feature = "async")]
+impl<Config, Inner, Delay, Retryable> AsyncConfigurationMarshal<Config> for RetryMarshal<Inner, Delay, Retryable>
+where
+  Inner: AsyncConfigurationMarshal<Config>,
+  Delay: DelayNs,
+  Retryable: FnMut(&Inner::Error) -> bool
+{
+  type Error = Inner::Error;
+
+  async fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let mut backoff = Backoff::default();
+    loop {
+      match self.inner.write(config).await {
+        Err(e) => match self.backoff(&mut backoff, &e) {
+          Some(wait) => self.delay.delay_ms(wait as u32).await,
+          None => return Err(e),
+        },
+        result => return result,
+      }
+    }
+  }
+
+  async fn read(&mut self) -> Result<Config, Self::Error> {
+    let mut backoff = Backoff::default();
+    loop {
+      match self.inner.read().await {
+        Err(e) => match self.backoff(&mut backoff, &e) {
+          Some(wait) => self.delay.delay_ms(wait as u32).await,
+          None => return Err(e),
+        },
+        result => return result,
+      }
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    Inner::is_blank(error)
+  }
+
+  fn migrated(&self) -> bool {
+    self.inner.migrated()
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.inner.revision()
+  }
+}
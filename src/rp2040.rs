@@ -0,0 +1,149 @@
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use embedded_storage::nor_flash::{check_erase, check_read, check_write, ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash};
+
+use crate::nor_flash::NorFlashConfigurationMarshal;
+
+/// The RP2040's flash erase granularity, in bytes.
+pub const SECTOR_SIZE: usize = 4096;
+/// The RP2040's flash program granularity, in bytes.
+pub const PAGE_SIZE: usize = 256;
+
+const XIP_BASE: usize = 0x1000_0000;
+// Erases a single 4K sector
+const SECTOR_ERASE_CMD: u8 = 0x20;
+
+/// Stores the configuration at `offset` into the RP2040's boot flash. See [`Rp2040Flash`].
+pub type Rp2040ConfigurationMarshal<Config, const FLASH_SIZE: usize> = NorFlashConfigurationMarshal<Config, Rp2040Flash<FLASH_SIZE>>;
+
+// The boot ROM's flash routines. See section 2.8.3 of the RP2040 datasheet.
+struct Rom {
+  connect_internal_flash: unsafe extern "C" fn(),
+  flash_exit_xip: unsafe extern "C" fn(),
+  flash_range_erase: unsafe extern "C" fn(u32, usize, u32, u8),
+  flash_range_program: unsafe extern "C" fn(u32, *const u8, usize),
+  flash_flush_cache: unsafe extern "C" fn(),
+}
+
+impl Rom {
+  unsafe fn lookup() -> Self {
+    // The ROM holds 16 bit pointers to its function table and lookup function
+    let table = core::ptr::read_volatile(0x0000_0014 as *const u16) as usize as *const u16;
+    let lookup = core::ptr::read_volatile(0x0000_0018 as *const u16) as usize;
+    let lookup: unsafe extern "C" fn(*const u16, u32) -> usize = core::mem::transmute(lookup);
+    let find = |code: &[u8; 2]| lookup(table, u16::from_le_bytes(*code) as u32);
+
+    Self {
+      connect_internal_flash: core::mem::transmute::<usize, unsafe extern "C" fn()>(find(b"IF")),
+      flash_exit_xip: core::mem::transmute::<usize, unsafe extern "C" fn()>(find(b"EX")),
+      flash_range_erase: core::mem::transmute::<usize, unsafe extern "C" fn(u32, usize, u32, u8)>(find(b"RE")),
+      flash_range_program: core::mem::transmute::<usize, unsafe extern "C" fn(u32, *const u8, usize)>(find(b"RP")),
+      flash_flush_cache: core::mem::transmute::<usize, unsafe extern "C" fn()>(find(b"FC")),
+    }
+  }
+}
+
+// Everything from here until XIP is restored has to run from RAM, as flash can't be read in the meantime.
+
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn erase_from_ram(rom: &Rom, boot2: &[u32; 64], offset: u32, len: usize) {
+  compiler_fence(Ordering::SeqCst);
+  (rom.connect_internal_flash)();
+  (rom.flash_exit_xip)();
+  (rom.flash_range_erase)(offset, len, SECTOR_SIZE as u32, SECTOR_ERASE_CMD);
+  (rom.flash_flush_cache)();
+  enter_xip(boot2);
+}
+
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn program_from_ram(rom: &Rom, boot2: &[u32; 64], offset: u32, data: *const u8, len: usize) {
+  compiler_fence(Ordering::SeqCst);
+  (rom.connect_internal_flash)();
+  (rom.flash_exit_xip)();
+  (rom.flash_range_program)(offset, data, len);
+  (rom.flash_flush_cache)();
+  enter_xip(boot2);
+}
+
+// Restore fast XIP by re-running the copy of boot2 taken at startup
+#[inline(always)]
+unsafe fn enter_xip(boot2: &[u32; 64]) {
+  let boot2: unsafe extern "C" fn() = core::mem::transmute((boot2.as_ptr() as usize) | 1);
+  boot2();
+  compiler_fence(Ordering::SeqCst);
+}
+
+// Run `f` with interrupts disabled, so that no handler runs from flash while XIP is off
+fn without_interrupts<T>(f: impl FnOnce() -> T) -> T {
+  let primask: u32;
+  unsafe {
+    core::arch::asm!("mrs {}, PRIMASK", out(reg) primask);
+    core::arch::asm!("cpsid i");
+  }
+  let result = f();
+  // Only re-enable interrupts if they were enabled to begin with
+  if primask & 1 == 0 {
+    unsafe { core::arch::asm!("cpsie i") };
+  }
+  result
+}
+
+/// The RP2040's boot flash, `FLASH_SIZE` bytes of it, as [`NorFlash`], erased and programmed through the boot
+/// ROM with XIP and interrupts disabled. Offsets are from the start of flash, so keep the configuration in a
+/// sector clear of the firmware, e.g. the last one. Bytes written must be in RAM, not flash.
+///
+/// The RP2350's boot ROM works differently, and isn't supported.
+pub struct Rp2040Flash<const FLASH_SIZE: usize> {
+  rom: Rom,
+  // boot2, copied out of flash so it can restore XIP after each operation
+  boot2: [u32; 64],
+}
+
+impl<const FLASH_SIZE: usize> Rp2040Flash<FLASH_SIZE> {
+  /// # Safety
+  /// Nothing else may execute from or access flash while this erases or programs it, including the other
+  /// core, which must be parked in RAM or idle. Only one `Rp2040Flash` should exist.
+  pub unsafe fn new() -> Self {
+    let mut boot2 = [0u32; 64];
+    core::ptr::copy_nonoverlapping(XIP_BASE as *const u32, boot2.as_mut_ptr(), boot2.len());
+    Self { rom: Rom::lookup(), boot2 }
+  }
+}
+
+impl<const FLASH_SIZE: usize> ErrorType for Rp2040Flash<FLASH_SIZE> {
+  type Error = NorFlashErrorKind;
+}
+
+impl<const FLASH_SIZE: usize> ReadNorFlash for Rp2040Flash<FLASH_SIZE> {
+  const READ_SIZE: usize = 1;
+
+  fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+    check_read(self, offset, bytes.len())?;
+    // Flash is memory mapped through XIP
+    unsafe { core::ptr::copy_nonoverlapping((XIP_BASE + offset as usize) as *const u8, bytes.as_mut_ptr(), bytes.len()) };
+    Ok(())
+  }
+
+  fn capacity(&self) -> usize {
+    FLASH_SIZE
+  }
+}
+
+impl<const FLASH_SIZE: usize> NorFlash for Rp2040Flash<FLASH_SIZE> {
+  const WRITE_SIZE: usize = PAGE_SIZE;
+  const ERASE_SIZE: usize = SECTOR_SIZE;
+
+  fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+    check_erase(self, from, to)?;
+    without_interrupts(|| unsafe { erase_from_ram(&self.rom, &self.boot2, from, (to - from) as usize) });
+    Ok(())
+  }
+
+  fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+    check_write(self, offset, bytes.len())?;
+    without_interrupts(|| unsafe { program_from_ram(&self.rom, &self.boot2, offset, bytes.as_ptr(), bytes.len()) });
+    Ok(())
+  }
+}
@@ -0,0 +1,132 @@
+extern crate alloc;
+
+use core::marker::PhantomData;
+
+use alloc::{vec, vec::Vec};
+use binmarshal::{rw::{BitView, BitWriter}, Marshal, MarshalError};
+use chacha20::{cipher::{KeyIvInit, StreamCipher}, ChaCha20};
+
+use crate::{crc::crc32, encrypted::{apply, next_nonce, EncryptedError}, store::ByteStore, wipe::{serialise, Scratch}, ConfigurationMarshal};
+
+// [public len:2][secret len:2], after the nonce
+const LENS_LEN: usize = 4;
+const CRC_LEN: usize = 4;
+
+/// A configuration split into public and secret fields, so that [`SecretFieldsMarshal`] only encrypts the
+/// secret ones. Derive it with `#[derive(Secrets)]`, marking secret fields `#[config(secret)]`.
+pub trait Secrets: Sized {
+  fn write_public<W: BitWriter>(&self, writer: &mut W) -> Result<(), MarshalError>;
+  fn write_secrets<W: BitWriter>(&self, writer: &mut W) -> Result<(), MarshalError>;
+  fn read_split<'dm>(public: &mut BitView<'dm>, secrets: &mut BitView<'dm>) -> Result<Self, MarshalError>;
+}
+
+// Serialises just one half of a configuration, so that it can go through `serialise` like any other
+struct Public<'a, Config>(&'a Config);
+struct Secret<'a, Config>(&'a Config);
+
+impl<Config: Secrets> Marshal<()> for Public<'_, Config> {
+  fn write<W: BitWriter>(&self, writer: &mut W, _ctx: ()) -> Result<(), MarshalError> {
+    self.0.write_public(writer)
+  }
+}
+
+impl<Config: Secrets> Marshal<()> for Secret<'_, Config> {
+  fn write<W: BitWriter>(&self, writer: &mut W, _ctx: ()) -> Result<(), MarshalError> {
+    self.0.write_secrets(writer)
+  }
+}
+
+/// Like [`crate::encrypted::EncryptedMarshal`], but only encrypting the fields marked `#[config(secret)]`,
+/// leaving the rest in the clear for debugging tools to inspect.
+///
+/// The stored image is `[nonce][public len:2][secret len:2][public fields + CRC32][encrypted secret fields +
+/// CRC32]`, little-endian, with the nonce a counter as for [`crate::encrypted::EncryptedMarshal`].
+pub struct SecretFieldsMarshal<Inner, Config, Cipher = ChaCha20> {
+  inner: Inner,
+  key: [u8; 32],
+  nonce: Option<Vec<u8>>,
+  marker: PhantomData<(Config, Cipher)>
+}
+
+impl<Inner, Config, Cipher> SecretFieldsMarshal<Inner, Config, Cipher> {
+  pub fn new(inner: Inner, key: [u8; 32]) -> Self {
+    Self { inner, key, nonce: None, marker: PhantomData }
+  }
+
+  pub fn into_inner(self) -> Inner {
+    self.inner
+  }
+}
+
+impl<Inner, Config, Cipher> ConfigurationMarshal<Config> for SecretFieldsMarshal<Inner, Config, Cipher>
+where
+  Inner: ByteStore,
+  Config: Secrets,
+  Cipher: KeyIvInit + StreamCipher
+{
+  type Error = EncryptedError<Inner::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let public = serialise(&Public(config), ()).map_err(|_| Self::Error::Serialisation)?;
+    let secrets = serialise(&Secret(config), ()).map_err(|_| Self::Error::Serialisation)?;
+    if public.len() > u16::MAX as usize || secrets.len() > u16::MAX as usize {
+      return Err(Self::Error::Serialisation);
+    }
+
+    let nonce = next_nonce::<Cipher, _>(&mut self.inner, self.nonce.take())?;
+    let mut sealed = Scratch(Vec::with_capacity(secrets.len() + CRC_LEN));
+    sealed.extend_from_slice(&secrets);
+    sealed.extend_from_slice(&crc32(&secrets).to_le_bytes());
+    apply::<Cipher, _>(&self.key, &nonce, &mut sealed)?;
+
+    let mut image = Vec::with_capacity(nonce.len() + LENS_LEN + public.len() + CRC_LEN + sealed.len());
+    image.extend_from_slice(&nonce);
+    image.extend_from_slice(&(public.len() as u16).to_le_bytes());
+    image.extend_from_slice(&(secrets.len() as u16).to_le_bytes());
+    image.extend_from_slice(&public);
+    image.extend_from_slice(&crc32(&public).to_le_bytes());
+    image.extend_from_slice(&sealed);
+
+    self.inner.write_at(0, &image).map_err(Self::Error::Store)?;
+    self.nonce = Some(nonce);
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let nonce_len = Cipher::iv_size();
+    let mut header = vec![0u8; nonce_len + LENS_LEN];
+    self.inner.read_at(0, &mut header).map_err(Self::Error::Store)?;
+
+    // Erased EEPROM and flash read back as 0xFF
+    if header.iter().all(|&b| b == 0xFF) {
+      return Err(Self::Error::Blank);
+    }
+
+    let public_len = u16::from_le_bytes([header[nonce_len], header[nonce_len + 1]]) as usize;
+    let secret_len = u16::from_le_bytes([header[nonce_len + 2], header[nonce_len + 3]]) as usize;
+    if header.len() + public_len + secret_len + 2 * CRC_LEN > self.inner.capacity() {
+      return Err(Self::Error::Decrypt);
+    }
+
+    let mut body = Scratch(vec![0u8; public_len + secret_len + 2 * CRC_LEN]);
+    self.inner.read_at(header.len(), &mut body).map_err(Self::Error::Store)?;
+    let (public, sealed) = body.split_at_mut(public_len + CRC_LEN);
+    apply::<Cipher, _>(&self.key, &header[..nonce_len], sealed)?;
+
+    let (public, public_crc) = public.split_at(public_len);
+    let (secrets, secrets_crc) = sealed.split_at(secret_len);
+    if crc32(public).to_le_bytes() != public_crc || crc32(secrets).to_le_bytes() != secrets_crc {
+      return Err(Self::Error::Decrypt);
+    }
+    self.nonce = Some(header[..nonce_len].to_vec());
+
+    match Config::read_split(&mut BitView::new(public), &mut BitView::new(secrets)) {
+      Ok(c) => Ok(c),
+      Err(_) => Err(Self::Error::Serialisation),
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+}
@@ -0,0 +1,233 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData, ops::Range};
+
+use alloc::vec::Vec;
+
+use binmarshal::{rw::{BitView, BitWriter, BufferBitWriter}, DemarshalOwned, Marshal, MarshalError};
+use embedded_storage::nor_flash::NorFlash;
+use sequential_storage::map::{fetch_item, store_item, MapError, StorageItem, StorageItemError};
+
+use crate::{kv::KvStore, ConfigurationMarshal, RecoveryReason};
+
+// The configuration is the only item in the map
+const KEY: u8 = 0;
+// [key:1][len:2]. The key also keeps the item from ever being all 0xFF, which sequential-storage forbids.
+const HEADER_LEN: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SeqStorageError<E> {
+  Flash(E),
+  Serialisation,
+  /// The configuration is larger than sequential-storage's maximum item size.
+  TooLarge,
+  /// There's no room left in the flash range, even after sequential-storage has reclaimed what it can.
+  Full,
+  /// sequential-storage found the flash range to be corrupt. Erasing the range recovers it.
+  Corrupted,
+  Blank,
+}
+
+impl<E: fmt::Debug> fmt::Display for SeqStorageError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Flash(e) => write!(f, "flash error: {:?}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::TooLarge => f.write_str("the configuration is larger than the maximum item size"),
+      Self::Full => f.write_str("there's no room left in the flash range"),
+      Self::Corrupted => f.write_str("the flash range is corrupt"),
+      Self::Blank => f.write_str("nothing is stored"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for SeqStorageError<E> { }
+
+fn map_error<E>(e: MapError<ItemError, E>) -> SeqStorageError<E> {
+  match e {
+    MapError::Storage(e) => SeqStorageError::Flash(e),
+    MapError::FullStorage => SeqStorageError::Full,
+    MapError::Corrupted => SeqStorageError::Corrupted,
+    MapError::Item(ItemError::BufferTooSmall) | MapError::BufferTooSmall | MapError::BufferTooBig => SeqStorageError::TooLarge,
+    _ => SeqStorageError::Serialisation,
+  }
+}
+
+#[derive(Debug)]
+enum ItemError {
+  BufferTooSmall,
+  Serialisation,
+}
+
+impl StorageItemError for ItemError {
+  fn is_buffer_too_small(&self) -> bool {
+    matches!(self, ItemError::BufferTooSmall)
+  }
+}
+
+struct ConfigItem<Config>(Config);
+
+impl<Config> StorageItem for ConfigItem<Config>
+where
+  Config: Marshal<()> + DemarshalOwned
+{
+  type Key = u8;
+  type Error = ItemError;
+
+  fn serialize_into(&self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+    if buffer.len() < HEADER_LEN {
+      return Err(ItemError::BufferTooSmall);
+    }
+    let (header, body) = buffer.split_at_mut(HEADER_LEN);
+
+    let mut writer = BufferBitWriter::new(body);
+    match self.0.write(&mut writer, ()) {
+      Ok(()) => (),
+      Err(MarshalError::BufferTooSmall) => return Err(ItemError::BufferTooSmall),
+      Err(_) => return Err(ItemError::Serialisation),
+    }
+    let len = writer.slice().len();
+
+    header[0] = KEY;
+    header[1..3].copy_from_slice(&(len as u16).to_le_bytes());
+    Ok(HEADER_LEN + len)
+  }
+
+  fn deserialize_from(buffer: &[u8]) -> Result<(Self, usize), Self::Error> {
+    if buffer.len() < HEADER_LEN || buffer[0] != KEY {
+      return Err(ItemError::Serialisation);
+    }
+    let len = u16::from_le_bytes([buffer[1], buffer[2]]) as usize;
+    let payload = buffer.get(HEADER_LEN..HEADER_LEN + len).ok_or(ItemError::BufferTooSmall)?;
+
+    let config = Config::read(&mut BitView::new(payload), ()).map_err(|_| ItemError::Serialisation)?;
+    Ok((Self(config), HEADER_LEN + len))
+  }
+
+  fn key(&self) -> Self::Key {
+    KEY
+  }
+}
+
+/// Stores the configuration as a single item in a [`sequential_storage`] map over `range` of NOR flash, which
+/// takes care of wear-levelling and power-loss safety. `PAGE_SIZE` must be the flash's erase size, and
+/// `range` must be aligned to it and hold at least two pages.
+///
+/// The range is dedicated to the configuration, and can't be shared with other map items.
+pub struct SeqStorageMarshal<Config, Flash, const PAGE_SIZE: usize> {
+  flash: Flash,
+  range: Range<u32>,
+  marker: PhantomData<Config>
+}
+
+impl<Config, Flash, const PAGE_SIZE: usize> SeqStorageMarshal<Config, Flash, PAGE_SIZE> {
+  pub fn new(flash: Flash, range: Range<u32>, marker: PhantomData<Config>) -> Self {
+    Self { flash, range, marker }
+  }
+
+  pub fn into_inner(self) -> Flash {
+    self.flash
+  }
+}
+
+impl<Config, Flash, const PAGE_SIZE: usize> ConfigurationMarshal<Config> for SeqStorageMarshal<Config, Flash, PAGE_SIZE>
+where
+  Config: Marshal<()> + DemarshalOwned + Clone,
+  Flash: NorFlash
+{
+  type Error = SeqStorageError<Flash::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    store_item::<_, _, PAGE_SIZE>(&mut self.flash, self.range.clone(), ConfigItem(config.clone())).map_err(map_error)?;
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    match fetch_item::<ConfigItem<Config>, _>(&mut self.flash, self.range.clone(), KEY).map_err(map_error)? {
+      Some(item) => Ok(item.0),
+      None => Err(Self::Error::Blank),
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      Self::Error::Blank => RecoveryReason::Blank,
+      Self::Error::Corrupted | Self::Error::Serialisation => RecoveryReason::Corrupt,
+      Self::Error::Flash(_) | Self::Error::TooLarge | Self::Error::Full => RecoveryReason::Unreadable,
+    }
+  }
+}
+
+// [key:2][len:2][value]
+const ENTRY_HEADER_LEN: usize = 4;
+
+struct Entry {
+  key: u16,
+  value: Vec<u8>,
+}
+
+impl StorageItem for Entry {
+  type Key = u16;
+  type Error = ItemError;
+
+  fn serialize_into(&self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+    let len = ENTRY_HEADER_LEN + self.value.len();
+    if buffer.len() < len {
+      return Err(ItemError::BufferTooSmall);
+    }
+    buffer[0..2].copy_from_slice(&self.key.to_le_bytes());
+    buffer[2..4].copy_from_slice(&(self.value.len() as u16).to_le_bytes());
+    buffer[ENTRY_HEADER_LEN..len].copy_from_slice(&self.value);
+    Ok(len)
+  }
+
+  fn deserialize_from(buffer: &[u8]) -> Result<(Self, usize), Self::Error> {
+    if buffer.len() < ENTRY_HEADER_LEN {
+      return Err(ItemError::Serialisation);
+    }
+    let key = u16::from_le_bytes([buffer[0], buffer[1]]);
+    let len = u16::from_le_bytes([buffer[2], buffer[3]]) as usize;
+    let value = buffer.get(ENTRY_HEADER_LEN..ENTRY_HEADER_LEN + len).ok_or(ItemError::BufferTooSmall)?;
+    Ok((Self { key, value: value.to_vec() }, ENTRY_HEADER_LEN + len))
+  }
+
+  fn key(&self) -> Self::Key {
+    self.key
+  }
+}
+
+/// A [`KvStore`] over a [`sequential_storage`] map, for a [`crate::KvMarshal`] on NOR flash. As with
+/// [`SeqStorageMarshal`], `PAGE_SIZE` must be the flash's erase size, and `range` is dedicated to it.
+pub struct SeqStorageKv<Flash, const PAGE_SIZE: usize> {
+  flash: Flash,
+  range: Range<u32>,
+}
+
+impl<Flash, const PAGE_SIZE: usize> SeqStorageKv<Flash, PAGE_SIZE> {
+  pub fn new(flash: Flash, range: Range<u32>) -> Self {
+    Self { flash, range }
+  }
+
+  pub fn into_inner(self) -> Flash {
+    self.flash
+  }
+}
+
+impl<Flash: NorFlash, const PAGE_SIZE: usize> KvStore for SeqStorageKv<Flash, PAGE_SIZE> {
+  type Error = SeqStorageError<Flash::Error>;
+
+  fn fetch(&mut self, key: u16) -> Result<Option<Vec<u8>>, Self::Error> {
+    let entry = fetch_item::<Entry, _>(&mut self.flash, self.range.clone(), key).map_err(map_error)?;
+    Ok(entry.map(|entry| entry.value))
+  }
+
+  fn store(&mut self, key: u16, value: &[u8]) -> Result<(), Self::Error> {
+    store_item::<_, _, PAGE_SIZE>(&mut self.flash, self.range.clone(), Entry { key, value: value.to_vec() }).map_err(map_error)
+  }
+}
@@ -0,0 +1,219 @@
+use core::fmt;
+
+use crate::{CommitObserver, ConfigurationMarshal, ConfigurationProvider, GenericConfigurationProvider, RecoveryReason};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ShadowError<P, S> {
+  /// The primary couldn't be written to, or couldn't be read for some reason other than being blank or
+  /// corrupt, e.g. a bus error.
+  Primary(P),
+  /// The shadow couldn't be written to on shutdown.
+  ShadowWrite(S),
+  /// The primary is blank or corrupt, and the shadow couldn't be read either.
+  Shadow { primary: P, shadow: S },
+}
+
+impl<P: fmt::Debug, S: fmt::Debug> fmt::Display for ShadowError<P, S> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Primary(e) => write!(f, "primary error: {:?}", e),
+      Self::ShadowWrite(e) => write!(f, "shadow write error: {:?}", e),
+      Self::Shadow { primary, shadow } => write!(f, "primary error: {:?}, and shadow error: {:?}", primary, shadow),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<P: fmt::Debug, S: fmt::Debug> std::error::Error for ShadowError<P, S> { }
+
+/// Writes to the primary marshal on every commit, but only to the shadow, e.g. a second EEPROM, on a clean
+/// [`ConfigurationProvider::shutdown`], so the shadow always holds a consistent, if slightly stale, copy to
+/// read from when the primary is blank or corrupt. Any other failure to read the primary, e.g. a bus error,
+/// is reported rather than running on the older shadow. Unlike [`crate::LastGoodMarshal`], commits only wear the primary.
+///
+/// The primary is left as it is after falling back, so the next commit is what repairs it.
+pub struct ShadowMarshal<P, S> {
+  primary: P,
+  shadow: S,
+  fell_back: bool,
+}
+
+impl<P, S> ShadowMarshal<P, S> {
+  pub fn new(primary: P, shadow: S) -> Self {
+    Self { primary, shadow, fell_back: false }
+  }
+
+  /// Whether the configuration last read came from the shadow, and hasn't since been written over.
+  pub fn fell_back(&self) -> bool {
+    self.fell_back
+  }
+
+  pub fn primary_mut(&mut self) -> &mut P {
+    &mut self.primary
+  }
+
+  pub fn shadow_mut(&mut self) -> &mut S {
+    &mut self.shadow
+  }
+
+  pub fn into_inner(self) -> (P, S) {
+    (self.primary, self.shadow)
+  }
+
+  /// Write `config` to the shadow alone.
+  pub fn write_shadow<Config>(&mut self, config: &Config) -> Result<(), S::Error>
+  where
+    S: ConfigurationMarshal<Config>
+  {
+    self.shadow.write(config)
+  }
+}
+
+impl<Config, P, S> ConfigurationMarshal<Config> for ShadowMarshal<P, S>
+where
+  P: ConfigurationMarshal<Config>,
+  S: ConfigurationMarshal<Config>
+{
+  type Error = ShadowError<P::Error, S::Error>;
+  const RETRY_HINT: usize = P::RETRY_HINT;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.primary.write(config).map_err(ShadowError::Primary)?;
+    self.fell_back = false;
+    Ok(())
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    self.primary.write_now(config).map_err(ShadowError::Primary)?;
+    self.fell_back = false;
+    Ok(())
+  }
+
+  fn write_changed(&mut self, config: &Config) -> Result<bool, Self::Error> {
+    let changed = self.primary.write_changed(config).map_err(ShadowError::Primary)?;
+    self.fell_back = false;
+    Ok(changed)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.fell_back = false;
+
+    let primary = match self.primary.read() {
+      Ok(c) => return Ok(c),
+      Err(e) if P::is_blank(&e) || P::recovery_reason(&e).is_corruption() => e,
+      Err(e) => return Err(ShadowError::Primary(e)),
+    };
+
+    match self.shadow.read() {
+      Ok(c) => {
+        self.fell_back = true;
+        Ok(c)
+      },
+      Err(shadow) => Err(ShadowError::Shadow { primary, shadow }),
+    }
+  }
+
+  fn capacity(&self) -> usize {
+    self.primary.capacity().min(self.shadow.capacity())
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    match error {
+      ShadowError::Primary(e) => P::is_blank(e),
+      ShadowError::ShadowWrite(_) => false,
+      ShadowError::Shadow { primary, shadow } => P::is_blank(primary) && S::is_blank(shadow),
+    }
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      ShadowError::Primary(e) => P::recovery_reason(e),
+      ShadowError::ShadowWrite(_) => RecoveryReason::Unreadable,
+      // A blank shadow says nothing about why the primary was abandoned
+      ShadowError::Shadow { primary, shadow } if S::is_blank(shadow) => P::recovery_reason(primary),
+      ShadowError::Shadow { shadow, .. } => S::recovery_reason(shadow),
+    }
+  }
+
+  fn migrated(&self) -> bool {
+    !self.fell_back && self.primary.migrated()
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.primary.revision()
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.primary.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.primary.last_modified()
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    self.primary.written_externally(config)
+  }
+}
+
+impl<Config, P, S, Observer> ConfigurationProvider<Config, ShadowMarshal<P, S>, Observer>
+where
+  P: ConfigurationMarshal<Config>,
+  S: ConfigurationMarshal<Config>,
+  Observer: CommitObserver<Config>
+{
+  /// Commit any uncommitted changes to the primary, then copy the configuration to the shadow, e.g. as part
+  /// of a clean power down.
+  pub fn shutdown(&mut self) -> Result<(), ShadowError<P::Error, S::Error>> {
+    if self.is_dirty() {
+      self.commit()?;
+    }
+    self.marshal.write_shadow(&self.volatile).map_err(ShadowError::ShadowWrite)
+  }
+
+  /// Whether the current configuration was recovered from the shadow because the primary was blank or corrupt,
+  /// e.g. to warn the user that changes since the last clean shutdown may have been lost.
+  pub fn current_is_shadow(&self) -> bool {
+    self.marshal.fell_back()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use binmarshal::{Demarshal, Marshal};
+
+  use crate::{fault::{Fault, FaultError, FaultInjectingMarshal}, HeaplessMarshal};
+  use super::*;
+
+  #[derive(Debug, Clone, Default, PartialEq, Marshal, Demarshal)]
+  struct Settings {
+    gain: u32,
+  }
+
+  type Primary = FaultInjectingMarshal<HeaplessMarshal<Settings, 8>>;
+
+  fn marshal(primary: u32, shadow: u32) -> ShadowMarshal<Primary, HeaplessMarshal<Settings, 8>> {
+    let mut m = ShadowMarshal::new(FaultInjectingMarshal::new(HeaplessMarshal::new()), HeaplessMarshal::new());
+    m.write_shadow(&Settings { gain: shadow }).unwrap();
+    m.primary_mut().write(&Settings { gain: primary }).unwrap();
+    m
+  }
+
+  #[test]
+  fn corrupt_primary_falls_back() {
+    let mut m = marshal(1, 2);
+    m.primary_mut().fail_reads(1, Fault::Corrupt);
+    assert_eq!(m.read().unwrap(), Settings { gain: 2 });
+    assert!(m.fell_back());
+  }
+
+  #[test]
+  fn transient_primary_error_is_returned_without_touching_the_primary() {
+    let mut m = marshal(1, 2);
+    m.primary_mut().fail_reads(1, Fault::Transient);
+    assert_eq!(m.read(), Err(ShadowError::Primary(FaultError::Injected(Fault::Transient))));
+    assert!(!m.fell_back());
+    assert_eq!(m.read().unwrap(), Settings { gain: 1 });
+  }
+}
@@ -0,0 +1,75 @@
+use core::{cell::RefCell, marker::PhantomData};
+
+use crate::GenericConfigurationProvider;
+
+/// Exclusive access to a value shared between tasks, e.g. a [`RefCell`] for tasks on a single executor, or a
+/// `std::sync::Mutex`. For an embassy-sync `blocking_mutex::Mutex<M, RefCell<T>>`, implement this with its
+/// `lock`, borrowing the `RefCell` mutably inside.
+pub trait SharedCell<T> {
+  fn with<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R;
+}
+
+impl<T> SharedCell<T> for RefCell<T> {
+  fn with<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+    f(&mut self.borrow_mut())
+  }
+}
+
+#[cfg(feature = "std")]
+impl<T> SharedCell<T> for std::sync::Mutex<T> {
+  fn with<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+    // A panic mid-update leaves the provider no worse off than any other failed commit
+    f(&mut self.lock().unwrap_or_else(std::sync::PoisonError::into_inner))
+  }
+}
+
+/// Shares a provider between many readers and the occasional writer, through a [`SharedCell`], so that every
+/// method takes `&self`. Each read or update holds the cell for its whole duration, so readers see the
+/// configuration either before or after an update, never part way through, e.g.
+///
+/// `SharedConfigurationProvider::new(RefCell::new(provider))`
+///
+/// Calling back into the shared provider from within [`Self::read`] or [`Self::update`] will deadlock, or for
+/// a [`RefCell`], panic.
+pub struct SharedConfigurationProvider<Config, Provider, Cell = RefCell<Provider>> {
+  cell: Cell,
+  marker: PhantomData<(Config, Provider)>
+}
+
+impl<Config, Provider, Cell> SharedConfigurationProvider<Config, Provider, Cell>
+where
+  Config: Clone,
+  Provider: GenericConfigurationProvider<Config>,
+  Cell: SharedCell<Provider>
+{
+  pub const fn new(cell: Cell) -> Self {
+    Self { cell, marker: PhantomData }
+  }
+
+  /// A copy of the current configuration, uncommitted changes and all, to hold on to without blocking anyone.
+  pub fn snapshot(&self) -> Config {
+    self.cell.with(|provider| provider.current().clone())
+  }
+
+  /// Look at the current configuration without copying it, holding the cell until `f` returns.
+  pub fn read<R, F: FnOnce(&Config) -> R>(&self, f: F) -> R {
+    self.cell.with(|provider| f(provider.current()))
+  }
+
+  /// Use the provider exclusively, e.g. to change and commit the configuration in one go.
+  pub fn update<R, F: FnOnce(&mut Provider) -> R>(&self, f: F) -> R {
+    self.cell.with(f)
+  }
+
+  /// Change the configuration with `f`, and commit it.
+  pub fn commit_with<F: FnOnce(&mut Config)>(&self, f: F) -> Result<(), Provider::Error> {
+    self.cell.with(|provider| {
+      f(provider.current_mut());
+      provider.commit()
+    })
+  }
+
+  pub fn into_inner(self) -> Cell {
+    self.cell
+  }
+}
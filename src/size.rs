@@ -0,0 +1,39 @@
+/// An upper bound on a configuration's serialised length, so that one that may have outgrown its storage
+/// can be caught at build time with [`crate::assert_fits`]. `#[derive(MaxSize)]`, with the `derive` feature,
+/// adds up the bounds of a struct's fields. Types without a bound, such as `Vec`, don't implement it.
+pub trait MaxSize {
+  const MAX_SERIALISED_LEN: usize;
+}
+
+macro_rules! max_size {
+  ($($t:ty => $len:expr),* $(,)?) => {
+    $(impl MaxSize for $t {
+      const MAX_SERIALISED_LEN: usize = $len;
+    })*
+  };
+}
+
+max_size! {
+  bool => 1,
+  u8 => 1, i8 => 1,
+  u16 => 2, i16 => 2,
+  u32 => 4, i32 => 4, f32 => 4,
+  u64 => 8, i64 => 8, f64 => 8,
+  u128 => 16, i128 => 16,
+}
+
+impl<T: MaxSize, const N: usize> MaxSize for [T; N] {
+  const MAX_SERIALISED_LEN: usize = T::MAX_SERIALISED_LEN * N;
+}
+
+/// Fail the build if `$config`'s [`MaxSize::MAX_SERIALISED_LEN`] is more than `$capacity` bytes, e.g.
+/// `assert_fits!(MyConfig, M24C64_CAPACITY - M24C64_OVERHEAD);`.
+#[macro_export]
+macro_rules! assert_fits {
+  ($config:ty, $capacity:expr) => {
+    const _: () = assert!(
+      <$config as $crate::size::MaxSize>::MAX_SERIALISED_LEN <= $capacity,
+      "the configuration may not fit in its storage"
+    );
+  };
+}
@@ -0,0 +1,72 @@
+use core::{fmt, marker::PhantomData};
+
+use binmarshal::{rw::{BitView, BufferBitWriter}, DemarshalOwned, Marshal, MarshalError};
+
+use crate::ConfigurationMarshal;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SliceMarshalError {
+  BufferTooSmall,
+  Serialisation,
+}
+
+impl fmt::Display for SliceMarshalError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::BufferTooSmall => f.write_str("the buffer is too small for the configuration"),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SliceMarshalError { }
+
+impl From<MarshalError> for SliceMarshalError {
+  fn from(e: MarshalError) -> Self {
+    match e {
+      MarshalError::BufferTooSmall => Self::BufferTooSmall,
+      _ => Self::Serialisation,
+    }
+  }
+}
+
+/// Persists the configuration into a borrowed byte buffer, e.g. a `.noinit` RAM section that survives
+/// a soft reset.
+pub struct SliceMarshal<'a, Config> {
+  buffer: &'a mut [u8],
+  marker: PhantomData<Config>
+}
+
+impl<'a, Config> SliceMarshal<'a, Config> {
+  pub fn new(buffer: &'a mut [u8]) -> Self {
+    Self { buffer, marker: PhantomData }
+  }
+
+  pub fn into_inner(self) -> &'a mut [u8] {
+    self.buffer
+  }
+}
+
+impl<'a, Config> ConfigurationMarshal<Config> for SliceMarshal<'a, Config>
+where
+  Config: Marshal<()> + DemarshalOwned
+{
+  type Error = SliceMarshalError;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let mut writer = BufferBitWriter::new(self.buffer);
+    config.write(&mut writer, ())?;
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    Ok(Config::read(&mut BitView::new(self.buffer), ())?)
+  }
+
+  fn is_blank(_error: &Self::Error) -> bool {
+    // There's no framing to tell an uninitialised buffer from a damaged one, and nothing in RAM worth keeping
+    true
+  }
+}
@@ -0,0 +1,178 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::vec;
+use binmarshal::{rw::{BitView, BitWriter, VecBitWriter}, DemarshalOwned, Marshal};
+use embedded_hal::{blocking::spi, digital::v2::OutputPin};
+
+use crate::ConfigurationMarshal;
+
+const MAGIC: [u8; 2] = *b"GR";
+// [magic:2][len:2]
+const HEADER_LEN: usize = 4;
+
+const CMD_READ: u8 = 0x03;
+const CMD_PAGE_PROGRAM: u8 = 0x02;
+const CMD_SECTOR_ERASE: u8 = 0x20;
+const CMD_WRITE_ENABLE: u8 = 0x06;
+const CMD_READ_STATUS: u8 = 0x05;
+const STATUS_BUSY: u8 = 0x01;
+
+const PAGE_SIZE: usize = 256;
+const SECTOR_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SpiFlashError<E> {
+  Spi(E),
+  /// The chip select pin couldn't be driven.
+  ChipSelect,
+  Serialisation,
+  Blank
+}
+
+impl<E: fmt::Debug> fmt::Display for SpiFlashError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Spi(e) => write!(f, "SPI error: {:?}", e),
+      Self::ChipSelect => f.write_str("the chip select pin couldn't be driven"),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Blank => f.write_str("nothing is stored"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for SpiFlashError<E> { }
+
+/// Stores the configuration in a W25Q-style SPI NOR flash, starting at `offset`. `offset` must be aligned
+/// to the 4KiB sector size, and the sectors covering the configuration are erased on every write.
+pub struct SpiFlashMarshal<Config, SPI, CS> {
+  spi: SPI,
+  cs: CS,
+  offset: u32,
+  marker: PhantomData<Config>
+}
+
+impl<Config, SPI, CS> SpiFlashMarshal<Config, SPI, CS> {
+  pub fn new(spi: SPI, cs: CS, offset: u32, marker: PhantomData<Config>) -> Self {
+    Self { spi, cs, offset, marker }
+  }
+
+  pub fn into_inner(self) -> (SPI, CS) {
+    (self.spi, self.cs)
+  }
+}
+
+impl<Config, SPI, CS, E> SpiFlashMarshal<Config, SPI, CS>
+where
+  SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+  CS: OutputPin
+{
+  // Run `f` with chip select asserted, releasing it again even if `f` fails
+  fn transaction<T>(&mut self, f: impl FnOnce(&mut SPI) -> Result<T, E>) -> Result<T, SpiFlashError<E>> {
+    self.cs.set_low().map_err(|_| SpiFlashError::ChipSelect)?;
+    let result = f(&mut self.spi);
+    self.cs.set_high().map_err(|_| SpiFlashError::ChipSelect)?;
+    result.map_err(SpiFlashError::Spi)
+  }
+
+  fn command(opcode: u8, address: u32) -> [u8; 4] {
+    let a = address.to_be_bytes();
+    [opcode, a[1], a[2], a[3]]
+  }
+
+  fn wait_until_ready(&mut self) -> Result<(), SpiFlashError<E>> {
+    loop {
+      let status = self.transaction(|spi| {
+        let mut buf = [CMD_READ_STATUS, 0];
+        spi.transfer(&mut buf)?;
+        Ok(buf[1])
+      })?;
+      if status & STATUS_BUSY == 0 {
+        return Ok(());
+      }
+    }
+  }
+
+  fn write_enable(&mut self) -> Result<(), SpiFlashError<E>> {
+    self.transaction(|spi| spi.write(&[CMD_WRITE_ENABLE]))
+  }
+
+  fn read_flash(&mut self, address: u32, buf: &mut [u8]) -> Result<(), SpiFlashError<E>> {
+    self.transaction(|spi| {
+      spi.write(&Self::command(CMD_READ, address))?;
+      spi.transfer(buf)?;
+      Ok(())
+    })
+  }
+
+  fn erase_sector(&mut self, address: u32) -> Result<(), SpiFlashError<E>> {
+    self.write_enable()?;
+    self.transaction(|spi| spi.write(&Self::command(CMD_SECTOR_ERASE, address)))?;
+    self.wait_until_ready()
+  }
+
+  fn program_page(&mut self, address: u32, data: &[u8]) -> Result<(), SpiFlashError<E>> {
+    self.write_enable()?;
+    self.transaction(|spi| {
+      spi.write(&Self::command(CMD_PAGE_PROGRAM, address))?;
+      spi.write(data)
+    })?;
+    self.wait_until_ready()
+  }
+}
+
+impl<Config, SPI, CS, E> ConfigurationMarshal<Config> for SpiFlashMarshal<Config, SPI, CS>
+where
+  Config: Marshal<()> + DemarshalOwned,
+  SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+  CS: OutputPin
+{
+  type Error = SpiFlashError<E>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let mut writer = VecBitWriter::new();
+    if config.write(&mut writer, ()).is_err() {
+      return Err(Self::Error::Serialisation);
+    }
+    let payload = writer.slice();
+
+    let mut image = vec![0u8; HEADER_LEN + payload.len()];
+    image[0..2].copy_from_slice(&MAGIC);
+    image[2..4].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+    image[HEADER_LEN..].copy_from_slice(payload);
+
+    for sector in 0..image.len().div_ceil(SECTOR_SIZE) {
+      self.erase_sector(self.offset + (sector * SECTOR_SIZE) as u32)?;
+    }
+    // `offset` is sector aligned, so it's page aligned too
+    for (i, page) in image.chunks(PAGE_SIZE).enumerate() {
+      self.program_page(self.offset + (i * PAGE_SIZE) as u32, page)?;
+    }
+    Ok(())
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let mut header = [0u8; HEADER_LEN];
+    self.read_flash(self.offset, &mut header)?;
+
+    if header[0..2] != MAGIC {
+      return Err(Self::Error::Blank);
+    }
+
+    let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+    let mut buf = vec![0u8; len];
+    self.read_flash(self.offset + HEADER_LEN as u32, &mut buf)?;
+
+    match Config::read(&mut BitView::new(&buf), ()) {
+      Ok(c) => Ok(c),
+      Err(_) => Err(Self::Error::Serialisation),
+    }
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+}
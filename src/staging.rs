@@ -0,0 +1,66 @@
+use crate::GenericConfigurationProvider;
+
+/// Keeps a staging copy of the configuration for edits, separate from the active one the rest of the system
+/// runs on, e.g. for a UI with a preview before Apply. Edits through [`Self::staging_mut`] only take effect
+/// once they're [`Self::apply`]d.
+pub struct StagingProvider<Config, Provider> {
+  provider: Provider,
+  staging: Config,
+}
+
+impl<Config, Provider> StagingProvider<Config, Provider>
+where
+  Config: Clone,
+  Provider: GenericConfigurationProvider<Config>
+{
+  pub fn new(provider: Provider) -> Self {
+    let staging = provider.current().clone();
+    Self { provider, staging }
+  }
+
+  /// The active configuration.
+  pub fn current(&self) -> &Config {
+    self.provider.current()
+  }
+
+  /// The staging configuration, with any edits not yet applied.
+  pub fn staging(&self) -> &Config {
+    &self.staging
+  }
+
+  pub fn staging_mut(&mut self) -> &mut Config {
+    &mut self.staging
+  }
+
+  /// Make the staging configuration active and commit it. If the commit fails, the previous configuration
+  /// stays active and the edits stay staged.
+  pub fn apply(&mut self) -> Result<(), Provider::Error> {
+    let previous = core::mem::replace(self.provider.current_mut(), self.staging.clone());
+    match self.provider.commit() {
+      Ok(()) => Ok(()),
+      Err(e) => {
+        *self.provider.current_mut() = previous;
+        Err(e)
+      },
+    }
+  }
+
+  /// Discard the staged edits, going back to the active configuration.
+  pub fn revert(&mut self) {
+    self.staging = self.provider.current().clone();
+  }
+
+  pub fn provider(&self) -> &Provider {
+    &self.provider
+  }
+
+  /// Changes made through the provider aren't staged. Call [`Self::revert`] to pick them up.
+  pub fn provider_mut(&mut self) -> &mut Provider {
+    &mut self.provider
+  }
+
+  /// Drop the staged edits, returning the wrapped provider.
+  pub fn into_inner(self) -> Provider {
+    self.provider
+  }
+}
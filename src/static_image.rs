@@ -0,0 +1,84 @@
+use core::fmt;
+
+use binmarshal::{DemarshalOwned, Marshal};
+
+use crate::{crc::{Checksum, Crc32}, framed::{FramedError, FramedMarshal}, store::{ByteStore, OutOfBounds}, ConfigurationMarshal};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StaticError {
+  /// The image is baked into firmware, and can't be written to.
+  ReadOnly,
+  Framed(FramedError<OutOfBounds>),
+}
+
+impl fmt::Display for StaticError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::ReadOnly => f.write_str("the image is baked into firmware, and can't be written to"),
+      Self::Framed(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StaticError { }
+
+struct StaticStore(&'static [u8]);
+
+impl ByteStore for StaticStore {
+  type Error = OutOfBounds;
+
+  fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Self::Error> {
+    let src = self.0.get(offset..offset + buf.len()).ok_or(OutOfBounds)?;
+    buf.copy_from_slice(src);
+    Ok(())
+  }
+
+  fn write_at(&mut self, _offset: usize, _bytes: &[u8]) -> Result<(), Self::Error> {
+    Err(OutOfBounds)
+  }
+
+  fn capacity(&self) -> usize {
+    self.0.len()
+  }
+}
+
+/// Reads a fixed configuration from an image baked into firmware, e.g. with `include_bytes!`, for devices
+/// whose configuration should never change. Writes are refused with [`StaticError::ReadOnly`].
+///
+/// The image is framed as a [`FramedMarshal`] with the same checksum would frame it, over a store the
+/// size of the image.
+pub struct StaticMarshal<Config, C = Crc32> {
+  inner: FramedMarshal<StaticStore, Config, C>,
+}
+
+impl<Config, C> StaticMarshal<Config, C> {
+  pub fn new(image: &'static [u8]) -> Self {
+    Self { inner: FramedMarshal::new(StaticStore(image)) }
+  }
+}
+
+impl<Config, C> ConfigurationMarshal<Config> for StaticMarshal<Config, C>
+where
+  Config: Marshal<()> + DemarshalOwned,
+  C: Checksum
+{
+  type Error = StaticError;
+
+  fn write(&mut self, _config: &Config) -> Result<(), Self::Error> {
+    Err(Self::Error::ReadOnly)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.inner.read().map_err(Self::Error::Framed)
+  }
+
+  fn capacity(&self) -> usize {
+    0
+  }
+
+  fn free(&mut self) -> Result<usize, Self::Error> {
+    Ok(0)
+  }
+}
@@ -0,0 +1,52 @@
+use crate::store::ByteStore;
+
+/// Counters for estimating storage wear. These count since the marshal or store was created, and aren't
+/// persisted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MarshalStats {
+  pub writes: u32,
+  pub bytes_written: u64,
+  pub reads: u32,
+}
+
+/// Counts the reads and writes made through a [`ByteStore`], for marshals that don't keep
+/// [`MarshalStats`] themselves.
+pub struct StatsStore<Store> {
+  store: Store,
+  stats: MarshalStats,
+}
+
+impl<Store> StatsStore<Store> {
+  pub fn new(store: Store) -> Self {
+    Self { store, stats: MarshalStats::default() }
+  }
+
+  pub fn stats(&self) -> MarshalStats {
+    self.stats
+  }
+
+  pub fn into_inner(self) -> Store {
+    self.store
+  }
+}
+
+impl<Store: ByteStore> ByteStore for StatsStore<Store> {
+  type Error = Store::Error;
+
+  fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Self::Error> {
+    self.stats.reads = self.stats.reads.wrapping_add(1);
+    self.store.read_at(offset, buf)
+  }
+
+  fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Self::Error> {
+    self.store.write_at(offset, bytes)?;
+    self.stats.writes = self.stats.writes.wrapping_add(1);
+    self.stats.bytes_written += bytes.len() as u64;
+    Ok(())
+  }
+
+  fn capacity(&self) -> usize {
+    self.store.capacity()
+  }
+}
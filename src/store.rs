@@ -0,0 +1,57 @@
+use core::fmt;
+
+/// Raw, offset-addressed byte storage that marshals can build their on-storage format on top of.
+pub trait ByteStore {
+  type Error;
+
+  fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Self::Error>;
+  fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Self::Error>;
+  fn capacity(&self) -> usize;
+}
+
+/// Copy `len` raw bytes from `from` to `to`, e.g. to back a configuration up into a reserved region before
+/// a risky migration. The ranges must not overlap.
+pub fn copy_within<Store: ByteStore>(store: &mut Store, from: usize, to: usize, len: usize) -> Result<(), Store::Error> {
+  let mut chunk = [0u8; 32];
+  let mut copied = 0;
+  while copied < len {
+    let n = chunk.len().min(len - copied);
+    store.read_at(from + copied, &mut chunk[..n])?;
+    store.write_at(to + copied, &chunk[..n])?;
+    copied += n;
+  }
+  Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OutOfBounds;
+
+impl fmt::Display for OutOfBounds {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("the access runs past the end of the store")
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OutOfBounds { }
+
+impl ByteStore for &mut [u8] {
+  type Error = OutOfBounds;
+
+  fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Self::Error> {
+    let src = self.get(offset..offset + buf.len()).ok_or(OutOfBounds)?;
+    buf.copy_from_slice(src);
+    Ok(())
+  }
+
+  fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Self::Error> {
+    let dst = self.get_mut(offset..offset + bytes.len()).ok_or(OutOfBounds)?;
+    dst.copy_from_slice(bytes);
+    Ok(())
+  }
+
+  fn capacity(&self) -> usize {
+    self.len()
+  }
+}
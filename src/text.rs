@@ -0,0 +1,50 @@
+use core::fmt;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::validate::{Validate, ValidationError};
+
+#[derive(Debug)]
+pub enum TextError {
+  /// The text couldn't be parsed into a configuration, with the parser's description of why.
+  Parse(String),
+  Serialisation,
+  Invalid(ValidationError),
+}
+
+impl fmt::Display for TextError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Parse(e) => write!(f, "couldn't parse the configuration: {}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Invalid(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl std::error::Error for TextError { }
+
+/// Export the configuration as human-editable TOML, e.g. for a support engineer to tweak and
+/// [`import_toml`] back.
+pub fn export_toml<Config: Serialize>(config: &Config) -> Result<String, TextError> {
+  toml::to_string_pretty(config).map_err(|_| TextError::Serialisation)
+}
+
+/// Parse a configuration from TOML, rejecting it if it fails [`Validate::validate`].
+pub fn import_toml<Config: DeserializeOwned + Validate>(text: &str) -> Result<Config, TextError> {
+  let config: Config = toml::from_str(text).map_err(|e| TextError::Parse(e.to_string()))?;
+  config.validate().map_err(TextError::Invalid)?;
+  Ok(config)
+}
+
+/// As with [`export_toml`], but as JSON.
+pub fn export_json<Config: Serialize>(config: &Config) -> Result<String, TextError> {
+  serde_json::to_string_pretty(config).map_err(|_| TextError::Serialisation)
+}
+
+/// As with [`import_toml`], but from JSON.
+pub fn import_json<Config: DeserializeOwned + Validate>(text: &str) -> Result<Config, TextError> {
+  let config: Config = serde_json::from_str(text).map_err(|e| TextError::Parse(e.to_string()))?;
+  config.validate().map_err(TextError::Invalid)?;
+  Ok(config)
+}
@@ -0,0 +1,175 @@
+extern crate alloc;
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::{vec, vec::Vec};
+use binmarshal::{rw::BitView, DemarshalOwned};
+
+use crate::{crc::crc32, kv::Entries, patch::{encode_patch, split_entry}, store::ByteStore, ConfigurationMarshal, RecoveryReason};
+
+const MAGIC: [u8; 2] = *b"GT";
+// [magic:2][len:2]
+const HEADER_LEN: usize = 4;
+const CRC_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TlvError<E> {
+  Store(E),
+  Serialisation,
+  Blank,
+  Corrupt
+}
+
+impl<E: fmt::Debug> fmt::Display for TlvError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Store(e) => write!(f, "store error: {:?}", e),
+      Self::Serialisation => f.write_str("the configuration couldn't be serialised or deserialised"),
+      Self::Blank => f.write_str("nothing is stored"),
+      Self::Corrupt => f.write_str("the stored configuration is corrupt"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for TlvError<E> { }
+
+/// A stored field, from [`TlvMarshal::dump`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlvField {
+  pub key: u16,
+  pub value: Vec<u8>,
+}
+
+/// Stores an [`Entries`] configuration as a tagged sequence of fields, so that a hex dump can be picked apart
+/// without the struct definition, and fields can be added or removed without a migration. Fields that aren't
+/// stored are filled from `Config::default()`, and stored fields the configuration no longer has are skipped.
+///
+/// The stored image is `[b"GT"][len:2][entries][CRC32]`, little-endian, where each entry is
+/// `[key:2][len:2][value]`, the same as a patch from [`crate::encode_patch`], and the CRC covers the entries.
+/// Tags are the fields' [`Entries`] keys, which `#[derive(Entries)]` hashes from their names unless pinned
+/// with `#[config(key = ...)]`.
+pub struct TlvMarshal<Store, Config> {
+  store: Store,
+  marker: PhantomData<Config>
+}
+
+impl<Store, Config> TlvMarshal<Store, Config> {
+  pub fn new(store: Store) -> Self {
+    Self { store, marker: PhantomData }
+  }
+
+  pub fn store_mut(&mut self) -> &mut Store {
+    &mut self.store
+  }
+
+  pub fn into_inner(self) -> Store {
+    self.store
+  }
+}
+
+impl<Store: ByteStore, Config> TlvMarshal<Store, Config> {
+  // The checked entries of the stored image
+  fn read_entries(&mut self) -> Result<Vec<u8>, TlvError<Store::Error>> {
+    let mut header = [0u8; HEADER_LEN];
+    self.store.read_at(0, &mut header).map_err(TlvError::Store)?;
+    if header[0..2] != MAGIC {
+      return Err(TlvError::Blank);
+    }
+
+    let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+    if HEADER_LEN + len + CRC_LEN > self.store.capacity() {
+      return Err(TlvError::Corrupt);
+    }
+
+    let mut buf = vec![0u8; len + CRC_LEN];
+    self.store.read_at(HEADER_LEN, &mut buf).map_err(TlvError::Store)?;
+    let crc = buf.split_off(len);
+    if crc32(&buf).to_le_bytes()[..] != crc[..] {
+      return Err(TlvError::Corrupt);
+    }
+    Ok(buf)
+  }
+
+  /// Every stored field, by key, in the order they're stored, including those the configuration doesn't know
+  /// about, e.g. for a diagnostic dump.
+  pub fn dump(&mut self) -> Result<Vec<TlvField>, TlvError<Store::Error>> {
+    let entries = self.read_entries()?;
+    let mut fields = Vec::new();
+    let mut rest = &entries[..];
+    while !rest.is_empty() {
+      let (key, value, next) = split_entry(rest).ok_or(TlvError::Serialisation)?;
+      fields.push(TlvField { key, value: value.to_vec() });
+      rest = next;
+    }
+    Ok(fields)
+  }
+
+  /// Deserialise just the field stored under `key`, without the rest of the configuration, e.g. to check a
+  /// safe mode flag early in boot, even if the rest was written by newer firmware. `None` if it isn't stored.
+  pub fn read_field<T: DemarshalOwned>(&mut self, key: u16) -> Result<Option<T>, TlvError<Store::Error>> {
+    let entries = self.read_entries()?;
+    let mut rest = &entries[..];
+    while !rest.is_empty() {
+      let (stored, value, next) = split_entry(rest).ok_or(TlvError::Serialisation)?;
+      if stored == key {
+        return T::read(&mut BitView::new(value), ()).map(Some).map_err(|_| TlvError::Serialisation);
+      }
+      rest = next;
+    }
+    Ok(None)
+  }
+}
+
+impl<Store, Config> ConfigurationMarshal<Config> for TlvMarshal<Store, Config>
+where
+  Store: ByteStore,
+  Config: Entries + Default
+{
+  type Error = TlvError<Store::Error>;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    let entries = encode_patch(config, Config::KEYS).map_err(|_| Self::Error::Serialisation)?;
+    if entries.len() > u16::MAX as usize {
+      return Err(Self::Error::Serialisation);
+    }
+
+    let mut image = vec![0u8; HEADER_LEN + entries.len() + CRC_LEN];
+    image[0..2].copy_from_slice(&MAGIC);
+    image[2..HEADER_LEN].copy_from_slice(&(entries.len() as u16).to_le_bytes());
+    image[HEADER_LEN..HEADER_LEN + entries.len()].copy_from_slice(&entries);
+    image[HEADER_LEN + entries.len()..].copy_from_slice(&crc32(&entries).to_le_bytes());
+
+    self.store.write_at(0, &image).map_err(Self::Error::Store)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    let entries = self.read_entries()?;
+    let mut config = Config::default();
+    let mut rest = &entries[..];
+    while !rest.is_empty() {
+      // The CRC passed, so a malformed entry was written that way
+      let (key, value, next) = split_entry(rest).ok_or(Self::Error::Serialisation)?;
+      config.read_entry(key, value).map_err(|_| Self::Error::Serialisation)?;
+      rest = next;
+    }
+    Ok(config)
+  }
+
+  fn capacity(&self) -> usize {
+    self.store.capacity().saturating_sub(HEADER_LEN + CRC_LEN)
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    matches!(error, Self::Error::Blank)
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      Self::Error::Blank => RecoveryReason::Blank,
+      Self::Error::Corrupt | Self::Error::Serialisation => RecoveryReason::Corrupt,
+      Self::Error::Store(_) => RecoveryReason::Unreadable,
+    }
+  }
+}
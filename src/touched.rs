@@ -0,0 +1,155 @@
+use core::{fmt, ops::Deref};
+
+use binmarshal::{rw::{BitView, BitWriter}, Demarshal, Marshal, MarshalError};
+
+use crate::{kv::Entries, size::MaxSize, CommitObserver, ConfigurationMarshal, ConfigurationProvider, Migrate, MigrationError};
+
+// [touched:8], before the configuration
+const TOUCHED_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResetError<E> {
+  /// A default value couldn't be copied into its field.
+  Serialisation,
+  Marshal(E),
+}
+
+impl<E: fmt::Debug> fmt::Display for ResetError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Serialisation => f.write_str("a default value couldn't be copied into its field"),
+      Self::Marshal(e) => write!(f, "marshal error: {:?}", e),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for ResetError<E> { }
+
+/// A configuration along with which of its fields have been explicitly set, persisted together, so that
+/// [`Self::reset_untouched`] can tell a field that's default because it was never changed from one the user set
+/// to its default value. Use it as the provider's configuration, e.g.
+/// `ConfigurationProvider<Touched<MyConfig>, _>`.
+///
+/// Fields are identified by their [`Entries`] key, and tracked by their position in [`Entries::KEYS`], so at
+/// most 64 fields can be tracked, and reordering `KEYS` mixes them up. Changes made through [`Self::set_field`]
+/// are tracked, while reading goes through [`Deref`].
+///
+/// The stored layout is `[touched:8][config]`. Migrations from older versions see the configuration's bytes
+/// only, and keep the stored bitmap.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Touched<Config> {
+  config: Config,
+  touched: u64,
+}
+
+impl<Config: Entries> Touched<Config> {
+  /// Start tracking `config`, with no fields set.
+  pub fn new(config: Config) -> Self {
+    Self { config, touched: 0 }
+  }
+
+  fn bit(key: u16) -> Option<u64> {
+    const { assert!(Config::KEYS.len() <= 64, "Touched can track at most 64 fields") };
+    Config::KEYS.iter().position(|k| *k == key).map(|i| 1 << i)
+  }
+
+  /// Change the configuration with `f`, marking the field under `key` as set. Returns `false`, without calling
+  /// `f`, if the configuration has no field under `key`.
+  pub fn set_field<F: FnOnce(&mut Config)>(&mut self, key: u16, f: F) -> bool {
+    match Self::bit(key) {
+      Some(bit) => {
+        f(&mut self.config);
+        self.touched |= bit;
+        true
+      },
+      None => false,
+    }
+  }
+
+  /// Whether the field under `key` has been set. Unknown keys never have been.
+  pub fn is_set(&self, key: u16) -> bool {
+    Self::bit(key).is_some_and(|bit| self.touched & bit != 0)
+  }
+
+  /// Forget that the field under `key` was set, leaving its value as it is.
+  pub fn unmark(&mut self, key: u16) {
+    if let Some(bit) = Self::bit(key) {
+      self.touched &= !bit;
+    }
+  }
+
+  /// Forget every field that was set, e.g. after a factory reset.
+  pub fn unmark_all(&mut self) {
+    self.touched = 0;
+  }
+
+  pub fn into_inner(self) -> Config {
+    self.config
+  }
+}
+
+impl<Config: Entries + Default> Touched<Config> {
+  /// Restore the default value of every field that hasn't been set, leaving the ones that have.
+  pub fn reset_untouched(&mut self) -> Result<(), MarshalError> {
+    let defaults = Config::default();
+    for key in Config::KEYS.iter().copied() {
+      if !self.is_set(key) {
+        self.config.read_entry(key, &defaults.write_entry(key)?)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl<Config> Deref for Touched<Config> {
+  type Target = Config;
+
+  fn deref(&self) -> &Config {
+    &self.config
+  }
+}
+
+impl<Config: Marshal<()>> Marshal<()> for Touched<Config> {
+  fn write<W: BitWriter>(&self, writer: &mut W, ctx: ()) -> Result<(), MarshalError> {
+    self.touched.write(writer, ())?;
+    self.config.write(writer, ctx)
+  }
+}
+
+impl<'dm, Config: Demarshal<'dm, ()>> Demarshal<'dm, ()> for Touched<Config> {
+  fn read(view: &mut BitView<'dm>, ctx: ()) -> Result<Self, MarshalError> {
+    let touched = u64::read(view, ())?;
+    Ok(Self { config: Config::read(view, ctx)?, touched })
+  }
+}
+
+impl<Config: Migrate> Migrate for Touched<Config> {
+  const VERSION: u8 = Config::VERSION;
+  const SCHEMA: u32 = Config::SCHEMA;
+
+  fn migrate(version: u8, bytes: &[u8]) -> Result<Self, MigrationError> {
+    let (touched, rest) = bytes.split_at_checked(TOUCHED_LEN).ok_or(MigrationError::Serialisation)?;
+    let touched = u64::from_be_bytes(touched.try_into().map_err(|_| MigrationError::Serialisation)?);
+    Ok(Self { config: Config::migrate(version, rest)?, touched })
+  }
+}
+
+impl<Config: MaxSize> MaxSize for Touched<Config> {
+  const MAX_SERIALISED_LEN: usize = TOUCHED_LEN + Config::MAX_SERIALISED_LEN;
+}
+
+impl<Config, Marshal, Observer> ConfigurationProvider<Touched<Config>, Marshal, Observer>
+where
+  Config: Entries + Default + Clone,
+  Marshal: ConfigurationMarshal<Touched<Config>>,
+  Observer: CommitObserver<Touched<Config>>
+{
+  /// Restore the default value of every field the user hasn't set, and commit the result.
+  pub fn reset_untouched(&mut self) -> Result<(), ResetError<Marshal::Error>> {
+    let mut config = self.snapshot();
+    config.reset_untouched().map_err(|_| ResetError::Serialisation)?;
+    self.commit_value(config).map_err(ResetError::Marshal)
+  }
+}
@@ -0,0 +1,138 @@
+use core::fmt;
+
+use crate::{ConfigurationMarshal, RecoveryReason};
+
+/// Why a configuration failed [`Validate::validate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ValidationError(pub &'static str);
+
+impl fmt::Display for ValidationError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "invalid configuration: {}", self.0)
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ValidationError { }
+
+/// A configuration with invariants that must hold before it's persisted. Values that can simply be repaired,
+/// e.g. by clamping, can be with [`crate::Normalize`] before they're validated.
+pub trait Validate {
+  fn validate(&self) -> Result<(), ValidationError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ValidatedError<E> {
+  Marshal(E),
+  Invalid(ValidationError),
+}
+
+impl<E: fmt::Debug> fmt::Display for ValidatedError<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Marshal(e) => write!(f, "marshal error: {:?}", e),
+      Self::Invalid(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for ValidatedError<E> { }
+
+/// Runs [`Validate::validate`] before every write, refusing to persist an invalid configuration.
+/// Wrap a provider's marshal in this to have its commits rejected when validation fails; configurations
+/// that don't implement [`Validate`] can use the inner marshal directly.
+pub struct ValidatedMarshal<Marshal> {
+  inner: Marshal,
+}
+
+impl<Marshal> ValidatedMarshal<Marshal> {
+  pub fn new(inner: Marshal) -> Self {
+    Self { inner }
+  }
+
+  pub fn inner_mut(&mut self) -> &mut Marshal {
+    &mut self.inner
+  }
+
+  pub fn into_inner(self) -> Marshal {
+    self.inner
+  }
+}
+
+impl<Config, Marshal> ConfigurationMarshal<Config> for ValidatedMarshal<Marshal>
+where
+  Config: Validate,
+  Marshal: ConfigurationMarshal<Config>
+{
+  type Error = ValidatedError<Marshal::Error>;
+  const RETRY_HINT: usize = Marshal::RETRY_HINT;
+
+  fn write(&mut self, config: &Config) -> Result<(), Self::Error> {
+    config.validate().map_err(Self::Error::Invalid)?;
+    self.inner.write(config).map_err(Self::Error::Marshal)
+  }
+
+  fn write_now(&mut self, config: &Config) -> Result<(), Self::Error> {
+    config.validate().map_err(Self::Error::Invalid)?;
+    self.inner.write_now(config).map_err(Self::Error::Marshal)
+  }
+
+  fn write_serialised(&mut self, config: &Config, bytes: &[u8]) -> Result<(), Self::Error> {
+    config.validate().map_err(Self::Error::Invalid)?;
+    self.inner.write_serialised(config, bytes).map_err(Self::Error::Marshal)
+  }
+
+  fn write_changed(&mut self, config: &Config) -> Result<bool, Self::Error> {
+    config.validate().map_err(Self::Error::Invalid)?;
+    self.inner.write_changed(config).map_err(Self::Error::Marshal)
+  }
+
+  fn capacity(&self) -> usize {
+    self.inner.capacity()
+  }
+
+  fn free(&mut self) -> Result<usize, Self::Error> {
+    self.inner.free().map_err(Self::Error::Marshal)
+  }
+
+  fn read(&mut self) -> Result<Config, Self::Error> {
+    self.inner.read().map_err(Self::Error::Marshal)
+  }
+
+  fn is_blank(error: &Self::Error) -> bool {
+    match error {
+      Self::Error::Marshal(e) => Marshal::is_blank(e),
+      Self::Error::Invalid(_) => false,
+    }
+  }
+
+  fn recovery_reason(error: &Self::Error) -> RecoveryReason {
+    match error {
+      Self::Error::Marshal(e) => Marshal::recovery_reason(e),
+      Self::Error::Invalid(_) => RecoveryReason::Invalid,
+    }
+  }
+
+  fn migrated(&self) -> bool {
+    self.inner.migrated()
+  }
+
+  fn revision(&self) -> Option<u32> {
+    self.inner.revision()
+  }
+
+  fn stamp(&mut self, now: u64) {
+    self.inner.stamp(now)
+  }
+
+  fn last_modified(&self) -> Option<u64> {
+    self.inner.last_modified()
+  }
+
+  fn written_externally(&mut self, config: &Config) {
+    self.inner.written_externally(config)
+  }
+}
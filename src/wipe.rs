@@ -0,0 +1,87 @@
+extern crate alloc;
+
+use core::ops::{Deref, DerefMut};
+
+use alloc::vec::Vec;
+use binmarshal::{Marshal, MarshalError};
+
+/// A buffer that may hold a serialised configuration. With the `zeroize` feature, it's wiped before it's freed.
+pub(crate) struct Scratch<B: Wipe = Vec<u8>>(pub B);
+
+/// Buffers a [`Scratch`] can hold, and how to wipe them.
+pub(crate) trait Wipe {
+  fn wipe(&mut self);
+}
+
+impl Wipe for Vec<u8> {
+  // Truncating leaves old bytes in the spare capacity, so that's wiped too
+  fn wipe(&mut self) {
+    #[cfg(feature = "zeroize")]
+    {
+      zeroize::Zeroize::zeroize(self.as_mut_slice());
+      zeroize::Zeroize::zeroize(self.spare_capacity_mut());
+    }
+  }
+}
+
+impl<const N: usize> Wipe for [u8; N] {
+  fn wipe(&mut self) {
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(self.as_mut_slice());
+  }
+}
+
+impl<B: Wipe> Deref for Scratch<B> {
+  type Target = B;
+
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+impl<B: Wipe> DerefMut for Scratch<B> {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    &mut self.0
+  }
+}
+
+impl<B: Wipe> Drop for Scratch<B> {
+  fn drop(&mut self) {
+    self.0.wipe();
+  }
+}
+
+/// Serialise `config` into a [`Scratch`] buffer.
+pub(crate) fn serialise<Config: Marshal<Ctx>, Ctx: Clone>(config: &Config, ctx: Ctx) -> Result<Scratch, MarshalError> {
+  let mut buf = Scratch(Vec::new());
+  serialise_into(config, ctx, &mut buf)?;
+  Ok(buf)
+}
+
+/// Serialise `config` into `buf`, replacing its contents but keeping its allocation where it's big enough.
+pub(crate) fn serialise_into<Config: Marshal<Ctx>, Ctx: Clone>(config: &Config, ctx: Ctx, buf: &mut Scratch) -> Result<(), MarshalError> {
+  use binmarshal::rw::{BitWriter, BufferBitWriter};
+
+  // A VecBitWriter's buffer can't be wiped or reused, so serialise into the buffer itself, growing it until
+  // it fits. Growing swaps in a new buffer rather than reallocating, so that the old one is wiped.
+  let mut len = buf.capacity().max(64);
+  loop {
+    if len > buf.capacity() {
+      *buf = Scratch(Vec::with_capacity(len));
+    }
+    buf.clear();
+    buf.resize(len, 0);
+
+    let mut writer = BufferBitWriter::new(&mut buf.0);
+    let written = match config.write(&mut writer, ctx.clone()) {
+      Ok(()) => writer.slice().len(),
+      Err(MarshalError::BufferTooSmall) => {
+        len *= 2;
+        continue;
+      },
+      Err(e) => return Err(e),
+    };
+    buf.truncate(written);
+    return Ok(());
+  }
+}
@@ -0,0 +1,88 @@
+use core::{marker::PhantomData, ops::{Deref, DerefMut}};
+
+use zeroize::Zeroize;
+
+use crate::GenericConfigurationProvider;
+
+/// Wraps a provider, overwriting its in-memory configuration when it's dropped so that secrets like Wi-Fi
+/// passwords don't linger in RAM. Derefs to the wrapped provider.
+///
+/// Copies taken out of the provider, e.g. with `snapshot`, aren't covered.
+pub struct ZeroizingProvider<Config, Provider>
+where
+  Config: Zeroize,
+  Provider: GenericConfigurationProvider<Config>
+{
+  provider: Provider,
+  marker: PhantomData<Config>
+}
+
+impl<Config, Provider> ZeroizingProvider<Config, Provider>
+where
+  Config: Zeroize,
+  Provider: GenericConfigurationProvider<Config>
+{
+  pub fn new(provider: Provider) -> Self {
+    Self { provider, marker: PhantomData }
+  }
+}
+
+impl<Config, Provider> GenericConfigurationProvider<Config> for ZeroizingProvider<Config, Provider>
+where
+  Config: Zeroize,
+  Provider: GenericConfigurationProvider<Config>
+{
+  type Error = Provider::Error;
+
+  fn commit(&mut self) -> Result<(), Self::Error> {
+    self.provider.commit()
+  }
+
+  fn current(&self) -> &Config {
+    self.provider.current()
+  }
+
+  fn current_mut(&mut self) -> &mut Config {
+    self.provider.current_mut()
+  }
+
+  fn is_dirty(&self) -> bool {
+    self.provider.is_dirty()
+  }
+
+  fn peek_stored(&mut self) -> Result<Config, Self::Error> {
+    self.provider.peek_stored()
+  }
+}
+
+impl<Config, Provider> Deref for ZeroizingProvider<Config, Provider>
+where
+  Config: Zeroize,
+  Provider: GenericConfigurationProvider<Config>
+{
+  type Target = Provider;
+
+  fn deref(&self) -> &Self::Target {
+    &self.provider
+  }
+}
+
+impl<Config, Provider> DerefMut for ZeroizingProvider<Config, Provider>
+where
+  Config: Zeroize,
+  Provider: GenericConfigurationProvider<Config>
+{
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    &mut self.provider
+  }
+}
+
+impl<Config, Provider> Drop for ZeroizingProvider<Config, Provider>
+where
+  Config: Zeroize,
+  Provider: GenericConfigurationProvider<Config>
+{
+  fn drop(&mut self) {
+    self.provider.current_mut().zeroize();
+  }
+}